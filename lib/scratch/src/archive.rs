@@ -0,0 +1,217 @@
+use crate::{
+    client::Client,
+    scratch3::ProjectJson as ProjectJson3,
+    types::ProjectJson as ProjectJson2,
+    Project,
+    ProjectData,
+    ScratchError,
+    ScratchResult,
+};
+use std::{
+    collections::{
+        HashMap,
+        HashSet,
+    },
+    fs::File,
+    io::{
+        Read,
+        Seek,
+        Write,
+    },
+    path::Path,
+};
+use zip::{
+    write::FileOptions,
+    CompressionMethod,
+    ZipArchive,
+    ZipWriter,
+};
+
+const PROJECT_JSON: &str = "project.json";
+
+/// The files inside a project archive, with `project.json` split out from the assets
+struct ArchiveFiles {
+    project_json: Vec<u8>,
+    assets: HashMap<String, Vec<u8>>,
+}
+
+fn read_archive<R: Read + Seek>(reader: R) -> ScratchResult<ArchiveFiles> {
+    let mut archive = ZipArchive::new(reader).map_err(ScratchError::Zip)?;
+    let mut project_json = None;
+    let mut assets = HashMap::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(ScratchError::Zip)?;
+        if file.is_dir() {
+            continue;
+        }
+
+        // Some tools nest everything in a folder, so only the file name is significant
+        let name = file.name().rsplit('/').next().unwrap_or("").to_string();
+        // The size in the header isn't trusted, so the buffer grows as the file is actually read
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).map_err(ScratchError::Io)?;
+
+        if name == PROJECT_JSON {
+            project_json = Some(data);
+        } else if !name.is_empty() {
+            assets.insert(name, data);
+        }
+    }
+
+    let project_json =
+        project_json.ok_or_else(|| ScratchError::Custom("Archive has no project.json".into()))?;
+
+    Ok(ArchiveFiles {
+        project_json,
+        assets,
+    })
+}
+
+fn file_stem(path: &Path) -> Option<String> {
+    path.file_stem().map(|s| s.to_string_lossy().into_owned())
+}
+
+pub fn read_sb3<R: Read + Seek>(reader: R) -> ScratchResult<Project> {
+    let files = read_archive(reader)?;
+    let data: ProjectJson3 =
+        serde_json::from_slice(&files.project_json).map_err(ScratchError::Json)?;
+
+    let mut project: Project = data.into();
+    project.assets = files.assets;
+    Ok(project)
+}
+
+/// Opens an `.sb3` file, naming the project after the file
+pub fn load_sb3<P: AsRef<Path>>(path: P) -> ScratchResult<Project> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(ScratchError::Io)?;
+    let mut project = read_sb3(file)?;
+    project.name = file_stem(path);
+    Ok(project)
+}
+
+/// Writes a Scratch 3 project and every asset it references, named by `md5ext`, as an `.sb3`
+pub fn write_sb3<W: Write + Seek>(
+    project: &Project,
+    client: &mut Client,
+    writer: W,
+) -> ScratchResult<W> {
+    let data = match &project.data {
+        ProjectData::Scratch3(data) => data,
+        ProjectData::Scratch2(_) => {
+            return Err(ScratchError::Custom(
+                "Only Scratch 3 projects can be written as .sb3".into(),
+            ));
+        }
+    };
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(writer);
+
+    zip.start_file(PROJECT_JSON, options)
+        .map_err(ScratchError::Zip)?;
+    serde_json::to_writer(&mut zip, data).map_err(ScratchError::Json)?;
+
+    let mut written = HashSet::new();
+    let files = data.targets.iter().flat_map(|t| {
+        t.costumes
+            .iter()
+            .map(|c| &c.md5ext)
+            .chain(t.sounds.iter().map(|s| &s.md5ext))
+    });
+
+    for md5ext in files {
+        if !written.insert(md5ext) {
+            continue;
+        }
+
+        let asset = project.load_asset(client, md5ext)?;
+        zip.start_file(md5ext.as_str(), options)
+            .map_err(ScratchError::Zip)?;
+        zip.write_all(&asset).map_err(ScratchError::Io)?;
+    }
+
+    zip.finish().map_err(ScratchError::Zip)
+}
+
+pub fn save_sb3<P: AsRef<Path>>(
+    project: &Project,
+    client: &mut Client,
+    path: P,
+) -> ScratchResult<()> {
+    let file = File::create(path).map_err(ScratchError::Io)?;
+    write_sb3(project, client, file)?;
+    Ok(())
+}
+
+const IMAGE_EXTENSIONS: &[&str] = &["svg", "png", "jpg", "jpeg", "gif", "bmp"];
+const SOUND_EXTENSIONS: &[&str] = &["wav", "mp3"];
+
+/// Finds the file an `.sb2` archive stores under its numbered ID, like `0.png` or `3.wav`. Costumes and sounds are
+/// numbered separately, so if the extension doesn't match `src`, only a file with one of `extensions` is taken.
+fn find_embedded<'a>(
+    assets: &'a HashMap<String, Vec<u8>>,
+    id: Option<i64>,
+    src: &str,
+    extensions: &[&str],
+) -> Option<&'a Vec<u8>> {
+    let id = id.filter(|id| *id >= 0)?.to_string();
+    let ext = Path::new(src).extension().and_then(|ext| ext.to_str());
+
+    ext.and_then(|ext| assets.get(&format!("{}.{}", id, ext)))
+        .or_else(|| {
+            assets
+                .iter()
+                .find(|(name, _)| {
+                    let name = Path::new(name);
+                    let kind = match name.extension().and_then(|ext| ext.to_str()) {
+                        Some(ext) => extensions.iter().any(|e| ext.eq_ignore_ascii_case(e)),
+                        None => false,
+                    };
+                    kind && name.file_stem().and_then(|s| s.to_str()) == Some(&id)
+                })
+                .map(|(_, data)| data)
+        })
+}
+
+pub fn read_sb2<R: Read + Seek>(reader: R) -> ScratchResult<Project> {
+    let files = read_archive(reader)?;
+    let data: ProjectJson2 =
+        serde_json::from_slice(&files.project_json).map_err(ScratchError::Json)?;
+
+    // Re-key the numbered files by the md5 names the rest of the crate uses
+    let mut assets = HashMap::new();
+    let costumes = data
+        .costumes
+        .iter()
+        .chain(data.children.iter().flat_map(|s| s.costumes.iter()))
+        .map(|c| (c.layer_id, &c.src, IMAGE_EXTENSIONS));
+    let sounds = data
+        .sounds
+        .iter()
+        .flatten()
+        .chain(data.children.iter().flat_map(|s| s.sounds.iter().flatten()))
+        .map(|s| (s.sound_id, &s.src, SOUND_EXTENSIONS));
+
+    for (id, src, extensions) in costumes.chain(sounds) {
+        let file =
+            find_embedded(&files.assets, id, src, extensions).or_else(|| files.assets.get(src));
+        if let Some(file) = file {
+            assets.insert(src.clone(), file.clone());
+        }
+    }
+
+    let mut project: Project = data.into();
+    project.assets = assets;
+    Ok(project)
+}
+
+/// Opens an `.sb2` file, naming the project after the file
+pub fn load_sb2<P: AsRef<Path>>(path: P) -> ScratchResult<Project> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(ScratchError::Io)?;
+    let mut project = read_sb2(file)?;
+    project.name = file_stem(path);
+    Ok(project)
+}
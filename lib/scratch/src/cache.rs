@@ -0,0 +1,188 @@
+use crate::{
+    ScratchError,
+    ScratchResult,
+};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// Overrides where the shared asset cache lives
+pub const CACHE_DIR_VAR: &str = "SCRATCH_NATIVE_CACHE";
+
+/// A directory of asset files shared by every project, each named by the md5 of its contents and its extension.
+/// Entries are checked against their names when read, so a damaged file is never handed out.
+#[derive(Debug, Clone)]
+pub struct AssetCache {
+    path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedAsset {
+    pub md5ext: String,
+    pub size: u64,
+}
+
+/// Checks that `data` hashes to the md5 in `md5ext`, ignoring the extension
+pub fn is_valid(md5ext: &str, data: &[u8]) -> bool {
+    let hash = md5ext.split('.').next().unwrap_or("");
+    hash.eq_ignore_ascii_case(&format!("{:x}", md5::compute(data)))
+}
+
+/// Rejects names that could escape the cache directory or collide with its temporary files
+fn check_name(md5ext: &str) -> ScratchResult<()> {
+    let valid = !md5ext.is_empty()
+        && !md5ext.starts_with('.')
+        && md5ext
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.');
+    if valid {
+        Ok(())
+    } else {
+        Err(ScratchError::Custom(
+            format!("{:?} is not an asset name", md5ext).into(),
+        ))
+    }
+}
+
+impl AssetCache {
+    pub fn new(path: PathBuf) -> Self {
+        AssetCache { path }
+    }
+
+    /// `$SCRATCH_NATIVE_CACHE`, or `scratch-native/assets` in the user's cache directory
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(CACHE_DIR_VAR) {
+            return Some(PathBuf::from(path));
+        }
+        dirs::cache_dir().map(|mut path| {
+            path.push("scratch-native");
+            path.push("assets");
+            path
+        })
+    }
+
+    pub fn open_default() -> Option<Self> {
+        Self::default_path().map(Self::new)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn entry_path(&self, md5ext: &str) -> PathBuf {
+        self.path.join(md5ext)
+    }
+
+    /// Gets an asset if it is cached and intact. Damaged entries are removed and reported as missing.
+    pub fn get(&self, md5ext: &str) -> ScratchResult<Option<Vec<u8>>> {
+        if check_name(md5ext).is_err() {
+            return Ok(None);
+        }
+        let path = self.entry_path(md5ext);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(ScratchError::Io(e)),
+        };
+
+        if is_valid(md5ext, &data) {
+            Ok(Some(data))
+        } else {
+            fs::remove_file(&path).map_err(ScratchError::Io)?;
+            Ok(None)
+        }
+    }
+
+    pub fn contains(&self, md5ext: &str) -> bool {
+        check_name(md5ext).is_ok() && self.entry_path(md5ext).is_file()
+    }
+
+    /// Stores an asset, refusing data that does not match its name.
+    /// The file is written under a temporary name first so readers never see half an asset.
+    pub fn insert(&self, md5ext: &str, data: &[u8]) -> ScratchResult<()> {
+        check_name(md5ext)?;
+        if !is_valid(md5ext, data) {
+            return Err(ScratchError::Custom(
+                format!("Data does not match the hash of {}", md5ext).into(),
+            ));
+        }
+
+        fs::create_dir_all(&self.path).map_err(ScratchError::Io)?;
+        let temp = self
+            .path
+            .join(format!(".{}.{}.tmp", md5ext, std::process::id()));
+        fs::write(&temp, data).map_err(ScratchError::Io)?;
+        fs::rename(&temp, self.entry_path(md5ext)).map_err(|e| {
+            let _ = fs::remove_file(&temp);
+            ScratchError::Io(e)
+        })
+    }
+
+    /// Lists every entry, sorted by name. Entries are not verified.
+    pub fn list(&self) -> ScratchResult<Vec<CachedAsset>> {
+        let entries = match fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(ScratchError::Io(e)),
+        };
+
+        let mut assets = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(ScratchError::Io)?;
+            let metadata = entry.metadata().map_err(ScratchError::Io)?;
+            let md5ext = entry.file_name().to_string_lossy().into_owned();
+            if metadata.is_file() && check_name(&md5ext).is_ok() {
+                assets.push(CachedAsset {
+                    md5ext,
+                    size: metadata.len(),
+                });
+            }
+        }
+        assets.sort_by(|a, b| a.md5ext.cmp(&b.md5ext));
+        Ok(assets)
+    }
+
+    /// Gets the names of the entries whose contents no longer match their names
+    pub fn verify(&self) -> ScratchResult<Vec<String>> {
+        let mut damaged = Vec::new();
+        for asset in self.list()? {
+            let data = fs::read(self.entry_path(&asset.md5ext)).map_err(ScratchError::Io)?;
+            if !is_valid(&asset.md5ext, &data) {
+                damaged.push(asset.md5ext);
+            }
+        }
+        Ok(damaged)
+    }
+
+    /// Removes damaged entries, entries `keep` rejects, and temporary files left by interrupted writes.
+    /// Returns the names of the removed entries.
+    pub fn prune<F: Fn(&str) -> bool>(&self, keep: F) -> ScratchResult<Vec<String>> {
+        let mut removed = Vec::new();
+        for md5ext in self.verify()? {
+            fs::remove_file(self.entry_path(&md5ext)).map_err(ScratchError::Io)?;
+            removed.push(md5ext);
+        }
+        for asset in self.list()? {
+            if !keep(&asset.md5ext) {
+                fs::remove_file(self.entry_path(&asset.md5ext)).map_err(ScratchError::Io)?;
+                removed.push(asset.md5ext);
+            }
+        }
+
+        if let Ok(entries) = fs::read_dir(&self.path) {
+            for entry in entries.filter_map(Result::ok) {
+                if entry.file_name().to_string_lossy().ends_with(".tmp") {
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+        }
+
+        removed.sort();
+        Ok(removed)
+    }
+}
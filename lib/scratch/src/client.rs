@@ -1,4 +1,23 @@
+mod transport;
+
+pub use self::transport::{
+    DirTransport,
+    HttpTransport,
+    MemoryTransport,
+    Transport,
+};
 use crate::{
+    cache::{
+        self,
+        AssetCache,
+    },
+    download::{
+        self,
+        DownloadOptions,
+        DownloadProgress,
+        Downloads,
+    },
+    manifest::Manifest,
     scratch3::{
         NetworkProject as NetworkProject3,
         ProjectJson as ProjectJson3,
@@ -8,52 +27,197 @@ use crate::{
         ProjectJson,
     },
     NetworkProject,
+    Project,
+    ProjectData,
     ScratchError,
     ScratchResult,
 };
+use std::time::Duration;
+
+/// Base URLs of the Scratch services, without trailing slashes
+#[derive(Debug, Clone)]
+pub struct Endpoints {
+    pub api: String,
+    pub projects: String,
+    pub legacy_projects: String,
+    pub assets: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Endpoints {
+            api: "https://api.scratch.mit.edu".into(),
+            projects: "https://projects.scratch.mit.edu".into(),
+            legacy_projects: "https://cdn.projects.scratch.mit.edu".into(),
+            assets: "https://cdn.assets.scratch.mit.edu".into(),
+        }
+    }
+}
+
+impl Endpoints {
+    /// Points every service at one server, for local mirrors and test stand-ins
+    pub fn from_base(base: &str) -> Self {
+        let base = base.trim_end_matches('/');
+        Endpoints {
+            api: base.into(),
+            projects: base.into(),
+            legacy_projects: base.into(),
+            assets: base.into(),
+        }
+    }
+}
+
+/// Where and how a `Client` makes its requests
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub endpoints: Endpoints,
+    pub user_agent: String,
+    /// Limits each whole request, `None` to wait forever
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            endpoints: Endpoints::default(),
+            user_agent: concat!("scratch-native/", env!("CARGO_PKG_VERSION")).into(),
+            timeout: Some(Duration::from_secs(30)),
+            connect_timeout: Some(Duration::from_secs(10)),
+        }
+    }
+}
 
 pub struct Client {
-    handle: reqwest::Client,
+    transport: Box<dyn Transport>,
+    endpoints: Endpoints,
+    cache: Option<AssetCache>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Client {
+    /// Talks to the real Scratch servers, keeping assets in the shared cache if there is one
     pub fn new() -> Self {
+        let client = Self::with_endpoints(Endpoints::default());
+        match AssetCache::open_default() {
+            Some(cache) => client.with_cache(cache),
+            None => client,
+        }
+    }
+
+    /// Makes an HTTP client without an asset cache
+    pub fn with_endpoints(endpoints: Endpoints) -> Self {
+        Self::with_config(ClientConfig {
+            endpoints,
+            ..ClientConfig::default()
+        })
+        .expect("Default Client Config")
+    }
+
+    /// Makes an HTTP client without an asset cache, failing if the config can't be used
+    pub fn with_config(config: ClientConfig) -> ScratchResult<Self> {
+        let transport = HttpTransport::new(&config)?;
+        Ok(Self::with_transport(transport, config.endpoints))
+    }
+
+    /// Makes a client without an asset cache that fetches through anything, e.g. local fixtures
+    pub fn with_transport<T: Transport + 'static>(transport: T, endpoints: Endpoints) -> Self {
         Client {
-            handle: reqwest::Client::new(),
+            transport: Box::new(transport),
+            endpoints,
+            cache: None,
         }
     }
 
+    pub fn with_cache(mut self, cache: AssetCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
+    pub fn cache(&self) -> Option<&AssetCache> {
+        self.cache.as_ref()
+    }
+
     pub fn get_url(&mut self, url: &str) -> ScratchResult<Vec<u8>> {
-        let mut buf = Vec::new();
-        self.handle
-            .get(url)
-            .send()
-            .map_err(|_e| ScratchError::Network)?
-            .copy_to(&mut buf)
-            .map_err(|_e| ScratchError::Network)?;
-        Ok(buf)
+        self.transport.get(url)
     }
 
     pub fn get_stats(&mut self, project: &NetworkProject) -> ScratchResult<ProjectInfoJson> {
-        serde_json::from_slice(&self.get_url(&project.get_stats_url())?)
-            .map_err(|e| ScratchError::Json(e))
+        let url = project.get_stats_url(&self.endpoints);
+        serde_json::from_slice(&self.get_url(&url)?).map_err(ScratchError::Json)
+    }
+
+    pub fn get_stats_3(&mut self, project: &NetworkProject3) -> ScratchResult<ProjectInfoJson> {
+        let url = project.get_stats_url(&self.endpoints);
+        serde_json::from_slice(&self.get_url(&url)?).map_err(ScratchError::Json)
     }
 
     pub fn get_data(&mut self, project: &NetworkProject) -> ScratchResult<ProjectJson> {
-        serde_json::from_slice(&self.get_url(&project.get_data_url())?)
-            .map_err(|e| ScratchError::Json(e))
+        let url = project.get_data_url(&self.endpoints);
+        serde_json::from_slice(&self.get_url(&url)?).map_err(ScratchError::Json)
+    }
+
+    /// Gets the raw project body, asking the info endpoint for a `project_token` first
+    fn get_project_body(
+        &mut self,
+        project: &NetworkProject3,
+    ) -> ScratchResult<(ProjectInfoJson, Vec<u8>)> {
+        let info = self.get_stats_3(project)?;
+        let url = project.get_data_url(&self.endpoints, info.project_token.as_deref());
+        Ok((info, self.get_url(&url)?))
     }
 
     pub fn get_data_3(&mut self, project: &NetworkProject3) -> ScratchResult<ProjectJson3> {
-        serde_json::from_slice(&self.get_url(&project.get_data_url())?)
-            .map_err(|e| ScratchError::Json(e))
+        serde_json::from_slice(&self.get_project_body(project)?.1).map_err(ScratchError::Json)
+    }
+
+    /// Gets a project's JSON without knowing in advance whether it is Scratch 2 or Scratch 3
+    pub fn get_project(&mut self, project: &NetworkProject) -> ScratchResult<ProjectData> {
+        ProjectData::from_slice(&self.get_project_body(&project.as_scratch3())?.1)
     }
 
+    /// Gets a project along with the info it was fetched with, ready to be saved
+    pub fn get_full_project(&mut self, project: &NetworkProject) -> ScratchResult<Project> {
+        let (info, body) = self.get_project_body(&project.as_scratch3())?;
+        let mut full: Project = ProjectData::from_slice(&body)?.into();
+        full.manifest = Manifest::fetched(project.id(), Some(&info));
+        Ok(full)
+    }
+
+    /// Gets an asset from the cache, downloading and caching it if it is missing
     pub fn get_asset(&mut self, file: &str) -> ScratchResult<Vec<u8>> {
-        let url = format!(
-            "https://cdn.assets.scratch.mit.edu/internalapi/asset/{}/get",
-            file
-        );
-        self.get_url(&url)
+        self.fetch_asset(file)
+    }
+
+    /// Gets many assets like `get_asset`, several at a time, without stopping at the first failure
+    pub fn get_assets<P>(&self, files: &[&str], options: &DownloadOptions, progress: P) -> Downloads
+    where
+        P: Fn(&DownloadProgress) + Sync,
+    {
+        download::download(files, options, |file| self.fetch_asset(file), progress)
+    }
+
+    /// The cache is only a shortcut: if it can't be read or written, the asset is downloaded and returned anyway
+    fn fetch_asset(&self, file: &str) -> ScratchResult<Vec<u8>> {
+        let cache = self.cache.as_ref();
+        if let Some(data) = cache.and_then(|c| c.get(file).ok().flatten()) {
+            return Ok(data);
+        }
+
+        let url = format!("{}/internalapi/asset/{}/get", self.endpoints.assets, file);
+        let data = self.transport.get(&url)?;
+        if let Some(cache) = cache.filter(|_| cache::is_valid(file, &data)) {
+            let _ = cache.insert(file, &data);
+        }
+        Ok(data)
     }
 }
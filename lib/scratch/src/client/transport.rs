@@ -0,0 +1,151 @@
+use crate::{
+    client::ClientConfig,
+    ScratchError,
+    ScratchResult,
+};
+use reqwest::header::{
+    HeaderMap,
+    HeaderValue,
+    USER_AGENT,
+};
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::PathBuf,
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+
+/// Fetches the bodies behind the URLs `Client` builds.
+/// Implementations must fail with `ScratchError::Status(404)` when there is nothing at a URL.
+pub trait Transport: Send + Sync {
+    fn get(&self, url: &str) -> ScratchResult<Vec<u8>>;
+}
+
+/// Lets a transport be shared, e.g. to inspect a `MemoryTransport` after handing it to a `Client`
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn get(&self, url: &str) -> ScratchResult<Vec<u8>> {
+        (**self).get(url)
+    }
+}
+
+/// Strips the scheme and host from a URL, leaving the path and query
+fn url_path(url: &str) -> &str {
+    match url.find("://") {
+        Some(i) => {
+            let rest = &url[i + 3..];
+            rest.find('/').map(|i| &rest[i..]).unwrap_or("/")
+        }
+        None => url,
+    }
+}
+
+pub struct HttpTransport {
+    handle: reqwest::Client,
+}
+
+impl HttpTransport {
+    pub fn new(config: &ClientConfig) -> ScratchResult<Self> {
+        let mut headers = HeaderMap::new();
+        let user_agent = HeaderValue::from_str(&config.user_agent)
+            .map_err(|_| ScratchError::Custom("Invalid User Agent".into()))?;
+        headers.insert(USER_AGENT, user_agent);
+
+        let handle = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(config.timeout)
+            .connect_timeout(config.connect_timeout)
+            .build()
+            .map_err(|_e| ScratchError::Network)?;
+        Ok(HttpTransport { handle })
+    }
+}
+
+impl Transport for HttpTransport {
+    fn get(&self, url: &str) -> ScratchResult<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.handle
+            .get(url)
+            .send()
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| match e.status() {
+                Some(status) => ScratchError::Status(status.as_u16()),
+                None => ScratchError::Network,
+            })?
+            .copy_to(&mut buf)
+            .map_err(|_e| ScratchError::Network)?;
+        Ok(buf)
+    }
+}
+
+/// Serves files from a directory laid out like the server's URL paths, for mirrors kept on disk.
+/// Query strings are ignored, as they only carry short-lived project tokens,
+/// so `https://api.scratch.mit.edu/projects/42/` is read from `projects/42`.
+pub struct DirTransport {
+    root: PathBuf,
+}
+
+impl DirTransport {
+    pub fn new(root: PathBuf) -> Self {
+        DirTransport { root }
+    }
+}
+
+impl Transport for DirTransport {
+    fn get(&self, url: &str) -> ScratchResult<Vec<u8>> {
+        let path = url_path(url).split('?').next().unwrap_or("");
+        let mut file = self.root.clone();
+        for part in path.split('/').filter(|p| !p.is_empty()) {
+            if part == ".." {
+                return Err(ScratchError::Status(404));
+            }
+            file.push(part);
+        }
+
+        match std::fs::read(&file) {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == ErrorKind::NotFound => Err(ScratchError::Status(404)),
+            Err(e) => Err(ScratchError::Io(e)),
+        }
+    }
+}
+
+/// Serves fixed bodies keyed by URL path and query, like `/42?token=abc`, and records every request
+#[derive(Default)]
+pub struct MemoryTransport {
+    routes: HashMap<String, Vec<u8>>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with<B: Into<Vec<u8>>>(mut self, path: &str, body: B) -> Self {
+        self.insert(path, body);
+        self
+    }
+
+    pub fn insert<B: Into<Vec<u8>>>(&mut self, path: &str, body: B) {
+        self.routes.insert(path.to_string(), body.into());
+    }
+
+    /// The paths requested so far, in order
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn get(&self, url: &str) -> ScratchResult<Vec<u8>> {
+        let path = url_path(url);
+        self.requests.lock().unwrap().push(path.to_string());
+        self.routes
+            .get(path)
+            .cloned()
+            .ok_or(ScratchError::Status(404))
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{
+    scratch3::{
+        convert::convert,
+        ProjectJson as ProjectJson3,
+        TargetBlock,
+    },
+    target::Target,
+    ProjectData,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// How many blocks of each opcode a project uses, and which targets can build them
+#[derive(Debug, Clone, Serialize)]
+pub struct Coverage {
+    /// The targets checked, in the order they were added
+    pub targets: Vec<String>,
+    pub sprites: Vec<SpriteCoverage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpriteCoverage {
+    pub name: String,
+    /// Sorted by opcode
+    pub opcodes: Vec<OpcodeCoverage>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OpcodeCoverage {
+    pub opcode: String,
+    pub count: usize,
+    /// The names of the targets that support this opcode
+    pub supported: Vec<String>,
+}
+
+impl SpriteCoverage {
+    pub fn blocks(&self) -> usize {
+        self.opcodes.iter().map(|o| o.count).sum()
+    }
+
+    /// How many of this sprite's blocks a target supports
+    pub fn supported_blocks(&self, target: &str) -> usize {
+        self.opcodes
+            .iter()
+            .filter(|o| o.supported.iter().any(|t| t == target))
+            .map(|o| o.count)
+            .sum()
+    }
+}
+
+impl Coverage {
+    /// Counts the opcodes of every target in the project, stage first.
+    /// Scratch 2 projects are counted after conversion, so the opcodes are always Scratch 3 ones.
+    pub fn new(data: &ProjectData) -> Self {
+        let sprites = match data {
+            ProjectData::Scratch2(data) => count_opcodes(&convert(data).project),
+            ProjectData::Scratch3(data) => count_opcodes(data),
+        };
+        Coverage {
+            targets: Vec::new(),
+            sprites,
+        }
+    }
+
+    pub fn target<T: Target>(self) -> Self {
+        self.target_with(T::name(), T::supports)
+    }
+
+    /// Checks a target that isn't a [`Target`], or that should be shown under a different name
+    pub fn target_with<F: Fn(&str) -> bool>(mut self, name: &str, supports: F) -> Self {
+        for opcode in self.sprites.iter_mut().flat_map(|s| s.opcodes.iter_mut()) {
+            if supports(&opcode.opcode) {
+                opcode.supported.push(name.to_string());
+            }
+        }
+        self.targets.push(name.to_string());
+        self
+    }
+}
+
+fn count_opcodes(project: &ProjectJson3) -> Vec<SpriteCoverage> {
+    let mut targets = project.targets.iter().collect::<Vec<_>>();
+    targets.sort_by_key(|t| !t.is_stage);
+
+    targets
+        .into_iter()
+        .map(|target| {
+            let mut counts = BTreeMap::new();
+            // Shadows are menus and prototypes, which are part of the block that owns them
+            let blocks = target.blocks.values().filter_map(TargetBlock::block);
+            for block in blocks.filter(|b| !b.shadow) {
+                *counts.entry(block.opcode.clone()).or_insert(0) += 1;
+            }
+
+            SpriteCoverage {
+                name: target.name.clone(),
+                opcodes: counts
+                    .into_iter()
+                    .map(|(opcode, count)| OpcodeCoverage {
+                        opcode,
+                        count,
+                        supported: Vec::new(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
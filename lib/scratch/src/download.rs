@@ -0,0 +1,180 @@
+use crate::{
+    ScratchError,
+    ScratchResult,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+        Mutex,
+    },
+    time::Duration,
+};
+
+/// How many assets are fetched at once and how hard failures are retried
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    jobs: usize,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DownloadOptions {
+    pub fn new() -> Self {
+        DownloadOptions {
+            jobs: 8,
+            retries: 3,
+            backoff: Duration::from_millis(250),
+        }
+    }
+
+    /// The most downloads running at once, at least 1
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// How many times a failed download is tried again
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// The wait before the first retry, doubled for each one after it
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+}
+
+/// Reported once per asset as it finishes, from whichever thread fetched it
+#[derive(Debug)]
+pub struct DownloadProgress<'a> {
+    pub name: &'a str,
+    /// Finished assets so far, including this one
+    pub done: usize,
+    pub total: usize,
+    /// Set if the asset failed after every retry
+    pub error: Option<&'a ScratchError>,
+}
+
+/// A progress callback that can be stored and shared with download threads
+pub type ProgressFn = dyn Fn(&DownloadProgress) + Sync;
+
+#[derive(Debug, Default)]
+pub struct Downloads {
+    pub assets: HashMap<String, Vec<u8>>,
+    /// Sorted by name
+    pub failed: Vec<(String, ScratchError)>,
+}
+
+impl Downloads {
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Gets the assets, or one error naming every asset that failed
+    pub fn into_result(self) -> ScratchResult<HashMap<String, Vec<u8>>> {
+        if self.failed.is_empty() {
+            return Ok(self.assets);
+        }
+
+        let failed = self
+            .failed
+            .iter()
+            .map(|(name, e)| format!("{} ({:?})", name, e))
+            .collect::<Vec<_>>();
+        Err(ScratchError::Custom(
+            format!(
+                "Failed to download {} asset(s): {}",
+                failed.len(),
+                failed.join(", ")
+            )
+            .into(),
+        ))
+    }
+}
+
+/// Network and IO errors and server errors may go away on their own. An asset the server says isn't there won't, so
+/// it isn't retried.
+fn is_transient(error: &ScratchError) -> bool {
+    match error {
+        ScratchError::Network | ScratchError::Io(_) => true,
+        ScratchError::Status(status) => *status >= 500,
+        _ => false,
+    }
+}
+
+fn fetch_with_retry<F>(name: &str, options: &DownloadOptions, fetch: &F) -> ScratchResult<Vec<u8>>
+where
+    F: Fn(&str) -> ScratchResult<Vec<u8>>,
+{
+    let mut wait = options.backoff;
+    let mut attempt = 0;
+    loop {
+        match fetch(name) {
+            Err(e) if attempt < options.retries && is_transient(&e) => {
+                std::thread::sleep(wait);
+                wait *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Fetches every named asset on up to `options.jobs` threads, carrying on past failures.
+/// Duplicate names are only fetched once.
+pub fn download<F, P>(names: &[&str], options: &DownloadOptions, fetch: F, progress: P) -> Downloads
+where
+    F: Fn(&str) -> ScratchResult<Vec<u8>> + Sync,
+    P: Fn(&DownloadProgress) + Sync,
+{
+    let mut names = names.to_vec();
+    names.sort_unstable();
+    names.dedup();
+
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let downloads = Mutex::new(Downloads::default());
+
+    let worker = || {
+        while let Some(&name) = names.get(next.fetch_add(1, Ordering::SeqCst)) {
+            let result = fetch_with_retry(name, options, &fetch);
+            let done = done.fetch_add(1, Ordering::SeqCst) + 1;
+            progress(&DownloadProgress {
+                name,
+                done,
+                total: names.len(),
+                error: result.as_ref().err(),
+            });
+
+            let mut downloads = downloads.lock().unwrap();
+            match result {
+                Ok(data) => {
+                    downloads.assets.insert(name.to_string(), data);
+                }
+                Err(e) => downloads.failed.push((name.to_string(), e)),
+            }
+        }
+    };
+
+    std::thread::scope(|scope| {
+        for _ in 0..options.jobs.min(names.len()) {
+            scope.spawn(worker);
+        }
+    });
+
+    let mut downloads = downloads.into_inner().unwrap();
+    downloads.failed.sort_by(|a, b| a.0.cmp(&b.0));
+    downloads
+}
@@ -0,0 +1,508 @@
+use crate::ProjectData;
+use serde_json::Value;
+
+mod lower;
+
+pub use self::lower::{
+    lower_opcode,
+    lowers,
+};
+
+/// A whole project, lowered from either Scratch 2 or Scratch 3
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub stage: Sprite,
+    pub sprites: Vec<Sprite>,
+    pub broadcasts: Vec<Broadcast>,
+}
+
+impl Program {
+    /// The stage followed by every sprite
+    pub fn targets(&self) -> impl Iterator<Item = &Sprite> {
+        std::iter::once(&self.stage).chain(self.sprites.iter())
+    }
+
+    /// The variable a block in `sprite` refers to, looking at the sprite's own variables before the stage's
+    pub fn variable<'a>(&'a self, sprite: &'a Sprite, data: &DataRef) -> Option<&'a Variable> {
+        sprite.variable(data).or_else(|| self.stage.variable(data))
+    }
+
+    /// The list a block in `sprite` refers to, looking at the sprite's own lists before the stage's
+    pub fn list<'a>(&'a self, sprite: &'a Sprite, data: &DataRef) -> Option<&'a List> {
+        sprite.list(data).or_else(|| self.stage.list(data))
+    }
+}
+
+impl From<&ProjectData> for Program {
+    fn from(data: &ProjectData) -> Self {
+        match data {
+            ProjectData::Scratch2(data) => lower::lower_scratch2(data),
+            ProjectData::Scratch3(data) => lower::lower_scratch3(data),
+        }
+    }
+}
+
+/// The stage or a sprite
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    pub name: String,
+    pub is_stage: bool,
+    pub x: f64,
+    pub y: f64,
+    pub direction: f64,
+    pub size: f64,
+    pub visible: bool,
+    pub current_costume: usize,
+    pub costumes: Vec<Costume>,
+    pub sounds: Vec<Sound>,
+    pub variables: Vec<Variable>,
+    pub lists: Vec<List>,
+    pub scripts: Vec<Script>,
+    pub procedures: Vec<Procedure>,
+}
+
+impl Sprite {
+    /// The custom block a call in this sprite runs
+    pub fn procedure(&self, proccode: &str) -> Option<&Procedure> {
+        self.procedures.iter().find(|p| p.proccode == proccode)
+    }
+
+    /// A variable declared on this target, by ID or else by name like Scratch does
+    pub fn variable(&self, data: &DataRef) -> Option<&Variable> {
+        let variables = &self.variables;
+        variables
+            .iter()
+            .find(|v| v.id == data.id)
+            .or_else(|| variables.iter().find(|v| v.name == data.name))
+    }
+
+    /// A list declared on this target, by ID or else by name
+    pub fn list(&self, data: &DataRef) -> Option<&List> {
+        let lists = &self.lists;
+        lists
+            .iter()
+            .find(|l| l.id == data.id)
+            .or_else(|| lists.iter().find(|l| l.name == data.name))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Costume {
+    pub name: String,
+    pub md5ext: String,
+    pub rotation_center_x: f64,
+    pub rotation_center_y: f64,
+    pub bitmap_resolution: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sound {
+    pub name: String,
+    pub md5ext: String,
+}
+
+/// Where a variable or list is declared
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    /// Declared on the stage and shared by every sprite
+    Stage,
+    /// Declared on a sprite, with one copy per clone
+    Sprite,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub id: String,
+    pub name: String,
+    pub value: Value,
+    pub is_cloud: bool,
+    pub scope: Scope,
+}
+
+#[derive(Debug, Clone)]
+pub struct List {
+    pub id: String,
+    pub name: String,
+    pub items: Vec<Value>,
+    pub scope: Scope,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Broadcast {
+    pub id: String,
+    pub name: String,
+}
+
+/// A variable or list as it is referred to from a block
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataRef {
+    pub id: String,
+    pub name: String,
+}
+
+/// A script and the event that starts it
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub hat: Hat,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Hat {
+    GreenFlag,
+    KeyPressed(String),
+    Clicked,
+    BackdropSwitchesTo(String),
+    BroadcastReceived(String),
+    GreaterThan {
+        sensor: String,
+        value: Expr,
+    },
+    CloneStart,
+    /// A hat no target understands yet, by opcode
+    Other(String),
+}
+
+/// A custom block definition
+#[derive(Debug, Clone)]
+pub struct Procedure {
+    /// The block's text with `%s`, `%n` and `%b` in place of its arguments, which identifies it within a sprite
+    pub proccode: String,
+    pub parameters: Vec<Parameter>,
+    /// Run without screen refresh
+    pub warp: bool,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub id: String,
+    pub name: String,
+    pub default: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    // Motion
+    MoveSteps(Expr),
+    TurnRight(Expr),
+    TurnLeft(Expr),
+    PointInDirection(Expr),
+    PointTowards(Expr),
+    GoTo(Expr),
+    GoToXY {
+        x: Expr,
+        y: Expr,
+    },
+    GlideToXY {
+        secs: Expr,
+        x: Expr,
+        y: Expr,
+    },
+    ChangeXBy(Expr),
+    SetX(Expr),
+    ChangeYBy(Expr),
+    SetY(Expr),
+    BounceOffEdge,
+    SetRotationStyle(String),
+
+    // Looks
+    Say {
+        message: Expr,
+        secs: Option<Expr>,
+    },
+    Think {
+        message: Expr,
+        secs: Option<Expr>,
+    },
+    Show,
+    Hide,
+    SwitchCostume(Expr),
+    NextCostume,
+    SwitchBackdrop {
+        backdrop: Expr,
+        wait: bool,
+    },
+    NextBackdrop,
+    ChangeEffectBy {
+        effect: String,
+        change: Expr,
+    },
+    SetEffectTo {
+        effect: String,
+        value: Expr,
+    },
+    ClearEffects,
+    ChangeSizeBy(Expr),
+    SetSizeTo(Expr),
+
+    // Sound
+    PlaySound {
+        sound: Expr,
+        wait: bool,
+    },
+    StopAllSounds,
+    ChangeVolumeBy(Expr),
+    SetVolumeTo(Expr),
+    PlayNote {
+        note: Expr,
+        beats: Expr,
+    },
+    Rest(Expr),
+
+    // Events
+    Broadcast {
+        message: Expr,
+        wait: bool,
+    },
+
+    // Control
+    Wait(Expr),
+    Repeat {
+        times: Expr,
+        body: Vec<Statement>,
+    },
+    Forever(Vec<Statement>),
+    If {
+        condition: Expr,
+        then: Vec<Statement>,
+        otherwise: Vec<Statement>,
+    },
+    WaitUntil(Expr),
+    RepeatUntil {
+        condition: Expr,
+        body: Vec<Statement>,
+    },
+    Stop(String),
+    CreateClone(Expr),
+    DeleteClone,
+
+    // Sensing
+    Ask(Expr),
+    ResetTimer,
+
+    // Data
+    SetVariable {
+        variable: DataRef,
+        value: Expr,
+    },
+    ChangeVariable {
+        variable: DataRef,
+        value: Expr,
+    },
+    AddToList {
+        list: DataRef,
+        item: Expr,
+    },
+    DeleteOfList {
+        list: DataRef,
+        index: Expr,
+    },
+    DeleteAllOfList(DataRef),
+    InsertAtList {
+        list: DataRef,
+        index: Expr,
+        item: Expr,
+    },
+    ReplaceItemOfList {
+        list: DataRef,
+        index: Expr,
+        item: Expr,
+    },
+
+    // Procedures
+    Call {
+        proccode: String,
+        args: Vec<Expr>,
+    },
+
+    /// A block no target understands yet, with its inputs and fields lowered as they are
+    Other {
+        opcode: String,
+        inputs: Vec<(String, Expr)>,
+        fields: Vec<(String, String)>,
+    },
+}
+
+impl Statement {
+    /// The Scratch 3 opcode this statement was lowered from
+    pub fn opcode(&self) -> &str {
+        match self {
+            Statement::MoveSteps(_) => "motion_movesteps",
+            Statement::TurnRight(_) => "motion_turnright",
+            Statement::TurnLeft(_) => "motion_turnleft",
+            Statement::PointInDirection(_) => "motion_pointindirection",
+            Statement::PointTowards(_) => "motion_pointtowards",
+            Statement::GoTo(_) => "motion_goto",
+            Statement::GoToXY { .. } => "motion_gotoxy",
+            Statement::GlideToXY { .. } => "motion_glidesecstoxy",
+            Statement::ChangeXBy(_) => "motion_changexby",
+            Statement::SetX(_) => "motion_setx",
+            Statement::ChangeYBy(_) => "motion_changeyby",
+            Statement::SetY(_) => "motion_sety",
+            Statement::BounceOffEdge => "motion_ifonedgebounce",
+            Statement::SetRotationStyle(_) => "motion_setrotationstyle",
+            Statement::Say { secs: Some(_), .. } => "looks_sayforsecs",
+            Statement::Say { secs: None, .. } => "looks_say",
+            Statement::Think { secs: Some(_), .. } => "looks_thinkforsecs",
+            Statement::Think { secs: None, .. } => "looks_think",
+            Statement::Show => "looks_show",
+            Statement::Hide => "looks_hide",
+            Statement::SwitchCostume(_) => "looks_switchcostumeto",
+            Statement::NextCostume => "looks_nextcostume",
+            Statement::SwitchBackdrop { wait: false, .. } => "looks_switchbackdropto",
+            Statement::SwitchBackdrop { wait: true, .. } => "looks_switchbackdroptoandwait",
+            Statement::NextBackdrop => "looks_nextbackdrop",
+            Statement::ChangeEffectBy { .. } => "looks_changeeffectby",
+            Statement::SetEffectTo { .. } => "looks_seteffectto",
+            Statement::ClearEffects => "looks_cleargraphiceffects",
+            Statement::ChangeSizeBy(_) => "looks_changesizeby",
+            Statement::SetSizeTo(_) => "looks_setsizeto",
+            Statement::PlaySound { wait: false, .. } => "sound_play",
+            Statement::PlaySound { wait: true, .. } => "sound_playuntildone",
+            Statement::StopAllSounds => "sound_stopallsounds",
+            Statement::ChangeVolumeBy(_) => "sound_changevolumeby",
+            Statement::SetVolumeTo(_) => "sound_setvolumeto",
+            Statement::PlayNote { .. } => "music_playNoteForBeats",
+            Statement::Rest(_) => "music_restForBeats",
+            Statement::Broadcast { wait: false, .. } => "event_broadcast",
+            Statement::Broadcast { wait: true, .. } => "event_broadcastandwait",
+            Statement::Wait(_) => "control_wait",
+            Statement::Repeat { .. } => "control_repeat",
+            Statement::Forever(_) => "control_forever",
+            Statement::If { otherwise, .. } if otherwise.is_empty() => "control_if",
+            Statement::If { .. } => "control_if_else",
+            Statement::WaitUntil(_) => "control_wait_until",
+            Statement::RepeatUntil { .. } => "control_repeat_until",
+            Statement::Stop(_) => "control_stop",
+            Statement::CreateClone(_) => "control_create_clone_of",
+            Statement::DeleteClone => "control_delete_this_clone",
+            Statement::Ask(_) => "sensing_askandwait",
+            Statement::ResetTimer => "sensing_resettimer",
+            Statement::SetVariable { .. } => "data_setvariableto",
+            Statement::ChangeVariable { .. } => "data_changevariableby",
+            Statement::AddToList { .. } => "data_addtolist",
+            Statement::DeleteOfList { .. } => "data_deleteoflist",
+            Statement::DeleteAllOfList(_) => "data_deletealloflist",
+            Statement::InsertAtList { .. } => "data_insertatlist",
+            Statement::ReplaceItemOfList { .. } => "data_replaceitemoflist",
+            Statement::Call { .. } => "procedures_call",
+            Statement::Other { opcode, .. } => opcode,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Mod,
+    Random,
+    LessThan,
+    Equals,
+    GreaterThan,
+    And,
+    Or,
+    Join,
+    LetterOf,
+    Contains,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// Literal text, which Scratch converts to a number where one is needed
+    Literal(String),
+    Variable(DataRef),
+    ListContents(DataRef),
+    /// An argument of the procedure being defined, by name
+    Argument(String),
+
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Not(Box<Expr>),
+    Round(Box<Expr>),
+    Length(Box<Expr>),
+    MathOp {
+        op: String,
+        num: Box<Expr>,
+    },
+
+    // Motion and looks
+    XPosition,
+    YPosition,
+    Direction,
+    Size,
+    Costume {
+        number: bool,
+    },
+    Backdrop {
+        number: bool,
+    },
+    Volume,
+    Tempo,
+
+    // Sensing
+    Touching(Box<Expr>),
+    DistanceTo(Box<Expr>),
+    KeyPressed(Box<Expr>),
+    MouseDown,
+    MouseX,
+    MouseY,
+    Loudness,
+    Timer,
+    Answer,
+    Of {
+        property: String,
+        object: Box<Expr>,
+    },
+    Current(String),
+    DaysSince2000,
+    Username,
+
+    // Data
+    ItemOfList {
+        list: DataRef,
+        index: Box<Expr>,
+    },
+    ItemNumOfList {
+        list: DataRef,
+        item: Box<Expr>,
+    },
+    LengthOfList(DataRef),
+    ListContains {
+        list: DataRef,
+        item: Box<Expr>,
+    },
+
+    /// A reporter no target understands yet
+    Other {
+        opcode: String,
+        inputs: Vec<(String, Expr)>,
+        fields: Vec<(String, String)>,
+    },
+}
+
+impl Expr {
+    /// The literal text, if this is a literal
+    pub fn as_literal(&self) -> Option<&str> {
+        match self {
+            Expr::Literal(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+/// What a Scratch 3 opcode lowers to on its own, with none of its inputs or fields
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lowered {
+    /// `procedures_definition`, which becomes a `Procedure` rather than a hat
+    Definition,
+    Hat(Hat),
+    Statement(Statement),
+    Reporter(Expr),
+}
@@ -0,0 +1,565 @@
+use super::{
+    BinaryOp,
+    Broadcast,
+    Costume,
+    DataRef,
+    Expr,
+    Hat,
+    List,
+    Lowered,
+    Parameter,
+    Procedure,
+    Program,
+    Scope,
+    Script,
+    Sound,
+    Sprite,
+    Statement,
+    Variable,
+};
+use crate::{
+    scratch3::{
+        convert::convert,
+        script::{
+            build_scripts,
+            Block,
+            Expr as BlockExpr,
+        },
+        Primitive,
+        ProjectJson as ProjectJson3,
+        TargetJson,
+    },
+    types::ProjectJson as ProjectJson2,
+};
+use std::collections::HashMap;
+
+/// Scratch 2 projects are lowered through the Scratch 3 converter, so there's only one lowering
+pub fn lower_scratch2(data: &ProjectJson2) -> Program {
+    lower_scratch3(&convert(data).project)
+}
+
+pub fn lower_scratch3(data: &ProjectJson3) -> Program {
+    let mut targets = data.targets.iter().map(lower_target).collect::<Vec<_>>();
+    let stage = match targets.iter().position(|t| t.is_stage) {
+        Some(i) => targets.remove(i),
+        None => empty_stage(),
+    };
+
+    // Broadcasts are declared on the stage, but older projects may have left some on sprites
+    let mut broadcasts = data
+        .targets
+        .iter()
+        .flat_map(|t| t.broadcasts.iter())
+        .map(|(id, name)| Broadcast {
+            id: id.clone(),
+            name: name.clone(),
+        })
+        .collect::<Vec<_>>();
+    broadcasts.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+    broadcasts.dedup();
+
+    Program {
+        stage,
+        sprites: targets,
+        broadcasts,
+    }
+}
+
+/// Lowers a Scratch 3 opcode on its own, giving `None` if it would only become one of the `Other` fallbacks
+pub fn lower_opcode(opcode: &str) -> Option<Lowered> {
+    let block = Block {
+        id: String::new(),
+        opcode: opcode.to_string(),
+        shadow: false,
+        inputs: HashMap::new(),
+        fields: HashMap::new(),
+        substacks: HashMap::new(),
+        mutation: None,
+    };
+    if opcode == "procedures_definition" {
+        return Some(Lowered::Definition);
+    }
+    match lower_hat(&block) {
+        Hat::Other(_) => {}
+        hat => return Some(Lowered::Hat(hat)),
+    }
+    match lower_statement(&block) {
+        Statement::Other { .. } => {}
+        statement => return Some(Lowered::Statement(statement)),
+    }
+    match lower_reporter(&block) {
+        Expr::Other { .. } => None,
+        expr => Some(Lowered::Reporter(expr)),
+    }
+}
+
+/// Whether a Scratch 3 opcode lowers to its own hat, statement or reporter rather than one of the `Other` fallbacks
+pub fn lowers(opcode: &str) -> bool {
+    lower_opcode(opcode).is_some()
+}
+
+fn empty_stage() -> Sprite {
+    Sprite {
+        name: "Stage".into(),
+        is_stage: true,
+        x: 0.0,
+        y: 0.0,
+        direction: 90.0,
+        size: 100.0,
+        visible: true,
+        current_costume: 0,
+        costumes: Vec::new(),
+        sounds: Vec::new(),
+        variables: Vec::new(),
+        lists: Vec::new(),
+        scripts: Vec::new(),
+        procedures: Vec::new(),
+    }
+}
+
+/// Sorts a map of declarations by name, so the output is stable
+fn sorted_by_name<T, F: Fn(&T) -> &str>(map: &HashMap<String, T>, name: F) -> Vec<(&String, &T)> {
+    let mut entries = map.iter().collect::<Vec<_>>();
+    entries.sort_by(|a, b| name(a.1).cmp(name(b.1)).then_with(|| a.0.cmp(b.0)));
+    entries
+}
+
+fn lower_target(target: &TargetJson) -> Sprite {
+    let mut scripts = Vec::new();
+    let mut procedures = Vec::new();
+    let scope = if target.is_stage {
+        Scope::Stage
+    } else {
+        Scope::Sprite
+    };
+
+    for script in build_scripts(&target.blocks).scripts {
+        let (hat, body) = match script.blocks.split_first() {
+            Some((hat, body)) if script.hat().is_some() => (hat, body),
+            // Loose stacks never run
+            _ => continue,
+        };
+
+        if hat.opcode == "procedures_definition" {
+            procedures.push(lower_procedure(hat, body));
+        } else {
+            scripts.push(Script {
+                hat: lower_hat(hat),
+                body: lower_stack(body),
+            });
+        }
+    }
+
+    Sprite {
+        name: target.name.clone(),
+        is_stage: target.is_stage,
+        x: target.x,
+        y: target.y,
+        direction: target.direction,
+        size: target.size,
+        visible: target.visible.unwrap_or(true),
+        current_costume: target.current_costume as usize,
+        costumes: target
+            .costumes
+            .iter()
+            .map(|c| Costume {
+                name: c.name.clone(),
+                md5ext: c.md5ext.clone(),
+                rotation_center_x: c.rotation_center_x,
+                rotation_center_y: c.rotation_center_y,
+                bitmap_resolution: c.bitmap_resolution.unwrap_or(1.0),
+            })
+            .collect(),
+        sounds: target
+            .sounds
+            .iter()
+            .map(|s| Sound {
+                name: s.name.clone(),
+                md5ext: s.md5ext.clone(),
+            })
+            .collect(),
+        variables: sorted_by_name(&target.variables, |v| &v.name)
+            .into_iter()
+            .map(|(id, v)| Variable {
+                id: id.clone(),
+                name: v.name.clone(),
+                value: v.value.clone(),
+                is_cloud: v.is_cloud,
+                scope,
+            })
+            .collect(),
+        lists: sorted_by_name(&target.lists, |l| &l.name)
+            .into_iter()
+            .map(|(id, l)| List {
+                id: id.clone(),
+                name: l.name.clone(),
+                items: l.items.clone(),
+                scope,
+            })
+            .collect(),
+        scripts,
+        procedures,
+    }
+}
+
+fn lower_hat(hat: &Block) -> Hat {
+    let field = |name| hat.field(name).unwrap_or("").to_string();
+    match hat.opcode.as_str() {
+        "event_whenflagclicked" => Hat::GreenFlag,
+        "event_whenkeypressed" => Hat::KeyPressed(field("KEY_OPTION")),
+        "event_whenthisspriteclicked" | "event_whenstageclicked" => Hat::Clicked,
+        "event_whenbackdropswitchesto" => Hat::BackdropSwitchesTo(field("BACKDROP")),
+        "event_whenbroadcastreceived" => Hat::BroadcastReceived(field("BROADCAST_OPTION")),
+        "event_whengreaterthan" => Hat::GreaterThan {
+            sensor: field("WHENGREATERTHANMENU"),
+            value: lower_expr(hat.input("VALUE")),
+        },
+        "control_start_as_clone" => Hat::CloneStart,
+        opcode => Hat::Other(opcode.to_string()),
+    }
+}
+
+fn lower_procedure(definition: &Block, body: &[Block]) -> Procedure {
+    let mutation = match definition.input("custom_block") {
+        BlockExpr::Block(prototype) => prototype.mutation.clone(),
+        _ => None,
+    }
+    .unwrap_or_default();
+
+    let names = mutation.argument_names();
+    let defaults = mutation.argument_defaults();
+    let parameters = mutation
+        .argument_ids()
+        .iter()
+        .enumerate()
+        .map(|(i, id)| Parameter {
+            id: id.clone(),
+            name: names.get(i).cloned().unwrap_or_default(),
+            default: defaults.get(i).cloned().unwrap_or_default(),
+        })
+        .collect();
+
+    Procedure {
+        proccode: mutation.proccode().to_string(),
+        parameters,
+        warp: mutation.warp(),
+        body: lower_stack(body),
+    }
+}
+
+fn lower_stack(stack: &[Block]) -> Vec<Statement> {
+    stack.iter().map(lower_statement).collect()
+}
+
+fn data_ref(block: &Block, field: &str) -> DataRef {
+    match block.fields.get(field) {
+        Some(field) => DataRef {
+            id: field.id.clone().unwrap_or_default(),
+            name: field.value.clone(),
+        },
+        None => DataRef {
+            id: String::new(),
+            name: String::new(),
+        },
+    }
+}
+
+fn lower_statement(block: &Block) -> Statement {
+    let input = |name| lower_expr(block.input(name));
+    let field = |name| block.field(name).unwrap_or("").to_string();
+    let substack = |name| lower_stack(block.substack(name));
+
+    match block.opcode.as_str() {
+        "motion_movesteps" => Statement::MoveSteps(input("STEPS")),
+        "motion_turnright" => Statement::TurnRight(input("DEGREES")),
+        "motion_turnleft" => Statement::TurnLeft(input("DEGREES")),
+        "motion_pointindirection" => Statement::PointInDirection(input("DIRECTION")),
+        "motion_pointtowards" => Statement::PointTowards(input("TOWARDS")),
+        "motion_goto" => Statement::GoTo(input("TO")),
+        "motion_gotoxy" => Statement::GoToXY {
+            x: input("X"),
+            y: input("Y"),
+        },
+        "motion_glidesecstoxy" => Statement::GlideToXY {
+            secs: input("SECS"),
+            x: input("X"),
+            y: input("Y"),
+        },
+        "motion_changexby" => Statement::ChangeXBy(input("DX")),
+        "motion_setx" => Statement::SetX(input("X")),
+        "motion_changeyby" => Statement::ChangeYBy(input("DY")),
+        "motion_sety" => Statement::SetY(input("Y")),
+        "motion_ifonedgebounce" => Statement::BounceOffEdge,
+        "motion_setrotationstyle" => Statement::SetRotationStyle(field("STYLE")),
+
+        "looks_sayforsecs" => Statement::Say {
+            message: input("MESSAGE"),
+            secs: Some(input("SECS")),
+        },
+        "looks_say" => Statement::Say {
+            message: input("MESSAGE"),
+            secs: None,
+        },
+        "looks_thinkforsecs" => Statement::Think {
+            message: input("MESSAGE"),
+            secs: Some(input("SECS")),
+        },
+        "looks_think" => Statement::Think {
+            message: input("MESSAGE"),
+            secs: None,
+        },
+        "looks_show" => Statement::Show,
+        "looks_hide" => Statement::Hide,
+        "looks_switchcostumeto" => Statement::SwitchCostume(input("COSTUME")),
+        "looks_nextcostume" => Statement::NextCostume,
+        "looks_switchbackdropto" => Statement::SwitchBackdrop {
+            backdrop: input("BACKDROP"),
+            wait: false,
+        },
+        "looks_switchbackdroptoandwait" => Statement::SwitchBackdrop {
+            backdrop: input("BACKDROP"),
+            wait: true,
+        },
+        "looks_nextbackdrop" => Statement::NextBackdrop,
+        "looks_changeeffectby" => Statement::ChangeEffectBy {
+            effect: field("EFFECT"),
+            change: input("CHANGE"),
+        },
+        "looks_seteffectto" => Statement::SetEffectTo {
+            effect: field("EFFECT"),
+            value: input("VALUE"),
+        },
+        "looks_cleargraphiceffects" => Statement::ClearEffects,
+        "looks_changesizeby" => Statement::ChangeSizeBy(input("CHANGE")),
+        "looks_setsizeto" => Statement::SetSizeTo(input("SIZE")),
+
+        "sound_play" => Statement::PlaySound {
+            sound: input("SOUND_MENU"),
+            wait: false,
+        },
+        "sound_playuntildone" => Statement::PlaySound {
+            sound: input("SOUND_MENU"),
+            wait: true,
+        },
+        "sound_stopallsounds" => Statement::StopAllSounds,
+        "sound_changevolumeby" => Statement::ChangeVolumeBy(input("VOLUME")),
+        "sound_setvolumeto" => Statement::SetVolumeTo(input("VOLUME")),
+        "music_playNoteForBeats" => Statement::PlayNote {
+            note: input("NOTE"),
+            beats: input("BEATS"),
+        },
+        "music_restForBeats" => Statement::Rest(input("BEATS")),
+
+        "event_broadcast" => Statement::Broadcast {
+            message: input("BROADCAST_INPUT"),
+            wait: false,
+        },
+        "event_broadcastandwait" => Statement::Broadcast {
+            message: input("BROADCAST_INPUT"),
+            wait: true,
+        },
+
+        "control_wait" => Statement::Wait(input("DURATION")),
+        "control_repeat" => Statement::Repeat {
+            times: input("TIMES"),
+            body: substack("SUBSTACK"),
+        },
+        "control_forever" => Statement::Forever(substack("SUBSTACK")),
+        "control_if" => Statement::If {
+            condition: input("CONDITION"),
+            then: substack("SUBSTACK"),
+            otherwise: Vec::new(),
+        },
+        "control_if_else" => Statement::If {
+            condition: input("CONDITION"),
+            then: substack("SUBSTACK"),
+            otherwise: substack("SUBSTACK2"),
+        },
+        "control_wait_until" => Statement::WaitUntil(input("CONDITION")),
+        "control_repeat_until" => Statement::RepeatUntil {
+            condition: input("CONDITION"),
+            body: substack("SUBSTACK"),
+        },
+        "control_stop" => Statement::Stop(field("STOP_OPTION")),
+        "control_create_clone_of" => Statement::CreateClone(input("CLONE_OPTION")),
+        "control_delete_this_clone" => Statement::DeleteClone,
+
+        "sensing_askandwait" => Statement::Ask(input("QUESTION")),
+        "sensing_resettimer" => Statement::ResetTimer,
+
+        "data_setvariableto" => Statement::SetVariable {
+            variable: data_ref(block, "VARIABLE"),
+            value: input("VALUE"),
+        },
+        "data_changevariableby" => Statement::ChangeVariable {
+            variable: data_ref(block, "VARIABLE"),
+            value: input("VALUE"),
+        },
+        "data_addtolist" => Statement::AddToList {
+            list: data_ref(block, "LIST"),
+            item: input("ITEM"),
+        },
+        "data_deleteoflist" => Statement::DeleteOfList {
+            list: data_ref(block, "LIST"),
+            index: input("INDEX"),
+        },
+        "data_deletealloflist" => Statement::DeleteAllOfList(data_ref(block, "LIST")),
+        "data_insertatlist" => Statement::InsertAtList {
+            list: data_ref(block, "LIST"),
+            index: input("INDEX"),
+            item: input("ITEM"),
+        },
+        "data_replaceitemoflist" => Statement::ReplaceItemOfList {
+            list: data_ref(block, "LIST"),
+            index: input("INDEX"),
+            item: input("ITEM"),
+        },
+
+        "procedures_call" => {
+            let mutation = block.mutation.clone().unwrap_or_default();
+            Statement::Call {
+                proccode: mutation.proccode().to_string(),
+                args: mutation.argument_ids().iter().map(|id| input(id)).collect(),
+            }
+        }
+
+        opcode => Statement::Other {
+            opcode: opcode.to_string(),
+            inputs: lower_inputs(block),
+            fields: lower_fields(block),
+        },
+    }
+}
+
+/// Inputs and fields sorted by name, so unknown blocks lower the same way every time
+fn lower_inputs(block: &Block) -> Vec<(String, Expr)> {
+    let mut inputs = block
+        .inputs
+        .iter()
+        .map(|(name, expr)| (name.clone(), lower_expr(expr)))
+        .collect::<Vec<_>>();
+    inputs.sort_by(|a, b| a.0.cmp(&b.0));
+    inputs
+}
+
+fn lower_fields(block: &Block) -> Vec<(String, String)> {
+    let mut fields = block
+        .fields
+        .iter()
+        .map(|(name, field)| (name.clone(), field.value.clone()))
+        .collect::<Vec<_>>();
+    fields.sort();
+    fields
+}
+
+fn lower_expr(expr: &BlockExpr) -> Expr {
+    match expr {
+        BlockExpr::Primitive(primitive) => match primitive {
+            Primitive::Variable { name, id } => Expr::Variable(DataRef {
+                id: id.clone(),
+                name: name.clone(),
+            }),
+            Primitive::List { name, id } => Expr::ListContents(DataRef {
+                id: id.clone(),
+                name: name.clone(),
+            }),
+            Primitive::Broadcast { name, .. } => Expr::Literal(name.clone()),
+            primitive => Expr::Literal(primitive.literal().unwrap_or("").to_string()),
+        },
+        BlockExpr::Block(block) => match expr.literal() {
+            Some(option) => Expr::Literal(option.to_string()),
+            None => lower_reporter(block),
+        },
+        BlockExpr::Empty => Expr::Literal(String::new()),
+    }
+}
+
+fn lower_reporter(block: &Block) -> Expr {
+    let input = |name| Box::new(lower_expr(block.input(name)));
+    let field = |name| block.field(name).unwrap_or("").to_string();
+    let binary = |op, lhs, rhs| Expr::Binary {
+        op,
+        lhs: input(lhs),
+        rhs: input(rhs),
+    };
+
+    match block.opcode.as_str() {
+        "operator_add" => binary(BinaryOp::Add, "NUM1", "NUM2"),
+        "operator_subtract" => binary(BinaryOp::Subtract, "NUM1", "NUM2"),
+        "operator_multiply" => binary(BinaryOp::Multiply, "NUM1", "NUM2"),
+        "operator_divide" => binary(BinaryOp::Divide, "NUM1", "NUM2"),
+        "operator_mod" => binary(BinaryOp::Mod, "NUM1", "NUM2"),
+        "operator_random" => binary(BinaryOp::Random, "FROM", "TO"),
+        "operator_lt" => binary(BinaryOp::LessThan, "OPERAND1", "OPERAND2"),
+        "operator_equals" => binary(BinaryOp::Equals, "OPERAND1", "OPERAND2"),
+        "operator_gt" => binary(BinaryOp::GreaterThan, "OPERAND1", "OPERAND2"),
+        "operator_and" => binary(BinaryOp::And, "OPERAND1", "OPERAND2"),
+        "operator_or" => binary(BinaryOp::Or, "OPERAND1", "OPERAND2"),
+        "operator_join" => binary(BinaryOp::Join, "STRING1", "STRING2"),
+        "operator_letter_of" => binary(BinaryOp::LetterOf, "LETTER", "STRING"),
+        "operator_contains" => binary(BinaryOp::Contains, "STRING1", "STRING2"),
+        "operator_not" => Expr::Not(input("OPERAND")),
+        "operator_round" => Expr::Round(input("NUM")),
+        "operator_length" => Expr::Length(input("STRING")),
+        "operator_mathop" => Expr::MathOp {
+            op: field("OPERATOR"),
+            num: input("NUM"),
+        },
+
+        "motion_xposition" => Expr::XPosition,
+        "motion_yposition" => Expr::YPosition,
+        "motion_direction" => Expr::Direction,
+        "looks_size" => Expr::Size,
+        "looks_costumenumbername" => Expr::Costume {
+            number: field("NUMBER_NAME") == "number",
+        },
+        "looks_backdropnumbername" => Expr::Backdrop {
+            number: field("NUMBER_NAME") == "number",
+        },
+        "sound_volume" => Expr::Volume,
+        "music_getTempo" => Expr::Tempo,
+
+        "sensing_touchingobject" => Expr::Touching(input("TOUCHINGOBJECTMENU")),
+        "sensing_distanceto" => Expr::DistanceTo(input("DISTANCETOMENU")),
+        "sensing_keypressed" => Expr::KeyPressed(input("KEY_OPTION")),
+        "sensing_mousedown" => Expr::MouseDown,
+        "sensing_mousex" => Expr::MouseX,
+        "sensing_mousey" => Expr::MouseY,
+        "sensing_loudness" => Expr::Loudness,
+        "sensing_timer" => Expr::Timer,
+        "sensing_answer" => Expr::Answer,
+        "sensing_of" => Expr::Of {
+            property: field("PROPERTY"),
+            object: input("OBJECT"),
+        },
+        "sensing_current" => Expr::Current(field("CURRENTMENU")),
+        "sensing_dayssince2000" => Expr::DaysSince2000,
+        "sensing_username" => Expr::Username,
+
+        "data_variable" => Expr::Variable(data_ref(block, "VARIABLE")),
+        "data_listcontents" => Expr::ListContents(data_ref(block, "LIST")),
+        "data_itemoflist" => Expr::ItemOfList {
+            list: data_ref(block, "LIST"),
+            index: input("INDEX"),
+        },
+        "data_itemnumoflist" => Expr::ItemNumOfList {
+            list: data_ref(block, "LIST"),
+            item: input("ITEM"),
+        },
+        "data_lengthoflist" => Expr::LengthOfList(data_ref(block, "LIST")),
+        "data_listcontainsitem" => Expr::ListContains {
+            list: data_ref(block, "LIST"),
+            item: input("ITEM"),
+        },
+
+        "argument_reporter_string_number" | "argument_reporter_boolean" => {
+            Expr::Argument(field("VALUE"))
+        }
+
+        opcode => Expr::Other {
+            opcode: opcode.to_string(),
+            inputs: lower_inputs(block),
+            fields: lower_fields(block),
+        },
+    }
+}
@@ -0,0 +1,81 @@
+use crate::{
+    manifest::{
+        Format,
+        Manifest,
+    },
+    types::{
+        ProjectInfoJson,
+        ProjectJson as ProjectJson2,
+    },
+    Project,
+    ProjectData,
+    ScratchError,
+    ScratchResult,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// The top-level `project.json` the old API types wrote, next to the real project in `metadata/project.json`
+#[derive(Deserialize)]
+struct LegacyProjectJson {
+    #[serde(default)]
+    code: Option<String>,
+}
+
+/// Checks for the layout the old API types saved: `project.json`, `metadata/project.json` and `assets/{images,audio}`
+pub(crate) fn is_legacy(path: &Path) -> bool {
+    path.join("metadata").join("project.json").is_file()
+}
+
+fn read_assets(dir: &Path, assets: &mut HashMap<String, Vec<u8>>) -> ScratchResult<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(ScratchError::Io(e)),
+    };
+    for entry in entries {
+        let entry = entry.map_err(ScratchError::Io)?;
+        if entry.path().is_file() {
+            let data = std::fs::read(entry.path()).map_err(ScratchError::Io)?;
+            assets.insert(entry.file_name().to_string_lossy().into_owned(), data);
+        }
+    }
+    Ok(())
+}
+
+/// Loads a project from the old layout, keeping its assets in memory so it can be saved in the current one
+pub(crate) fn load(path: PathBuf) -> ScratchResult<Project> {
+    let metadata = path.join("metadata");
+    let body = std::fs::read(metadata.join("project.json")).map_err(ScratchError::Io)?;
+    let data: ProjectJson2 = serde_json::from_slice(&body).map_err(ScratchError::Json)?;
+
+    let legacy = std::fs::read(path.join("project.json"))
+        .ok()
+        .and_then(|main| serde_json::from_slice::<LegacyProjectJson>(&main).ok());
+    // Old stats files may predate fields `ProjectInfoJson` needs, in which case they're skipped
+    let info = std::fs::read(metadata.join("project_stats.json"))
+        .ok()
+        .and_then(|stats| serde_json::from_slice::<ProjectInfoJson>(&stats).ok());
+
+    let mut manifest = Manifest::new();
+    manifest.id = legacy.and_then(|l| l.code);
+    manifest.format = Some(Format::Scratch2);
+    manifest.info = info.as_ref().map(Into::into);
+
+    let mut assets = HashMap::new();
+    read_assets(&path.join("assets").join("images"), &mut assets)?;
+    read_assets(&path.join("assets").join("audio"), &mut assets)?;
+
+    let mut project: Project = ProjectData::Scratch2(data).into();
+    project.name = path.file_name().map(|n| n.to_string_lossy().into_owned());
+    project.manifest = manifest;
+    project.assets = assets;
+    project.path = Some(path);
+    Ok(project)
+}
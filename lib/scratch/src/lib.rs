@@ -1,56 +1,204 @@
+pub mod archive;
+pub mod cache;
 pub mod client;
+pub mod coverage;
+pub mod download;
+pub mod ir;
+mod legacy;
+pub mod manifest;
 pub mod scratch3;
 pub mod target;
 pub mod types;
 pub mod util;
+pub mod validate;
+pub mod value;
+pub mod vm;
 
 use crate::{
-    scratch3::ProjectJson as ProjectJson3,
+    client::{
+        Client,
+        Endpoints,
+    },
+    download::{
+        DownloadOptions,
+        DownloadProgress,
+        Downloads,
+    },
+    manifest::{
+        Format,
+        Manifest,
+        MANIFEST_FILE,
+    },
+    scratch3::{
+        NetworkProject as NetworkProject3,
+        ProjectJson as ProjectJson3,
+    },
     target::Target,
     types::ProjectJson as ProjectJson2,
     util::FileCreater,
 };
 use serde::{
+    de::Error as _,
     Deserialize,
+    Deserializer,
     Serialize,
+    Serializer,
 };
 use std::{
     borrow::Cow,
-    path::PathBuf,
+    collections::HashMap,
+    path::{
+        Path,
+        PathBuf,
+    },
 };
 
 #[derive(Debug)]
 pub enum ScratchError {
     Network,
+    /// The server answered with an error status, like 404 for something that isn't there
+    Status(u16),
     Json(serde_json::Error),
+    Io(std::io::Error),
+    Zip(zip::result::ZipError),
+    Toml(toml::de::Error),
     Custom(Cow<'static, str>),
     InvalidSavePath,
 }
 
 pub type ScratchResult<T> = Result<T, ScratchError>;
 
+/// Where a saved project keeps its costumes and sounds, inside its `data` dir
+pub const ASSETS_DIR: &str = "assets";
+
 pub struct SaveOptions {
     overwrite: bool,
+    update: bool,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SaveOptions {
     pub fn new() -> Self {
-        SaveOptions { overwrite: false }
+        SaveOptions {
+            overwrite: false,
+            update: false,
+        }
     }
 
+    /// Replaces the project's data if it was saved before, keeping build outputs and downloaded assets
     pub fn overwrite(mut self, overwrite: bool) -> Self {
         self.overwrite = overwrite;
         self
     }
+
+    /// Like `overwrite`, but also keeps the old files and target settings the new save doesn't replace
+    pub fn update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
 }
 
-#[derive(Serialize, Deserialize)]
+/// A project's JSON, (de)serialized as the plain `project.json` Scratch itself uses
 pub enum ProjectData {
     Scratch2(ProjectJson2),
     Scratch3(ProjectJson3),
 }
 
+impl Serialize for ProjectData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ProjectData::Scratch2(data) => data.serialize(serializer),
+            ProjectData::Scratch3(data) => data.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ProjectData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = serde_json::Value::deserialize(deserializer)?;
+        ProjectData::from_value(json).map_err(|e| D::Error::custom(format!("{:?}", e)))
+    }
+}
+
 impl ProjectData {
+    /// Parses a `project.json`, telling Scratch 2 and Scratch 3 apart by their top-level keys
+    pub fn from_slice(data: &[u8]) -> ScratchResult<Self> {
+        let json = serde_json::from_slice(data).map_err(ScratchError::Json)?;
+        Self::from_value(json)
+    }
+
+    /// Like `from_slice`. Also reads the `{"Scratch3": {...}}` wrapper older versions saved projects in.
+    pub fn from_value(json: serde_json::Value) -> ScratchResult<Self> {
+        if let Some(wrapped) = json.as_object().filter(|o| o.len() == 1) {
+            if let Some(data) = wrapped.get("Scratch2") {
+                return serde_json::from_value(data.clone())
+                    .map(ProjectData::Scratch2)
+                    .map_err(ScratchError::Json);
+            }
+            if let Some(data) = wrapped.get("Scratch3") {
+                return serde_json::from_value(data.clone())
+                    .map(ProjectData::Scratch3)
+                    .map_err(ScratchError::Json);
+            }
+        }
+
+        let has_key = |key| json.get(key).is_some();
+
+        if has_key("targets") || has_key("meta") {
+            serde_json::from_value(json)
+                .map(ProjectData::Scratch3)
+                .map_err(ScratchError::Json)
+        } else if has_key("objName") || has_key("children") {
+            serde_json::from_value(json)
+                .map(ProjectData::Scratch2)
+                .map_err(ScratchError::Json)
+        } else {
+            Err(ScratchError::Custom("Unknown project format".into()))
+        }
+    }
+
+    /// Gets the file names of every costume and sound, in target order, without duplicates
+    pub fn asset_names(&self) -> Vec<&str> {
+        let names: Vec<&str> = match self {
+            ProjectData::Scratch2(data) => {
+                let sprites = std::iter::once((&data.costumes, &data.sounds))
+                    .chain(data.children.iter().map(|s| (&s.costumes, &s.sounds)));
+                sprites
+                    .flat_map(|(costumes, sounds)| {
+                        let sounds = sounds.iter().flatten().map(|s| s.src.as_str());
+                        costumes.iter().map(|c| c.src.as_str()).chain(sounds)
+                    })
+                    .collect()
+            }
+            ProjectData::Scratch3(data) => data
+                .targets
+                .iter()
+                .flat_map(|t| {
+                    let sounds = t.sounds.iter().map(|s| s.md5ext.as_str());
+                    t.costumes.iter().map(|c| c.md5ext.as_str()).chain(sounds)
+                })
+                .collect(),
+        };
+
+        let mut seen = std::collections::HashSet::new();
+        names
+            .into_iter()
+            .filter(|name| seen.insert(*name))
+            .collect()
+    }
+
+    pub fn format(&self) -> Format {
+        match self {
+            ProjectData::Scratch2(_) => Format::Scratch2,
+            ProjectData::Scratch3(_) => Format::Scratch3,
+        }
+    }
+
     pub fn as_ref2(&self) -> Option<&ProjectJson2> {
         match self {
             ProjectData::Scratch2(data) => Some(data),
@@ -63,57 +211,250 @@ pub struct Project {
     path: Option<PathBuf>,
     pub name: Option<String>,
     pub data: ProjectData,
+    /// Asset files bundled with the project, keyed by file name (`md5ext` for Scratch 3)
+    pub assets: HashMap<String, Vec<u8>>,
+    pub manifest: Manifest,
 }
 
 impl Project {
     pub fn get_name(&self) -> &str {
-        self.name.as_ref().map(|s| s.as_str()).unwrap_or("")
+        self.name.as_deref().unwrap_or("")
     }
 
+    /// Gets the path of an asset stored in the saved project's `data/assets` dir, if it is there
+    pub fn asset_path(&self, name: &str) -> Option<PathBuf> {
+        let path = self.path.as_ref()?.join("data").join(ASSETS_DIR).join(name);
+        Some(path).filter(|path| util::is_file_name(name) && path.is_file())
+    }
+
+    /// Copies an asset out of the saved project's `data/assets` dir, giving `false` if it isn't there
+    pub fn copy_asset(&self, name: &str, dest: &Path) -> ScratchResult<bool> {
+        match self.asset_path(name) {
+            Some(path) => std::fs::copy(path, dest)
+                .map(|_| true)
+                .map_err(ScratchError::Io),
+            None => Ok(false),
+        }
+    }
+
+    /// Gets an asset bundled with the project or stored alongside it
+    fn local_asset(&self, name: &str) -> ScratchResult<Option<Vec<u8>>> {
+        if let Some(data) = self.assets.get(name) {
+            return Ok(Some(data.clone()));
+        }
+        self.asset_path(name)
+            .map(|path| std::fs::read(path).map_err(ScratchError::Io))
+            .transpose()
+    }
+
+    /// Gets an asset by file name, only going to the network if the project doesn't have it
+    pub fn load_asset(&self, client: &mut Client, name: &str) -> ScratchResult<Vec<u8>> {
+        match self.local_asset(name)? {
+            Some(data) => Ok(data),
+            None => client.get_asset(name),
+        }
+    }
+
+    /// Gets many assets by file name, downloading the ones the project doesn't have several at a time.
+    /// `progress` only hears about downloads.
+    pub fn load_assets<P>(
+        &self,
+        client: &Client,
+        names: &[&str],
+        options: &DownloadOptions,
+        progress: P,
+    ) -> Downloads
+    where
+        P: Fn(&DownloadProgress) + Sync,
+    {
+        let mut local = HashMap::new();
+        let mut failed = Vec::new();
+        let mut missing = Vec::new();
+        for name in names.iter().copied() {
+            match self.local_asset(name) {
+                Ok(Some(data)) => {
+                    local.insert(name.to_string(), data);
+                }
+                Ok(None) => missing.push(name),
+                Err(e) => failed.push((name.to_string(), e)),
+            }
+        }
+
+        let mut downloads = client.get_assets(&missing, options, progress);
+        downloads.assets.extend(local);
+        downloads.failed.extend(failed);
+        downloads.failed.sort_by(|a, b| a.0.cmp(&b.0));
+        downloads
+    }
+
+    /// Saves with a default `Client`, see `save_with_client`
     pub fn save(&mut self, path: PathBuf, options: SaveOptions) -> ScratchResult<()> {
-        let mut file_creater = FileCreater::new(path);
-        let name = self.get_name();
-
-        if file_creater.is_dir() {
-            if file_creater.down(name).is_ok() {
-                if options.overwrite {
-                    unimplemented!("Overwrite");
-                } else {
-                    return Err(ScratchError::InvalidSavePath);
+        self.save_with_client(&Client::new(), path, options)
+    }
+
+    /// Saves the project into a new directory named after it inside `path`, with every costume and sound in `data/assets`.
+    /// Assets the project doesn't have are downloaded first, and the save fails if any can't be.
+    /// Everything is written to a temporary directory first, so a failed save never leaves a partial project behind.
+    pub fn save_with_client(
+        &mut self,
+        client: &Client,
+        path: PathBuf,
+        options: SaveOptions,
+    ) -> ScratchResult<()> {
+        self.save_with_progress(client, path, options, |_| {})
+    }
+
+    /// Like `save_with_client`, reporting the downloads of missing assets to `progress`
+    pub fn save_with_progress<P>(
+        &mut self,
+        client: &Client,
+        path: PathBuf,
+        options: SaveOptions,
+        progress: P,
+    ) -> ScratchResult<()>
+    where
+        P: Fn(&DownloadProgress) + Sync,
+    {
+        let name = self.get_name().to_string();
+        if !path.is_dir() || name.is_empty() {
+            return Err(ScratchError::InvalidSavePath);
+        }
+
+        let dest = path.join(&name);
+        util::restore_dir(&dest).map_err(ScratchError::Io)?;
+        let exists = dest.exists();
+        if exists && !(options.overwrite || options.update) {
+            return Err(ScratchError::InvalidSavePath);
+        }
+        if exists && options.update {
+            if let Some(old) = Manifest::read(&dest.join("data").join(MANIFEST_FILE))? {
+                for (target, settings) in old.targets {
+                    self.manifest.targets.entry(target).or_insert(settings);
                 }
-            } else {
+            }
+        }
+        self.manifest.name = name.clone();
+        self.manifest.format = Some(self.data.format());
+
+        // Assets already saved with an older copy are carried over rather than fetched again
+        let old_assets = dest.join("data").join(ASSETS_DIR);
+        let names = self.data.asset_names();
+        let missing = names
+            .into_iter()
+            .filter(|name| !self.assets.contains_key(*name))
+            .filter(|name| !(exists && old_assets.join(name).is_file()))
+            .collect::<Vec<_>>();
+        let downloads = self.load_assets(client, &missing, &DownloadOptions::new(), progress);
+        self.assets.extend(downloads.into_result()?);
 
+        let temp = util::staging_dir(&dest);
+        if temp.exists() {
+            std::fs::remove_dir_all(&temp).map_err(ScratchError::Io)?;
+        }
+        let written = self.write_data(temp.clone()).and_then(|_| {
+            if exists {
+                // Overwriting keeps everything outside `data` and the assets inside it
+                let update = options.update;
+                util::swap_dir(&dest, &temp, |relative| {
+                    update
+                        || !relative.starts_with("data")
+                        || relative.starts_with(Path::new("data").join(ASSETS_DIR))
+                })
+                .map_err(ScratchError::Io)
+            } else {
+                std::fs::rename(&temp, &dest).map_err(ScratchError::Io)
             }
-        } else {
-            return Err(ScratchError::InvalidSavePath);
+        });
+        if written.is_err() {
+            let _ = std::fs::remove_dir_all(&temp);
         }
+        written?;
+
+        self.path = Some(dest);
+        Ok(())
+    }
 
+    fn write_data(&self, path: PathBuf) -> ScratchResult<()> {
+        std::fs::create_dir(&path)
+            .map_err(|_| ScratchError::Custom("Error Creating Project Dir".into()))?;
+        let mut file_creater = FileCreater::new(path);
         file_creater
-            .mkdir(name)
-            .map_err(|_| ScratchError::Custom("Error Creating Project Dir".into()))?
             .mkdir("data")
             .map_err(|_| ScratchError::Custom("Error Creating Data Dir!".into()))?
             .write_json("project.json", &self.data)
             .map_err(|_| ScratchError::Custom("Error Writing Project Json!".into()))?
-            .up();
-
-        self.path = Some(file_creater.path);
+            .write_bytes(MANIFEST_FILE, self.manifest.to_toml()?.as_bytes())
+            .map_err(|_| ScratchError::Custom("Error Writing Manifest!".into()))?
+            .mkdir(ASSETS_DIR)
+            .map_err(|_| ScratchError::Custom("Error Creating Assets Dir!".into()))?;
 
+        for (name, data) in self
+            .assets
+            .iter()
+            .filter(|(name, _)| util::is_file_name(name))
+        {
+            file_creater
+                .write_bytes(name, data)
+                .map_err(ScratchError::Io)?;
+        }
         Ok(())
     }
 
-    pub fn load(mut path: PathBuf) -> ScratchResult<Self> {
-        path.push("data");
-        path.push("project.json");
-        let file = std::fs::File::open(&path).unwrap();
-        path.pop();
-        path.pop();
+    /// Loads a saved project, naming it after its directory if it has no manifest.
+    /// Projects saved by older versions are read too, see `migrate`.
+    pub fn load(path: PathBuf) -> ScratchResult<Self> {
+        util::restore_dir(&path).map_err(ScratchError::Io)?;
+        if legacy::is_legacy(&path) && !path.join("data").join("project.json").exists() {
+            return legacy::load(path);
+        }
+
+        let data_dir = path.join("data");
+        let body = std::fs::read(data_dir.join("project.json")).map_err(ScratchError::Io)?;
+        let data = ProjectData::from_slice(&body)?;
+        let manifest = Manifest::read(&data_dir.join(MANIFEST_FILE))?.unwrap_or_default();
+
+        let name = Some(manifest.name.clone())
+            .filter(|name| !name.is_empty())
+            .or_else(|| path.file_name().map(|n| n.to_string_lossy().into_owned()));
         Ok(Project {
             path: Some(path),
-            name: None, //TODO: METATDATA file
-            data: serde_json::from_reader(&file).unwrap(),
+            name,
+            data,
+            assets: HashMap::new(),
+            manifest,
         })
     }
+
+    /// Rewrites a saved project in the current layout, if it is in an older one.
+    /// That covers `project.json` wrapped as `{"Scratch3": {...}}`, projects without a manifest or assets,
+    /// and the `project.json` + `metadata/project.json` layout of the old API types.
+    /// Old files are left in place. Returns whether anything was rewritten.
+    pub fn migrate(client: &Client, path: PathBuf) -> ScratchResult<bool> {
+        let data_dir = path.join("data");
+        let current = data_dir.join("project.json").exists()
+            && data_dir.join(MANIFEST_FILE).exists()
+            && data_dir.join(ASSETS_DIR).exists()
+            && !is_wrapped(&data_dir.join("project.json"))?;
+        if current {
+            return Ok(false);
+        }
+
+        let mut project = Project::load(path.clone())?;
+        let parent = path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+        // The project is saved under its directory name so it replaces itself
+        project.name = path.file_name().map(|n| n.to_string_lossy().into_owned());
+        project.save_with_client(client, parent, SaveOptions::new().update(true))?;
+        Ok(true)
+    }
+
+    /// Gets the settings a target keeps in the manifest
+    pub fn target_settings<T: Target>(&self) -> Option<&toml::value::Table> {
+        self.manifest.target_settings(T::name())
+    }
+
     //TODO: Multiple build dirs? use type inference instead? keep for api similarity?
     pub fn get_build_path<T: Target>(&self, _target: &T) -> PathBuf {
         let mut path = self.path.as_ref().unwrap().clone();
@@ -124,11 +465,32 @@ impl Project {
     }
 
     pub fn build<T: Target>(&self, target: &mut T) -> ScratchResult<()> {
-        target.build(&self)
+        target.build(self)
     }
 
     pub fn run<T: Target>(&self, target: &mut T) -> ScratchResult<()> {
-        target.run(&self)
+        target.run(self)
+    }
+}
+
+/// Checks for the `{"Scratch3": {...}}` wrapper older versions saved projects in
+fn is_wrapped(path: &Path) -> ScratchResult<bool> {
+    let body = std::fs::read(path).map_err(ScratchError::Io)?;
+    let json: serde_json::Value = serde_json::from_slice(&body).map_err(ScratchError::Json)?;
+    Ok(json.as_object().map_or(false, |o| {
+        o.len() == 1 && (o.contains_key("Scratch2") || o.contains_key("Scratch3"))
+    }))
+}
+
+impl From<ProjectData> for Project {
+    fn from(data: ProjectData) -> Self {
+        Project {
+            data,
+            name: None,
+            path: None,
+            assets: HashMap::new(),
+            manifest: Manifest::new(),
+        }
     }
 }
 
@@ -138,6 +500,8 @@ impl From<ProjectJson2> for Project {
             data: ProjectData::Scratch2(data),
             name: None,
             path: None,
+            assets: HashMap::new(),
+            manifest: Manifest::new(),
         }
     }
 }
@@ -148,6 +512,8 @@ impl From<ProjectJson3> for Project {
             data: ProjectData::Scratch3(data),
             name: None,
             path: None,
+            assets: HashMap::new(),
+            manifest: Manifest::new(),
         }
     }
 }
@@ -162,14 +528,23 @@ impl NetworkProject {
         NetworkProject { id }
     }
 
-    pub fn get_data_url(&self) -> String {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn get_data_url(&self, endpoints: &Endpoints) -> String {
         format!(
-            "https://cdn.projects.scratch.mit.edu/internalapi/project/{}/get",
-            self.id
+            "{}/internalapi/project/{}/get",
+            endpoints.legacy_projects, self.id
         )
     }
 
-    pub fn get_stats_url(&self) -> String {
-        format!("https://api.scratch.mit.edu/projects/{}/", self.id)
+    pub fn get_stats_url(&self, endpoints: &Endpoints) -> String {
+        format!("{}/projects/{}/", endpoints.api, self.id)
+    }
+
+    /// Views this project through the Scratch 3 API, which shares its IDs with Scratch 2
+    pub fn as_scratch3(&self) -> NetworkProject3 {
+        NetworkProject3::new(self.id.clone())
     }
 }
@@ -0,0 +1,112 @@
+use crate::{
+    types::ProjectInfoJson,
+    ScratchError,
+    ScratchResult,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    time::{
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+/// The name of the manifest, kept next to `project.json` in a saved project's `data` dir
+pub const MANIFEST_FILE: &str = "manifest.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Scratch2,
+    Scratch3,
+}
+
+/// What a saved project remembers about itself besides its JSON
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Filled in from the project when it is saved
+    #[serde(default)]
+    pub name: String,
+    /// Filled in from the project when it is saved
+    pub format: Option<Format>,
+    /// The Scratch project ID, if the project was downloaded
+    pub id: Option<String>,
+    /// When the project was downloaded, in seconds since the Unix epoch
+    pub fetched_at: Option<u64>,
+    pub info: Option<ProjectInfo>,
+    /// Settings for each target, keyed by `Target::name`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub targets: BTreeMap<String, toml::value::Table>,
+}
+
+/// The parts of the project page worth keeping offline
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectInfo {
+    pub title: String,
+    pub author: Option<String>,
+    #[serde(default)]
+    pub instructions: String,
+    /// URL of the thumbnail image
+    pub thumbnail: Option<String>,
+}
+
+impl From<&ProjectInfoJson> for ProjectInfo {
+    fn from(info: &ProjectInfoJson) -> Self {
+        ProjectInfo {
+            title: info.title.clone(),
+            author: info.author.as_ref().map(|a| a.username.clone()),
+            instructions: info.instructions.clone(),
+            thumbnail: Some(info.image.clone()).filter(|image| !image.is_empty()),
+        }
+    }
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Describes a project that was just downloaded
+    pub fn fetched(id: &str, info: Option<&ProjectInfoJson>) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .ok();
+        Manifest {
+            id: Some(id.to_string()),
+            fetched_at: now,
+            info: info.map(ProjectInfo::from),
+            ..Self::default()
+        }
+    }
+
+    pub fn parse(data: &str) -> ScratchResult<Self> {
+        toml::from_str(data).map_err(ScratchError::Toml)
+    }
+
+    pub fn to_toml(&self) -> ScratchResult<String> {
+        toml::to_string(self).map_err(|e| ScratchError::Custom(e.to_string().into()))
+    }
+
+    /// Reads a manifest file, giving `None` if there isn't one
+    pub fn read(path: &Path) -> ScratchResult<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(data) => Self::parse(&data).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(ScratchError::Io(e)),
+        }
+    }
+
+    pub fn target_settings(&self, target: &str) -> Option<&toml::value::Table> {
+        self.targets.get(target)
+    }
+
+    pub fn target_settings_mut(&mut self, target: &str) -> &mut toml::value::Table {
+        self.targets.entry(target.to_string()).or_default()
+    }
+}
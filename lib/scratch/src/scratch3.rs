@@ -1,3 +1,25 @@
+pub mod convert;
+mod data;
+mod input;
+mod mutation;
+pub mod script;
+
+pub use self::{
+    data::{
+        ListJson,
+        VariableJson,
+    },
+    input::{
+        Field,
+        Input,
+        InputValue,
+        Primitive,
+        TopLevelPrimitive,
+    },
+    mutation::Mutation,
+};
+
+use crate::client::Endpoints;
 use serde::{
     Deserialize,
     Serialize,
@@ -8,8 +30,8 @@ fn default_size() -> f64 {
     0.0
 }
 
-fn default_direction() -> u32 {
-    0
+fn default_direction() -> f64 {
+    0.0
 }
 
 #[derive(Debug)]
@@ -22,12 +44,16 @@ impl NetworkProject {
         NetworkProject { id }
     }
 
-    pub fn get_data_url(&self) -> String {
-        format!("https://projects.scratch.mit.edu/309320008/{}/", self.id)
+    /// The project body can only be fetched with the `project_token` handed out by the info endpoint
+    pub fn get_data_url(&self, endpoints: &Endpoints, token: Option<&str>) -> String {
+        match token {
+            Some(token) => format!("{}/{}?token={}", endpoints.projects, self.id, token),
+            None => format!("{}/{}", endpoints.projects, self.id),
+        }
     }
 
-    pub fn get_stats_url(&self) -> String {
-        format!("https://api.scratch.mit.edu/projects/{}/", self.id)
+    pub fn get_stats_url(&self, endpoints: &Endpoints) -> String {
+        format!("{}/projects/{}/", endpoints.api, self.id)
     }
 }
 
@@ -44,34 +70,40 @@ pub struct ProjectJson {
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TargetJson {
-    pub blocks: HashMap<String, BlockJson>,
-    broadcasts: serde_json::Value,
+    pub blocks: HashMap<String, TargetBlock>,
+    /// Broadcast names by ID, which Scratch only keeps on the stage
+    #[serde(default)]
+    pub broadcasts: HashMap<String, String>,
     comments: serde_json::Value,
     pub costumes: Vec<CostumeJson>,
     #[serde(rename = "currentCostume")]
     pub current_costume: u32,
     #[serde(default = "default_direction")]
-    pub direction: u32,
+    pub direction: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
     draggable: Option<bool>,
     #[serde(rename = "isStage")]
-    is_stage: bool,
+    pub is_stage: bool,
     #[serde(rename = "layerOrder")]
     layer_order: u32,
-    lists: serde_json::Value,
+    #[serde(default)]
+    pub lists: HashMap<String, ListJson>,
     pub name: String,
-    #[serde(rename = "rotationStyle")]
+    #[serde(rename = "rotationStyle", skip_serializing_if = "Option::is_none")]
     rotation_style: Option<String>,
     #[serde(default = "default_size")]
     pub size: f64,
     pub sounds: Vec<SoundJson>,
-    variables: serde_json::Value,
-    visible: Option<bool>,
+    #[serde(default)]
+    pub variables: HashMap<String, VariableJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) visible: Option<bool>,
     volume: u32,
 
     #[serde(default)]
-    x: f64,
+    pub(crate) x: f64,
     #[serde(default)]
-    y: f64,
+    pub(crate) y: f64,
 
     #[serde(flatten)]
     unknown: HashMap<String, serde_json::Value>,
@@ -87,9 +119,9 @@ pub struct CostumeJson {
     pub rotation_center_y: f64,
     #[serde(rename = "dataFormat")]
     data_format: String,
-    #[serde(rename = "bitmapResolution")]
+    #[serde(rename = "bitmapResolution", skip_serializing_if = "Option::is_none")]
     pub bitmap_resolution: Option<f64>,
-    name: String,
+    pub(crate) name: String,
     pub md5ext: String,
 
     #[serde(flatten)]
@@ -113,18 +145,45 @@ pub struct SoundJson {
     unknown: HashMap<String, serde_json::Value>,
 }
 
+/// An entry in `TargetJson::blocks`: either a block, or a variable or list reporter on its own
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum TargetBlock {
+    Block(Box<BlockJson>),
+    Primitive(TopLevelPrimitive),
+}
+
+impl TargetBlock {
+    pub fn block(&self) -> Option<&BlockJson> {
+        match self {
+            TargetBlock::Block(block) => Some(block.as_ref()),
+            TargetBlock::Primitive(_) => None,
+        }
+    }
+}
+
+impl From<BlockJson> for TargetBlock {
+    fn from(block: BlockJson) -> Self {
+        TargetBlock::Block(Box::new(block))
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct BlockJson {
-    pub fields: HashMap<String, Vec<serde_json::Value>>,
-    parent: Option<String>,
+    pub fields: HashMap<String, Field>,
+    pub(crate) parent: Option<String>,
     pub next: Option<String>,
     #[serde(rename = "topLevel")]
     pub top_level: bool,
-    shadow: bool,
+    pub(crate) shadow: bool,
     pub opcode: String,
-    pub inputs: HashMap<String, Vec<serde_json::Value>>,
-    x: Option<u32>,
-    y: Option<u32>,
+    pub inputs: HashMap<String, Input>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    x: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    y: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mutation: Option<Mutation>,
 
     #[serde(flatten)]
     unknown: HashMap<String, serde_json::Value>,
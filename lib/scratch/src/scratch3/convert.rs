@@ -0,0 +1,1252 @@
+use super::{
+    BlockJson,
+    CostumeJson,
+    Field,
+    Input,
+    InputValue,
+    ListJson,
+    Mutation,
+    Primitive,
+    ProjectJson,
+    SoundJson,
+    TargetBlock,
+    TargetJson,
+    VariableJson,
+};
+use crate::types::{
+    CostumeJson as CostumeJson2,
+    ListJson as ListJson2,
+    ProjectJson as ProjectJson2,
+    ScriptJson as ScriptJson2,
+    SoundJson as SoundJson2,
+    SpriteJson as SpriteJson2,
+    VariableJson as VariableJson2,
+};
+use serde_json::{
+    json,
+    Value,
+};
+use std::collections::{
+    BTreeSet,
+    HashMap,
+};
+
+/// The shadow a Scratch 3 input is given when it holds a literal instead of a block
+#[derive(Debug, Clone, Copy)]
+enum Shadow {
+    Number,
+    PositiveNumber,
+    WholeNumber,
+    Integer,
+    Angle,
+    Color,
+    Text,
+    Broadcast,
+    /// A menu shadow block with the given opcode and field name
+    Menu(&'static str, &'static str),
+    Boolean,
+    Substack,
+}
+
+/// Where each Scratch 2 argument ends up on the converted block
+#[derive(Debug, Clone, Copy)]
+enum Arg {
+    Input(&'static str, Shadow),
+    Field(&'static str),
+    Variable(&'static str),
+    List(&'static str),
+    Message(&'static str),
+    /// A field the Scratch 3 block needs that has no Scratch 2 argument
+    Fixed(&'static str, &'static str),
+}
+
+/// Maps a Scratch 2 selector to its Scratch 3 opcode and argument layout
+fn lookup(selector: &str) -> Option<(&'static str, &'static [Arg])> {
+    use self::{
+        Arg::*,
+        Shadow::*,
+    };
+
+    let spec: (&'static str, &'static [Arg]) = match selector {
+        // Motion
+        "forward:" => ("motion_movesteps", &[Input("STEPS", Number)]),
+        "turnRight:" => ("motion_turnright", &[Input("DEGREES", Number)]),
+        "turnLeft:" => ("motion_turnleft", &[Input("DEGREES", Number)]),
+        "heading:" => ("motion_pointindirection", &[Input("DIRECTION", Angle)]),
+        "pointTowards:" => (
+            "motion_pointtowards",
+            &[Input(
+                "TOWARDS",
+                Menu("motion_pointtowards_menu", "TOWARDS"),
+            )],
+        ),
+        "gotoX:y:" => ("motion_gotoxy", &[Input("X", Number), Input("Y", Number)]),
+        "gotoSpriteOrMouse:" => (
+            "motion_goto",
+            &[Input("TO", Menu("motion_goto_menu", "TO"))],
+        ),
+        "glideSecs:toX:y:elapsed:from:" => (
+            "motion_glidesecstoxy",
+            &[
+                Input("SECS", Number),
+                Input("X", Number),
+                Input("Y", Number),
+            ],
+        ),
+        "changeXposBy:" => ("motion_changexby", &[Input("DX", Number)]),
+        "xpos:" => ("motion_setx", &[Input("X", Number)]),
+        "changeYposBy:" => ("motion_changeyby", &[Input("DY", Number)]),
+        "ypos:" => ("motion_sety", &[Input("Y", Number)]),
+        "bounceOffEdge" => ("motion_ifonedgebounce", &[]),
+        "setRotationStyle" => ("motion_setrotationstyle", &[Field("STYLE")]),
+        "xpos" => ("motion_xposition", &[]),
+        "ypos" => ("motion_yposition", &[]),
+        "heading" => ("motion_direction", &[]),
+        "scrollRight" => ("motion_scroll_right", &[Input("DISTANCE", Number)]),
+        "scrollUp" => ("motion_scroll_up", &[Input("DISTANCE", Number)]),
+        "scrollAlign" => ("motion_align_scene", &[Field("ALIGNMENT")]),
+        "xScroll" => ("motion_xscroll", &[]),
+        "yScroll" => ("motion_yscroll", &[]),
+
+        // Looks
+        "say:duration:elapsed:from:" => (
+            "looks_sayforsecs",
+            &[Input("MESSAGE", Text), Input("SECS", Number)],
+        ),
+        "say:" => ("looks_say", &[Input("MESSAGE", Text)]),
+        "think:duration:elapsed:from:" => (
+            "looks_thinkforsecs",
+            &[Input("MESSAGE", Text), Input("SECS", Number)],
+        ),
+        "think:" => ("looks_think", &[Input("MESSAGE", Text)]),
+        "show" => ("looks_show", &[]),
+        "hide" => ("looks_hide", &[]),
+        "hideAll" => ("looks_hideallsprites", &[]),
+        "lookLike:" => (
+            "looks_switchcostumeto",
+            &[Input("COSTUME", Menu("looks_costume", "COSTUME"))],
+        ),
+        "nextCostume" => ("looks_nextcostume", &[]),
+        "startScene" => (
+            "looks_switchbackdropto",
+            &[Input("BACKDROP", Menu("looks_backdrops", "BACKDROP"))],
+        ),
+        "startSceneAndWait" => (
+            "looks_switchbackdroptoandwait",
+            &[Input("BACKDROP", Menu("looks_backdrops", "BACKDROP"))],
+        ),
+        "nextScene" => ("looks_nextbackdrop", &[]),
+        "changeGraphicEffect:by:" => (
+            "looks_changeeffectby",
+            &[Field("EFFECT"), Input("CHANGE", Number)],
+        ),
+        "setGraphicEffect:to:" => (
+            "looks_seteffectto",
+            &[Field("EFFECT"), Input("VALUE", Number)],
+        ),
+        "filterReset" => ("looks_cleargraphiceffects", &[]),
+        "changeSizeBy:" => ("looks_changesizeby", &[Input("CHANGE", Number)]),
+        "setSizeTo:" => ("looks_setsizeto", &[Input("SIZE", Number)]),
+        "changeStretchBy:" => ("looks_changestretchby", &[Input("CHANGE", Number)]),
+        "setStretchTo:" => ("looks_setstretchto", &[Input("STRETCH", Number)]),
+        "comeToFront" => ("looks_gotofrontback", &[Fixed("FRONT_BACK", "front")]),
+        "goBackByLayers:" => (
+            "looks_goforwardbackwardlayers",
+            &[Fixed("FORWARD_BACKWARD", "backward"), Input("NUM", Integer)],
+        ),
+        "costumeIndex" => ("looks_costumenumbername", &[Fixed("NUMBER_NAME", "number")]),
+        "costumeName" => ("looks_costumenumbername", &[Fixed("NUMBER_NAME", "name")]),
+        "backgroundIndex" => (
+            "looks_backdropnumbername",
+            &[Fixed("NUMBER_NAME", "number")],
+        ),
+        "sceneName" => ("looks_backdropnumbername", &[Fixed("NUMBER_NAME", "name")]),
+        "scale" => ("looks_size", &[]),
+
+        // Sound
+        "playSound:" => (
+            "sound_play",
+            &[Input("SOUND_MENU", Menu("sound_sounds_menu", "SOUND_MENU"))],
+        ),
+        "doPlaySoundAndWait" => (
+            "sound_playuntildone",
+            &[Input("SOUND_MENU", Menu("sound_sounds_menu", "SOUND_MENU"))],
+        ),
+        "stopAllSounds" => ("sound_stopallsounds", &[]),
+        "changeVolumeBy:" => ("sound_changevolumeby", &[Input("VOLUME", Number)]),
+        "setVolumeTo:" => ("sound_setvolumeto", &[Input("VOLUME", Number)]),
+        "volume" => ("sound_volume", &[]),
+
+        // Music
+        "drum:duration:elapsed:from:" => (
+            "music_playDrumForBeats",
+            &[
+                Input("DRUM", Menu("music_menu_DRUM", "DRUM")),
+                Input("BEATS", Number),
+            ],
+        ),
+        // Scratch 2's MIDI drums 35 to 81, which Scratch 3 still plays but no longer offers in its menu
+        "playDrum" => (
+            "music_midiPlayDrumForBeats",
+            &[Input("DRUM", Number), Input("BEATS", Number)],
+        ),
+        "rest:elapsed:from:" => ("music_restForBeats", &[Input("BEATS", Number)]),
+        "noteOn:duration:elapsed:from:" => (
+            "music_playNoteForBeats",
+            &[Input("NOTE", Menu("note", "NOTE")), Input("BEATS", Number)],
+        ),
+        "instrument:" => (
+            "music_setInstrument",
+            &[Input(
+                "INSTRUMENT",
+                Menu("music_menu_INSTRUMENT", "INSTRUMENT"),
+            )],
+        ),
+        "midiInstrument:" => ("music_midiSetInstrument", &[Input("INSTRUMENT", Number)]),
+        "changeTempoBy:" => ("music_changeTempo", &[Input("TEMPO", Number)]),
+        "setTempoTo:" => ("music_setTempo", &[Input("TEMPO", Number)]),
+        "tempo" => ("music_getTempo", &[]),
+
+        // Pen
+        "clearPenTrails" => ("pen_clear", &[]),
+        "stampCostume" => ("pen_stamp", &[]),
+        "putPenDown" => ("pen_penDown", &[]),
+        "putPenUp" => ("pen_penUp", &[]),
+        "penColor:" => ("pen_setPenColorToColor", &[Input("COLOR", Color)]),
+        "changePenHueBy:" => ("pen_changePenHueBy", &[Input("HUE", Number)]),
+        "setPenHueTo:" => ("pen_setPenHueToNumber", &[Input("HUE", Number)]),
+        "changePenShadeBy:" => ("pen_changePenShadeBy", &[Input("SHADE", Number)]),
+        "setPenShadeTo:" => ("pen_setPenShadeToNumber", &[Input("SHADE", Number)]),
+        "changePenSizeBy:" => ("pen_changePenSizeBy", &[Input("SIZE", Number)]),
+        "penSize:" => ("pen_setPenSizeTo", &[Input("SIZE", Number)]),
+
+        // Events
+        "whenGreenFlag" => ("event_whenflagclicked", &[]),
+        "whenKeyPressed" => ("event_whenkeypressed", &[Field("KEY_OPTION")]),
+        "whenClicked" => ("event_whenthisspriteclicked", &[]),
+        "whenSceneStarts" => ("event_whenbackdropswitchesto", &[Field("BACKDROP")]),
+        "whenSensorGreaterThan" => (
+            "event_whengreaterthan",
+            &[Field("WHENGREATERTHANMENU"), Input("VALUE", Number)],
+        ),
+        "whenIReceive" => (
+            "event_whenbroadcastreceived",
+            &[Message("BROADCAST_OPTION")],
+        ),
+        "broadcast:" => ("event_broadcast", &[Input("BROADCAST_INPUT", Broadcast)]),
+        "doBroadcastAndWait" => (
+            "event_broadcastandwait",
+            &[Input("BROADCAST_INPUT", Broadcast)],
+        ),
+
+        // Control
+        "wait:elapsed:from:" => ("control_wait", &[Input("DURATION", PositiveNumber)]),
+        "doRepeat" => (
+            "control_repeat",
+            &[Input("TIMES", WholeNumber), Input("SUBSTACK", Substack)],
+        ),
+        "doForever" => ("control_forever", &[Input("SUBSTACK", Substack)]),
+        "doIf" => (
+            "control_if",
+            &[Input("CONDITION", Boolean), Input("SUBSTACK", Substack)],
+        ),
+        "doIfElse" => (
+            "control_if_else",
+            &[
+                Input("CONDITION", Boolean),
+                Input("SUBSTACK", Substack),
+                Input("SUBSTACK2", Substack),
+            ],
+        ),
+        "doWaitUntil" => ("control_wait_until", &[Input("CONDITION", Boolean)]),
+        "doUntil" => (
+            "control_repeat_until",
+            &[Input("CONDITION", Boolean), Input("SUBSTACK", Substack)],
+        ),
+        "doWhile" => (
+            "control_while",
+            &[Input("CONDITION", Boolean), Input("SUBSTACK", Substack)],
+        ),
+        "doForLoop" => (
+            "control_for_each",
+            &[
+                Variable("VARIABLE"),
+                Input("VALUE", Text),
+                Input("SUBSTACK", Substack),
+            ],
+        ),
+        "stopScripts" => ("control_stop", &[Field("STOP_OPTION")]),
+        "whenCloned" => ("control_start_as_clone", &[]),
+        "createCloneOf" => (
+            "control_create_clone_of",
+            &[Input(
+                "CLONE_OPTION",
+                Menu("control_create_clone_of_menu", "CLONE_OPTION"),
+            )],
+        ),
+        "deleteClone" => ("control_delete_this_clone", &[]),
+        "COUNT" => ("control_get_counter", &[]),
+        "INCR_COUNT" => ("control_incr_counter", &[]),
+        "CLR_COUNT" => ("control_clear_counter", &[]),
+        "warpSpeed" => ("control_all_at_once", &[Input("SUBSTACK", Substack)]),
+
+        // Sensing
+        "touching:" => (
+            "sensing_touchingobject",
+            &[Input(
+                "TOUCHINGOBJECTMENU",
+                Menu("sensing_touchingobjectmenu", "TOUCHINGOBJECTMENU"),
+            )],
+        ),
+        "touchingColor:" => ("sensing_touchingcolor", &[Input("COLOR", Color)]),
+        "color:sees:" => (
+            "sensing_coloristouchingcolor",
+            &[Input("COLOR", Color), Input("COLOR2", Color)],
+        ),
+        "distanceTo:" => (
+            "sensing_distanceto",
+            &[Input(
+                "DISTANCETOMENU",
+                Menu("sensing_distancetomenu", "DISTANCETOMENU"),
+            )],
+        ),
+        "doAsk" => ("sensing_askandwait", &[Input("QUESTION", Text)]),
+        "answer" => ("sensing_answer", &[]),
+        "keyPressed:" => (
+            "sensing_keypressed",
+            &[Input(
+                "KEY_OPTION",
+                Menu("sensing_keyoptions", "KEY_OPTION"),
+            )],
+        ),
+        "mousePressed" => ("sensing_mousedown", &[]),
+        "mouseX" => ("sensing_mousex", &[]),
+        "mouseY" => ("sensing_mousey", &[]),
+        "soundLevel" => ("sensing_loudness", &[]),
+        "isLoud" => ("sensing_loud", &[]),
+        "timer" => ("sensing_timer", &[]),
+        "timerReset" => ("sensing_resettimer", &[]),
+        "getAttribute:of:" => (
+            "sensing_of",
+            &[
+                Field("PROPERTY"),
+                Input("OBJECT", Menu("sensing_of_object_menu", "OBJECT")),
+            ],
+        ),
+        "timeAndDate" => ("sensing_current", &[Field("CURRENTMENU")]),
+        "timestamp" => ("sensing_dayssince2000", &[]),
+        "getUserName" => ("sensing_username", &[]),
+
+        // Operators
+        "+" => (
+            "operator_add",
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        "-" => (
+            "operator_subtract",
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        "*" => (
+            "operator_multiply",
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        "/" => (
+            "operator_divide",
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        "randomFrom:to:" => (
+            "operator_random",
+            &[Input("FROM", Number), Input("TO", Number)],
+        ),
+        "<" => (
+            "operator_lt",
+            &[Input("OPERAND1", Text), Input("OPERAND2", Text)],
+        ),
+        "=" => (
+            "operator_equals",
+            &[Input("OPERAND1", Text), Input("OPERAND2", Text)],
+        ),
+        ">" => (
+            "operator_gt",
+            &[Input("OPERAND1", Text), Input("OPERAND2", Text)],
+        ),
+        "&" => (
+            "operator_and",
+            &[Input("OPERAND1", Boolean), Input("OPERAND2", Boolean)],
+        ),
+        "|" => (
+            "operator_or",
+            &[Input("OPERAND1", Boolean), Input("OPERAND2", Boolean)],
+        ),
+        "not" => ("operator_not", &[Input("OPERAND", Boolean)]),
+        "concatenate:with:" => (
+            "operator_join",
+            &[Input("STRING1", Text), Input("STRING2", Text)],
+        ),
+        "letter:of:" => (
+            "operator_letter_of",
+            &[Input("LETTER", WholeNumber), Input("STRING", Text)],
+        ),
+        "stringLength:" => ("operator_length", &[Input("STRING", Text)]),
+        "%" => (
+            "operator_mod",
+            &[Input("NUM1", Number), Input("NUM2", Number)],
+        ),
+        "rounded" => ("operator_round", &[Input("NUM", Number)]),
+        "computeFunction:of:" => (
+            "operator_mathop",
+            &[Field("OPERATOR"), Input("NUM", Number)],
+        ),
+
+        // Data
+        "readVariable" => ("data_variable", &[Variable("VARIABLE")]),
+        "setVar:to:" => (
+            "data_setvariableto",
+            &[Variable("VARIABLE"), Input("VALUE", Text)],
+        ),
+        "changeVar:by:" => (
+            "data_changevariableby",
+            &[Variable("VARIABLE"), Input("VALUE", Number)],
+        ),
+        "showVariable:" => ("data_showvariable", &[Variable("VARIABLE")]),
+        "hideVariable:" => ("data_hidevariable", &[Variable("VARIABLE")]),
+        "contentsOfList:" => ("data_listcontents", &[List("LIST")]),
+        "append:toList:" => ("data_addtolist", &[Input("ITEM", Text), List("LIST")]),
+        "deleteLine:ofList:" => (
+            "data_deleteoflist",
+            &[Input("INDEX", Integer), List("LIST")],
+        ),
+        "insert:at:ofList:" => (
+            "data_insertatlist",
+            &[Input("ITEM", Text), Input("INDEX", Integer), List("LIST")],
+        ),
+        "setLine:ofList:to:" => (
+            "data_replaceitemoflist",
+            &[Input("INDEX", Integer), List("LIST"), Input("ITEM", Text)],
+        ),
+        "getLine:ofList:" => ("data_itemoflist", &[Input("INDEX", Integer), List("LIST")]),
+        "lineCountOfList:" => ("data_lengthoflist", &[List("LIST")]),
+        "list:contains:" => (
+            "data_listcontainsitem",
+            &[List("LIST"), Input("ITEM", Text)],
+        ),
+        "showList:" => ("data_showlist", &[List("LIST")]),
+        "hideList:" => ("data_hidelist", &[List("LIST")]),
+
+        _ => return None,
+    };
+
+    Some(spec)
+}
+
+/// A Scratch 2 block the converter had no Scratch 3 equivalent for
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsupportedBlock {
+    pub target: String,
+    pub selector: String,
+}
+
+pub struct Conversion {
+    pub project: ProjectJson,
+    /// Blocks left out of the converted project, in the order they were found
+    pub unsupported: Vec<UnsupportedBlock>,
+    /// The script each converted block came from, by the block's new ID, as an index into its Scratch 2 object's
+    /// `scripts`
+    pub scripts: HashMap<String, usize>,
+}
+
+/// Converts a Scratch 2 project into an equivalent Scratch 3 project
+pub fn convert(project: &ProjectJson2) -> Conversion {
+    Converter::new().convert(project)
+}
+
+struct Declaration {
+    id: String,
+    name: String,
+    value: Value,
+    is_cloud: bool,
+}
+
+/// The variables and lists declared on one target
+#[derive(Default)]
+struct Scope {
+    variables: Vec<Declaration>,
+    lists: Vec<Declaration>,
+}
+
+impl Scope {
+    fn find(declarations: &[Declaration], name: &str) -> Option<String> {
+        declarations
+            .iter()
+            .find(|d| d.name == name)
+            .map(|d| d.id.clone())
+    }
+
+    fn declare(declarations: &mut Vec<Declaration>, id: String, name: &str, value: Value) {
+        declarations.push(Declaration {
+            id,
+            name: name.to_string(),
+            value,
+            is_cloud: false,
+        });
+    }
+
+    fn variables_json(&self) -> HashMap<String, VariableJson> {
+        self.variables
+            .iter()
+            .map(|v| {
+                let variable = VariableJson {
+                    name: v.name.clone(),
+                    value: v.value.clone(),
+                    is_cloud: v.is_cloud,
+                };
+                (v.id.clone(), variable)
+            })
+            .collect()
+    }
+
+    fn lists_json(&self) -> HashMap<String, ListJson> {
+        self.lists
+            .iter()
+            .map(|l| {
+                let items = l.value.as_array().cloned().unwrap_or_default();
+                (l.id.clone(), ListJson::new(l.name.clone(), items))
+            })
+            .collect()
+    }
+}
+
+/// Argument IDs and types of a custom block, taken from its `procDef`
+struct Procedure {
+    ids: Vec<String>,
+    booleans: Vec<bool>,
+    /// Whether the definition runs without screen refresh, which calls carry in their mutation too
+    warp: bool,
+}
+
+struct Converter {
+    next_id: usize,
+    target: String,
+    blocks: HashMap<String, BlockJson>,
+    globals: Scope,
+    locals: Scope,
+    broadcasts: Vec<(String, String)>,
+    procedures: HashMap<String, Procedure>,
+    extensions: BTreeSet<&'static str>,
+    unsupported: Vec<UnsupportedBlock>,
+    scripts: HashMap<String, usize>,
+}
+
+impl Converter {
+    fn new() -> Self {
+        Converter {
+            next_id: 0,
+            target: String::new(),
+            blocks: HashMap::new(),
+            globals: Scope::default(),
+            locals: Scope::default(),
+            broadcasts: Vec::new(),
+            procedures: HashMap::new(),
+            extensions: BTreeSet::new(),
+            unsupported: Vec::new(),
+            scripts: HashMap::new(),
+        }
+    }
+
+    fn new_id(&mut self) -> String {
+        self.next_id += 1;
+        format!("sn{}", self.next_id)
+    }
+
+    fn convert(mut self, project: &ProjectJson2) -> Conversion {
+        let stage_unknown = &project.unknown;
+        self.globals = self.declare(&project.variables, &project.lists);
+        let stage_blocks = self.convert_scripts("Stage", project.scripts.as_ref());
+
+        let mut sprites = Vec::new();
+        for (i, sprite) in project.children.iter().enumerate() {
+            self.locals = self.declare(&sprite.variables, &sprite.lists);
+            let blocks = self.convert_scripts(&sprite.name, sprite.scripts.as_ref());
+            let locals = std::mem::take(&mut self.locals);
+            sprites.push(convert_sprite(sprite, blocks, &locals, i as u32 + 1));
+        }
+
+        let mut stage_extra = HashMap::new();
+        stage_extra.insert(
+            "tempo".to_string(),
+            stage_unknown
+                .get("tempoBPM")
+                .cloned()
+                .unwrap_or_else(|| json!(60)),
+        );
+        stage_extra.insert(
+            "videoTransparency".to_string(),
+            json!(100.0 - project.video_alpha * 100.0),
+        );
+        stage_extra.insert("videoState".to_string(), json!("off"));
+        stage_extra.insert("textToSpeechLanguage".to_string(), Value::Null);
+
+        let stage = TargetJson {
+            blocks: stage_blocks,
+            broadcasts: self.broadcasts.iter().cloned().collect(),
+            comments: json!({}),
+            costumes: project.costumes.iter().map(convert_costume).collect(),
+            current_costume: project.current_costume_index as u32,
+            direction: 90.0,
+            draggable: None,
+            is_stage: true,
+            layer_order: 0,
+            lists: self.globals.lists_json(),
+            name: String::from("Stage"),
+            rotation_style: None,
+            size: 100.0,
+            sounds: project.sounds.iter().flatten().map(convert_sound).collect(),
+            variables: self.globals.variables_json(),
+            visible: None,
+            volume: 100,
+            x: 0.0,
+            y: 0.0,
+            unknown: stage_extra,
+        };
+
+        let mut targets = vec![stage];
+        targets.extend(sprites);
+
+        Conversion {
+            project: ProjectJson {
+                extensions: self.extensions.iter().map(|e| json!(e)).collect(),
+                meta: json!({
+                    "semver": "3.0.0",
+                    "vm": "0.2.0",
+                    "agent": "scratch-native",
+                }),
+                monitors: Vec::new(),
+                targets,
+                unknown: HashMap::new(),
+            },
+            unsupported: self.unsupported,
+            scripts: self.scripts,
+        }
+    }
+
+    /// Gives IDs to the variables and lists a Scratch 2 object declares
+    fn declare(&mut self, variables: &[VariableJson2], lists: &[ListJson2]) -> Scope {
+        let mut scope = Scope::default();
+
+        for variable in variables {
+            scope.variables.push(Declaration {
+                id: self.new_id(),
+                name: variable.name.clone(),
+                value: match &variable.value {
+                    Value::Null => json!(0),
+                    value => value.clone(),
+                },
+                is_cloud: variable.is_persistent,
+            });
+        }
+
+        for list in lists {
+            let id = self.new_id();
+            let contents = Value::Array(list.contents.clone());
+            Scope::declare(&mut scope.lists, id, &list.name, contents);
+        }
+
+        scope
+    }
+
+    fn variable_id(&mut self, name: &str) -> String {
+        let found = Scope::find(&self.locals.variables, name)
+            .or_else(|| Scope::find(&self.globals.variables, name));
+        if let Some(id) = found {
+            return id;
+        }
+
+        // Scratch 2 tolerates references to variables that were never declared
+        let id = self.new_id();
+        Scope::declare(&mut self.globals.variables, id.clone(), name, json!(0));
+        id
+    }
+
+    fn list_id(&mut self, name: &str) -> String {
+        let found = Scope::find(&self.locals.lists, name)
+            .or_else(|| Scope::find(&self.globals.lists, name));
+        if let Some(id) = found {
+            return id;
+        }
+
+        let id = self.new_id();
+        Scope::declare(&mut self.globals.lists, id.clone(), name, json!([]));
+        id
+    }
+
+    fn broadcast_id(&mut self, name: &str) -> String {
+        if let Some((id, _)) = self.broadcasts.iter().find(|(_, n)| n == name) {
+            return id.clone();
+        }
+
+        let id = self.new_id();
+        self.broadcasts.push((id.clone(), name.to_string()));
+        id
+    }
+
+    fn convert_scripts(
+        &mut self,
+        target: &str,
+        scripts: Option<&Vec<ScriptJson2>>,
+    ) -> HashMap<String, TargetBlock> {
+        self.target = target.to_string();
+        self.blocks = HashMap::new();
+        self.procedures = HashMap::new();
+
+        let scripts = scripts
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .filter_map(|(i, script)| {
+                let blocks = serde_json::to_value(&script.blocks).ok()?;
+                Some((i, script.x, script.y, blocks))
+            })
+            .collect::<Vec<_>>();
+
+        // Calls can appear before the definition they refer to
+        for (_, _, _, blocks) in scripts.iter() {
+            let definition = blocks
+                .get(0)
+                .and_then(|b| b.as_array())
+                .filter(|b| b.first().and_then(|s| s.as_str()) == Some("procDef"));
+            if let Some(definition) = definition {
+                let proccode = definition.get(1).and_then(|p| p.as_str()).unwrap_or("");
+                let warp = definition.get(4).and_then(|w| w.as_bool()).unwrap_or(false);
+                let procedure = self.new_procedure(proccode, warp);
+                self.procedures.insert(proccode.to_string(), procedure);
+            }
+        }
+
+        for (i, x, y, blocks) in scripts {
+            let stack = blocks.as_array().map(|b| b.as_slice()).unwrap_or(&[]);
+            let first_id = self.next_id + 1;
+            let top = self.convert_stack(stack, None);
+
+            // IDs are numbered in order, so everything made since the script started belongs to it
+            for n in first_id..=self.next_id {
+                let id = format!("sn{}", n);
+                if self.blocks.contains_key(&id) {
+                    self.scripts.insert(id, i);
+                }
+            }
+
+            if let Some(id) = top {
+                if let Some(block) = self.blocks.get_mut(&id) {
+                    block.top_level = true;
+                    block.x = Some(f64::from(x));
+                    block.y = Some(f64::from(y));
+                }
+            }
+        }
+
+        std::mem::take(&mut self.blocks)
+            .into_iter()
+            .map(|(id, block)| (id, block.into()))
+            .collect()
+    }
+
+    fn new_procedure(&mut self, proccode: &str, warp: bool) -> Procedure {
+        let booleans = argument_types(proccode);
+        let ids = booleans.iter().map(|_| self.new_id()).collect();
+        Procedure {
+            ids,
+            booleans,
+            warp,
+        }
+    }
+
+    /// Converts a list of blocks, linking them with `next`, and returns the ID of the first one
+    fn convert_stack(&mut self, stack: &[Value], parent: Option<&str>) -> Option<String> {
+        let mut first = None;
+        let mut previous: Option<String> = None;
+
+        for block in stack {
+            let parent = previous.as_deref().or(parent);
+            let id = match self.convert_block(block, parent) {
+                Some(id) => id,
+                None => continue,
+            };
+
+            match previous.as_ref().and_then(|p| self.blocks.get_mut(p)) {
+                Some(previous) => previous.next = Some(id.clone()),
+                None => first = Some(id.clone()),
+            }
+            previous = Some(id);
+        }
+
+        first
+    }
+
+    fn report(&mut self, selector: &str) {
+        self.unsupported.push(UnsupportedBlock {
+            target: self.target.clone(),
+            selector: selector.to_string(),
+        });
+    }
+
+    fn convert_block(&mut self, block: &Value, parent: Option<&str>) -> Option<String> {
+        let args = block.as_array()?;
+        let selector = args.first()?.as_str()?;
+        let args = &args[1..];
+
+        match selector {
+            "procDef" => return self.convert_definition(args, parent),
+            "call" => return self.convert_call(args, parent),
+            "getParam" => {
+                let name = args.first().and_then(|n| n.as_str()).unwrap_or("");
+                let opcode = if args.get(1).and_then(|t| t.as_str()) == Some("b") {
+                    "argument_reporter_boolean"
+                } else {
+                    "argument_reporter_string_number"
+                };
+                let mut reporter = new_block(opcode, parent);
+                reporter.fields.insert("VALUE".into(), Field::new(name));
+                return Some(self.insert(reporter));
+            }
+            "deleteLine:ofList:" if args.first() == Some(&json!("all")) => {
+                let mut block = new_block("data_deletealloflist", parent);
+                let name = args.get(1).and_then(|n| n.as_str()).unwrap_or("");
+                let id = self.list_id(name);
+                block.fields.insert("LIST".into(), Field::with_id(name, id));
+                return Some(self.insert(block));
+            }
+            _ => {}
+        }
+
+        let (opcode, spec) = match lookup(selector) {
+            Some(spec) => spec,
+            None => {
+                self.report(selector);
+                return None;
+            }
+        };
+
+        if let Some(extension) = extension_of(opcode) {
+            self.extensions.insert(extension);
+        }
+
+        let id = self.new_id();
+        let mut converted = new_block(opcode, parent);
+        let mut args = args.iter();
+
+        for arg in spec {
+            match *arg {
+                Arg::Fixed(name, value) => {
+                    converted.fields.insert(name.into(), Field::new(value));
+                }
+                Arg::Field(name) => {
+                    let value = field_value(opcode, args.next());
+                    converted.fields.insert(name.into(), Field::new(value));
+                }
+                Arg::Variable(name) => {
+                    let variable = value_to_string(args.next());
+                    let variable_id = self.variable_id(&variable);
+                    converted
+                        .fields
+                        .insert(name.into(), Field::with_id(variable, variable_id));
+                }
+                Arg::List(name) => {
+                    let list = value_to_string(args.next());
+                    let list_id = self.list_id(&list);
+                    converted
+                        .fields
+                        .insert(name.into(), Field::with_id(list, list_id));
+                }
+                Arg::Message(name) => {
+                    let broadcast = value_to_string(args.next());
+                    let broadcast_id = self.broadcast_id(&broadcast);
+                    converted
+                        .fields
+                        .insert(name.into(), Field::with_id(broadcast, broadcast_id));
+                }
+                Arg::Input(name, shadow) => {
+                    if let Some(input) = self.convert_input(&id, shadow, args.next()) {
+                        converted.inputs.insert(name.into(), input);
+                    }
+                }
+            }
+        }
+
+        if opcode == "control_stop" {
+            let option = converted
+                .fields
+                .get("STOP_OPTION")
+                .map(|f| f.value.as_str())
+                .unwrap_or("");
+            let has_next = option.starts_with("other scripts");
+            converted.mutation = Some(Mutation {
+                has_next: Some(has_next),
+                ..Mutation::new()
+            });
+        }
+
+        self.blocks.insert(id.clone(), converted);
+        Some(id)
+    }
+
+    fn insert(&mut self, block: BlockJson) -> String {
+        let id = self.new_id();
+        self.blocks.insert(id.clone(), block);
+        id
+    }
+
+    fn convert_input(
+        &mut self,
+        parent: &str,
+        shadow: Shadow,
+        value: Option<&Value>,
+    ) -> Option<Input> {
+        let value = value.unwrap_or(&Value::Null);
+
+        match shadow {
+            Shadow::Substack => {
+                let stack = value.as_array()?;
+                let first = self.convert_stack(stack, Some(parent))?;
+                Some(Input::NoShadow(Some(InputValue::Block(first))))
+            }
+            Shadow::Boolean => {
+                if !is_block(value) {
+                    return None;
+                }
+                let id = self.convert_block(value, Some(parent))?;
+                Some(Input::NoShadow(Some(InputValue::Block(id))))
+            }
+            _ if is_block(value) => {
+                let empty = self.shadow(parent, shadow, &Value::Null);
+                let reporter = match self.reporter_primitive(value) {
+                    Some(primitive) => Some(primitive.into()),
+                    None => self
+                        .convert_block(value, Some(parent))
+                        .map(InputValue::Block),
+                };
+
+                match reporter {
+                    Some(reporter) => Some(Input::Obscured(reporter, empty)),
+                    None => Some(Input::Shadow(empty)),
+                }
+            }
+            _ => Some(Input::Shadow(self.shadow(parent, shadow, value))),
+        }
+    }
+
+    /// Variable and list reporters are stored inline in Scratch 3 inputs
+    fn reporter_primitive(&mut self, block: &Value) -> Option<Primitive> {
+        let selector = block.get(0)?.as_str()?;
+        let name = block.get(1)?.as_str()?;
+        match selector {
+            "readVariable" => {
+                let id = self.variable_id(name);
+                Some(Primitive::Variable {
+                    name: name.to_string(),
+                    id,
+                })
+            }
+            "contentsOfList:" => {
+                let id = self.list_id(name);
+                Some(Primitive::List {
+                    name: name.to_string(),
+                    id,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn shadow(&mut self, parent: &str, shadow: Shadow, value: &Value) -> Option<InputValue> {
+        let text = || value_to_string(Some(value));
+        let primitive = match shadow {
+            Shadow::Number => Primitive::Number(text()),
+            Shadow::PositiveNumber => Primitive::PositiveNumber(text()),
+            Shadow::WholeNumber => Primitive::WholeNumber(text()),
+            Shadow::Integer => Primitive::Integer(text()),
+            Shadow::Angle => Primitive::Angle(text()),
+            Shadow::Color => Primitive::Color(color_to_hex(value)),
+            Shadow::Text => Primitive::Text(text()),
+            Shadow::Broadcast => {
+                let name = text();
+                let id = self.broadcast_id(&name);
+                Primitive::Broadcast { name, id }
+            }
+            Shadow::Menu(opcode, field) => {
+                let mut menu = new_block(opcode, Some(parent));
+                menu.shadow = true;
+                menu.fields.insert(field.into(), Field::new(text()));
+                return Some(InputValue::Block(self.insert(menu)));
+            }
+            Shadow::Boolean | Shadow::Substack => return None,
+        };
+        Some(primitive.into())
+    }
+
+    fn convert_definition(&mut self, args: &[Value], parent: Option<&str>) -> Option<String> {
+        let proccode = args.first().and_then(|p| p.as_str()).unwrap_or("");
+        let names = args.get(1).cloned().unwrap_or_else(|| json!([]));
+        let defaults = args.get(2).cloned().unwrap_or_else(|| json!([]));
+        let warp = args.get(3).and_then(|w| w.as_bool()).unwrap_or(false);
+
+        let procedure = match self.procedures.remove(proccode) {
+            Some(procedure) => procedure,
+            None => self.new_procedure(proccode, warp),
+        };
+
+        let definition_id = self.new_id();
+        let prototype_id = self.new_id();
+
+        let mut prototype = new_block("procedures_prototype", Some(&definition_id));
+        prototype.shadow = true;
+        let arg_names = names.as_array().map(|n| n.as_slice()).unwrap_or(&[]);
+        for (i, (arg_id, is_boolean)) in procedure
+            .ids
+            .iter()
+            .zip(procedure.booleans.iter())
+            .enumerate()
+        {
+            let opcode = if *is_boolean {
+                "argument_reporter_boolean"
+            } else {
+                "argument_reporter_string_number"
+            };
+            let mut reporter = new_block(opcode, Some(&prototype_id));
+            reporter.shadow = true;
+            reporter.fields.insert(
+                "VALUE".into(),
+                Field::new(value_to_string(arg_names.get(i))),
+            );
+            let reporter_id = self.insert(reporter);
+            prototype.inputs.insert(
+                arg_id.clone(),
+                Input::Shadow(Some(InputValue::Block(reporter_id))),
+            );
+        }
+        prototype.mutation = Some(Mutation {
+            proccode: Some(convert_proccode(proccode)),
+            argument_ids: Some(procedure.ids.clone()),
+            argument_names: Some(strings(&names)),
+            argument_defaults: Some(strings(&defaults)),
+            warp: Some(warp),
+            ..Mutation::new()
+        });
+        self.blocks.insert(prototype_id.clone(), prototype);
+
+        let mut definition = new_block("procedures_definition", parent);
+        definition.inputs.insert(
+            "custom_block".into(),
+            Input::Shadow(Some(InputValue::Block(prototype_id))),
+        );
+        self.blocks.insert(definition_id.clone(), definition);
+
+        self.procedures.insert(proccode.to_string(), procedure);
+        Some(definition_id)
+    }
+
+    fn convert_call(&mut self, args: &[Value], parent: Option<&str>) -> Option<String> {
+        let proccode = args.first().and_then(|p| p.as_str()).unwrap_or("");
+        if !self.procedures.contains_key(proccode) {
+            let procedure = self.new_procedure(proccode, false);
+            self.procedures.insert(proccode.to_string(), procedure);
+        }
+        let (ids, booleans, warp) = {
+            let procedure = &self.procedures[proccode];
+            (
+                procedure.ids.clone(),
+                procedure.booleans.clone(),
+                procedure.warp,
+            )
+        };
+
+        let id = self.new_id();
+        let mut call = new_block("procedures_call", parent);
+        for (i, (arg_id, is_boolean)) in ids.iter().zip(booleans.iter()).enumerate() {
+            let shadow = if *is_boolean {
+                Shadow::Boolean
+            } else {
+                Shadow::Text
+            };
+            if let Some(input) = self.convert_input(&id, shadow, args.get(i + 1)) {
+                call.inputs.insert(arg_id.clone(), input);
+            }
+        }
+        call.mutation = Some(Mutation {
+            proccode: Some(convert_proccode(proccode)),
+            argument_ids: Some(ids),
+            warp: Some(warp),
+            ..Mutation::new()
+        });
+
+        self.blocks.insert(id.clone(), call);
+        Some(id)
+    }
+}
+
+fn new_block(opcode: &str, parent: Option<&str>) -> BlockJson {
+    BlockJson {
+        fields: HashMap::new(),
+        parent: parent.map(String::from),
+        next: None,
+        top_level: false,
+        shadow: false,
+        opcode: opcode.to_string(),
+        inputs: HashMap::new(),
+        x: None,
+        y: None,
+        mutation: None,
+        unknown: HashMap::new(),
+    }
+}
+
+fn is_block(value: &Value) -> bool {
+    value
+        .as_array()
+        .and_then(|v| v.first())
+        .map(|s| s.is_string())
+        .unwrap_or(false)
+}
+
+fn extension_of(opcode: &str) -> Option<&'static str> {
+    if opcode.starts_with("pen_") {
+        Some("pen")
+    } else if opcode.starts_with("music_") {
+        Some("music")
+    } else {
+        None
+    }
+}
+
+/// Whether each `%s`/`%n`/`%b` argument slot of a proccode is a boolean
+fn argument_types(proccode: &str) -> Vec<bool> {
+    let mut types = Vec::new();
+    let mut chars = proccode.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            match chars.next() {
+                Some('b') => types.push(true),
+                Some('s') | Some('n') => types.push(false),
+                _ => {}
+            }
+        }
+    }
+    types
+}
+
+/// Scratch 3 has no separate number slot, so `%n` becomes `%s`
+fn convert_proccode(proccode: &str) -> String {
+    proccode.replace("%n", "%s")
+}
+
+fn strings(values: &Value) -> Vec<String> {
+    let values = values.as_array().map(|v| v.as_slice()).unwrap_or(&[]);
+    values.iter().map(|v| value_to_string(Some(v))).collect()
+}
+
+fn value_to_string(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// Scratch 2 stores colors as signed ARGB integers
+fn color_to_hex(value: &Value) -> String {
+    match value.as_i64().or_else(|| value.as_f64().map(|f| f as i64)) {
+        Some(color) => format!("#{:06x}", color & 0x00ff_ffff),
+        None => value_to_string(Some(value)),
+    }
+}
+
+/// Adjusts field values whose spelling changed between versions
+fn field_value(opcode: &str, value: Option<&Value>) -> String {
+    let value = value_to_string(value);
+    match opcode {
+        "sensing_current" => value.replace(' ', "").to_uppercase(),
+        "looks_changeeffectby" | "looks_seteffectto" | "event_whengreaterthan" => {
+            value.to_uppercase()
+        }
+        _ => value,
+    }
+}
+
+fn split_md5ext(md5ext: &str) -> (String, String) {
+    match md5ext.rfind('.') {
+        Some(i) => (md5ext[..i].to_string(), md5ext[i + 1..].to_lowercase()),
+        None => (md5ext.to_string(), String::new()),
+    }
+}
+
+fn convert_costume(costume: &CostumeJson2) -> CostumeJson {
+    let (asset_id, data_format) = split_md5ext(&costume.src);
+
+    // Both versions measure the rotation center in the pixels of the stored image, so it only
+    // carries over unchanged if the resolution it was measured at does too
+    CostumeJson {
+        asset_id,
+        rotation_center_x: f64::from(costume.center_x),
+        rotation_center_y: f64::from(costume.center_y),
+        data_format,
+        bitmap_resolution: Some(f64::from(costume.resolution.max(1))),
+        name: costume.name.clone(),
+        md5ext: costume.src.clone(),
+        unknown: HashMap::new(),
+    }
+}
+
+fn convert_sound(sound: &SoundJson2) -> SoundJson {
+    let (asset_id, data_format) = split_md5ext(&sound.src);
+    let number = |key: &str| sound.unknown.get(key).and_then(|n| n.as_u64()).unwrap_or(0) as u32;
+
+    SoundJson {
+        asset_id,
+        data_format,
+        format: sound
+            .unknown
+            .get("format")
+            .cloned()
+            .unwrap_or_else(|| json!("")),
+        md5ext: sound.src.clone(),
+        name: sound.name.clone(),
+        rate: number("rate"),
+        sample_count: number("sampleCount"),
+        unknown: HashMap::new(),
+    }
+}
+
+fn convert_sprite(
+    sprite: &SpriteJson2,
+    blocks: HashMap<String, TargetBlock>,
+    locals: &Scope,
+    layer_order: u32,
+) -> TargetJson {
+    let rotation_style = match sprite.rotation_style.as_str() {
+        "leftRight" => "left-right",
+        "none" => "don't rotate",
+        _ => "all around",
+    };
+
+    TargetJson {
+        blocks,
+        broadcasts: HashMap::new(),
+        comments: json!({}),
+        costumes: sprite.costumes.iter().map(convert_costume).collect(),
+        current_costume: sprite.current_costume_index as u32,
+        direction: sprite.direction,
+        draggable: Some(sprite.is_draggable),
+        is_stage: false,
+        layer_order,
+        lists: locals.lists_json(),
+        name: sprite.name.clone(),
+        rotation_style: Some(rotation_style.to_string()),
+        size: sprite.scale * 100.0,
+        sounds: sprite.sounds.iter().flatten().map(convert_sound).collect(),
+        variables: locals.variables_json(),
+        visible: Some(sprite.visible),
+        volume: 100,
+        x: sprite.x,
+        y: sprite.y,
+        unknown: HashMap::new(),
+    }
+}
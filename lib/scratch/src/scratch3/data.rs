@@ -0,0 +1,91 @@
+use serde::{
+    de::Error as _,
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
+use serde_json::Value;
+
+/// A variable declared on a target, stored as `[name, value]` or `[name, value, true]` if it is a cloud variable
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariableJson {
+    pub name: String,
+    pub value: Value,
+    pub is_cloud: bool,
+}
+
+impl VariableJson {
+    pub fn new<S: Into<String>>(name: S, value: Value) -> Self {
+        VariableJson {
+            name: name.into(),
+            value,
+            is_cloud: false,
+        }
+    }
+}
+
+impl Serialize for VariableJson {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.is_cloud {
+            (&self.name, &self.value, true).serialize(serializer)
+        } else {
+            (&self.name, &self.value).serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for VariableJson {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = Vec::<Value>::deserialize(deserializer)?;
+        let name = values
+            .first()
+            .and_then(Value::as_str)
+            .ok_or_else(|| D::Error::custom("Variable has no name"))?;
+        Ok(VariableJson {
+            name: name.to_string(),
+            value: values.get(1).cloned().unwrap_or_else(|| Value::from(0)),
+            is_cloud: values.get(2).and_then(Value::as_bool).unwrap_or(false),
+        })
+    }
+}
+
+/// A list declared on a target, stored as `[name, [items]]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListJson {
+    pub name: String,
+    pub items: Vec<Value>,
+}
+
+impl ListJson {
+    pub fn new<S: Into<String>>(name: S, items: Vec<Value>) -> Self {
+        ListJson {
+            name: name.into(),
+            items,
+        }
+    }
+}
+
+impl Serialize for ListJson {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.name, &self.items).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ListJson {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = Vec::<Value>::deserialize(deserializer)?;
+        let name = values
+            .first()
+            .and_then(Value::as_str)
+            .ok_or_else(|| D::Error::custom("List has no name"))?;
+        let items = match values.get(1) {
+            Some(Value::Array(items)) => items.clone(),
+            _ => Vec::new(),
+        };
+        Ok(ListJson {
+            name: name.to_string(),
+            items,
+        })
+    }
+}
@@ -0,0 +1,316 @@
+use serde::{
+    de::Error as _,
+    ser::SerializeSeq,
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
+use serde_json::Value;
+
+const INPUT_SAME_BLOCK_SHADOW: u64 = 1;
+const INPUT_BLOCK_NO_SHADOW: u64 = 2;
+const INPUT_DIFF_BLOCK_SHADOW: u64 = 3;
+
+const MATH_NUM_PRIMITIVE: u64 = 4;
+const POSITIVE_NUM_PRIMITIVE: u64 = 5;
+const WHOLE_NUM_PRIMITIVE: u64 = 6;
+const INTEGER_NUM_PRIMITIVE: u64 = 7;
+const ANGLE_NUM_PRIMITIVE: u64 = 8;
+const COLOR_PICKER_PRIMITIVE: u64 = 9;
+const TEXT_PRIMITIVE: u64 = 10;
+const BROADCAST_PRIMITIVE: u64 = 11;
+const VAR_PRIMITIVE: u64 = 12;
+const LIST_PRIMITIVE: u64 = 13;
+
+/// The compact array form Scratch 3 uses for literals and data reporters inside inputs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Primitive {
+    Number(String),
+    PositiveNumber(String),
+    WholeNumber(String),
+    Integer(String),
+    Angle(String),
+    /// A `#rrggbb` color
+    Color(String),
+    Text(String),
+    Broadcast {
+        name: String,
+        id: String,
+    },
+    Variable {
+        name: String,
+        id: String,
+    },
+    List {
+        name: String,
+        id: String,
+    },
+}
+
+impl Primitive {
+    /// The literal text of a number, color or text primitive
+    pub fn literal(&self) -> Option<&str> {
+        match self {
+            Primitive::Number(value)
+            | Primitive::PositiveNumber(value)
+            | Primitive::WholeNumber(value)
+            | Primitive::Integer(value)
+            | Primitive::Angle(value)
+            | Primitive::Color(value)
+            | Primitive::Text(value) => Some(value),
+            Primitive::Broadcast { .. } | Primitive::Variable { .. } | Primitive::List { .. } => {
+                None
+            }
+        }
+    }
+
+    fn from_values(values: &[Value]) -> Result<Self, String> {
+        let kind = values
+            .first()
+            .and_then(Value::as_u64)
+            .ok_or("Primitive has no type")?;
+        let text = |i: usize| values.get(i).map(value_to_string).unwrap_or_default();
+        let named = || (text(1), text(2));
+
+        Ok(match kind {
+            MATH_NUM_PRIMITIVE => Primitive::Number(text(1)),
+            POSITIVE_NUM_PRIMITIVE => Primitive::PositiveNumber(text(1)),
+            WHOLE_NUM_PRIMITIVE => Primitive::WholeNumber(text(1)),
+            INTEGER_NUM_PRIMITIVE => Primitive::Integer(text(1)),
+            ANGLE_NUM_PRIMITIVE => Primitive::Angle(text(1)),
+            COLOR_PICKER_PRIMITIVE => Primitive::Color(text(1)),
+            TEXT_PRIMITIVE => Primitive::Text(text(1)),
+            BROADCAST_PRIMITIVE => {
+                let (name, id) = named();
+                Primitive::Broadcast { name, id }
+            }
+            VAR_PRIMITIVE => {
+                let (name, id) = named();
+                Primitive::Variable { name, id }
+            }
+            LIST_PRIMITIVE => {
+                let (name, id) = named();
+                Primitive::List { name, id }
+            }
+            kind => return Err(format!("Unknown primitive type {}", kind)),
+        })
+    }
+
+    fn to_values(&self) -> Vec<Value> {
+        let literal = |kind: u64, value: &String| vec![kind.into(), value.clone().into()];
+        let named = |kind: u64, name: &String, id: &String| {
+            vec![kind.into(), name.clone().into(), id.clone().into()]
+        };
+
+        match self {
+            Primitive::Number(value) => literal(MATH_NUM_PRIMITIVE, value),
+            Primitive::PositiveNumber(value) => literal(POSITIVE_NUM_PRIMITIVE, value),
+            Primitive::WholeNumber(value) => literal(WHOLE_NUM_PRIMITIVE, value),
+            Primitive::Integer(value) => literal(INTEGER_NUM_PRIMITIVE, value),
+            Primitive::Angle(value) => literal(ANGLE_NUM_PRIMITIVE, value),
+            Primitive::Color(value) => literal(COLOR_PICKER_PRIMITIVE, value),
+            Primitive::Text(value) => literal(TEXT_PRIMITIVE, value),
+            Primitive::Broadcast { name, id } => named(BROADCAST_PRIMITIVE, name, id),
+            Primitive::Variable { name, id } => named(VAR_PRIMITIVE, name, id),
+            Primitive::List { name, id } => named(LIST_PRIMITIVE, name, id),
+        }
+    }
+}
+
+/// A variable or list reporter lying loose in the editor, which a target's blocks store as `[12, name, id, x, y]`
+/// rather than as a block
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopLevelPrimitive {
+    pub primitive: Primitive,
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Serialize for TopLevelPrimitive {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut values = self.primitive.to_values();
+        values.push(self.x.into());
+        values.push(self.y.into());
+        values.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TopLevelPrimitive {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = Vec::<Value>::deserialize(deserializer)?;
+        let primitive = Primitive::from_values(&values).map_err(D::Error::custom)?;
+        let position = |i: usize| values.get(i).and_then(Value::as_f64).unwrap_or(0.0);
+        Ok(TopLevelPrimitive {
+            primitive,
+            x: position(3),
+            y: position(4),
+        })
+    }
+}
+
+/// What fills an input slot: the ID of another block, or an inline primitive
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputValue {
+    Block(String),
+    Primitive(Primitive),
+}
+
+impl InputValue {
+    fn from_value(value: &Value) -> Result<Option<Self>, String> {
+        match value {
+            Value::Null => Ok(None),
+            Value::String(id) => Ok(Some(InputValue::Block(id.clone()))),
+            Value::Array(values) => Primitive::from_values(values).map(|p| Some(p.into())),
+            value => Err(format!("Invalid input value {}", value)),
+        }
+    }
+
+    fn to_value(value: Option<&Self>) -> Value {
+        match value {
+            Some(InputValue::Block(id)) => id.clone().into(),
+            Some(InputValue::Primitive(primitive)) => primitive.to_values().into(),
+            None => Value::Null,
+        }
+    }
+}
+
+impl From<Primitive> for InputValue {
+    fn from(primitive: Primitive) -> Self {
+        InputValue::Primitive(primitive)
+    }
+}
+
+/// A block input, serialized as `[1, shadow]`, `[2, block]` or `[3, block, shadow]`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    /// Only the shadow is present, such as a number literal or a menu
+    Shadow(Option<InputValue>),
+    /// A block with nothing behind it, used by boolean slots and substacks
+    NoShadow(Option<InputValue>),
+    /// A block dropped on top of a shadow, which is kept for when the block is removed
+    Obscured(InputValue, Option<InputValue>),
+}
+
+impl Input {
+    /// The value Scratch evaluates: the top block if there is one, otherwise the shadow
+    pub fn value(&self) -> Option<&InputValue> {
+        match self {
+            Input::Shadow(value) | Input::NoShadow(value) => value.as_ref(),
+            Input::Obscured(value, _) => Some(value),
+        }
+    }
+
+    pub fn shadow(&self) -> Option<&InputValue> {
+        match self {
+            Input::Shadow(shadow) | Input::Obscured(_, shadow) => shadow.as_ref(),
+            Input::NoShadow(_) => None,
+        }
+    }
+
+    /// The ID of the block this input evaluates, if it is not an inline primitive
+    pub fn block(&self) -> Option<&str> {
+        match self.value() {
+            Some(InputValue::Block(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn primitive(&self) -> Option<&Primitive> {
+        match self.value() {
+            Some(InputValue::Primitive(primitive)) => Some(primitive),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for Input {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (kind, values) = match self {
+            Input::Shadow(shadow) => (INPUT_SAME_BLOCK_SHADOW, vec![shadow.as_ref()]),
+            Input::NoShadow(block) => (INPUT_BLOCK_NO_SHADOW, vec![block.as_ref()]),
+            Input::Obscured(block, shadow) => {
+                (INPUT_DIFF_BLOCK_SHADOW, vec![Some(block), shadow.as_ref()])
+            }
+        };
+
+        let mut seq = serializer.serialize_seq(Some(values.len() + 1))?;
+        seq.serialize_element(&kind)?;
+        for value in values {
+            seq.serialize_element(&InputValue::to_value(value))?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Input {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = Vec::<Value>::deserialize(deserializer)?;
+        let value = |i: usize| {
+            InputValue::from_value(values.get(i).unwrap_or(&Value::Null)).map_err(D::Error::custom)
+        };
+
+        match values.first().and_then(Value::as_u64) {
+            Some(INPUT_SAME_BLOCK_SHADOW) => Ok(Input::Shadow(value(1)?)),
+            Some(INPUT_BLOCK_NO_SHADOW) => Ok(Input::NoShadow(value(1)?)),
+            Some(INPUT_DIFF_BLOCK_SHADOW) => match value(1)? {
+                Some(block) => Ok(Input::Obscured(block, value(2)?)),
+                None => Ok(Input::Shadow(value(2)?)),
+            },
+            _ => Err(D::Error::custom(format!("Invalid input {:?}", values))),
+        }
+    }
+}
+
+/// A block field, serialized as `[value, id]`; only variable, list and broadcast fields have IDs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub value: String,
+    pub id: Option<String>,
+}
+
+impl Field {
+    pub fn new<S: Into<String>>(value: S) -> Self {
+        Field {
+            value: value.into(),
+            id: None,
+        }
+    }
+
+    pub fn with_id<S: Into<String>, I: Into<String>>(value: S, id: I) -> Self {
+        Field {
+            value: value.into(),
+            id: Some(id.into()),
+        }
+    }
+}
+
+impl Serialize for Field {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.value, &self.id).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = Vec::<Value>::deserialize(deserializer)?;
+        let value = values
+            .first()
+            .map(value_to_string)
+            .ok_or_else(|| D::Error::custom("Field has no value"))?;
+        let id = values.get(1).and_then(|id| match id {
+            Value::Null => None,
+            id => Some(value_to_string(id)),
+        });
+        Ok(Field { value, id })
+    }
+}
+
+/// Scratch freely mixes numbers and strings for literals, so both are read as text
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
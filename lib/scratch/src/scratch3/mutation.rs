@@ -0,0 +1,153 @@
+use serde::{
+    de::Error as _,
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
+use serde_json::{
+    Map,
+    Value,
+};
+
+/// Extra data a block carries, most importantly the signature of a custom block.
+/// Scratch stores the argument lists as JSON encoded strings and booleans as `"true"`/`"false"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mutation {
+    pub proccode: Option<String>,
+    pub argument_ids: Option<Vec<String>>,
+    /// Only set on `procedures_prototype`
+    pub argument_names: Option<Vec<String>>,
+    /// Only set on `procedures_prototype`
+    pub argument_defaults: Option<Vec<String>>,
+    /// Run without screen refresh
+    pub warp: Option<bool>,
+    /// Set on `control_stop`, which only allows blocks below it when stopping other scripts
+    pub has_next: Option<bool>,
+
+    pub(crate) unknown: Map<String, Value>,
+}
+
+impl Mutation {
+    pub fn new() -> Self {
+        let mut unknown = Map::new();
+        unknown.insert("tagName".into(), Value::from("mutation"));
+        unknown.insert("children".into(), Value::Array(Vec::new()));
+        Mutation {
+            proccode: None,
+            argument_ids: None,
+            argument_names: None,
+            argument_defaults: None,
+            warp: None,
+            has_next: None,
+            unknown,
+        }
+    }
+
+    pub fn proccode(&self) -> &str {
+        self.proccode.as_deref().unwrap_or("")
+    }
+
+    pub fn argument_ids(&self) -> &[String] {
+        self.argument_ids.as_deref().unwrap_or(&[])
+    }
+
+    pub fn argument_names(&self) -> &[String] {
+        self.argument_names.as_deref().unwrap_or(&[])
+    }
+
+    pub fn argument_defaults(&self) -> &[String] {
+        self.argument_defaults.as_deref().unwrap_or(&[])
+    }
+
+    pub fn warp(&self) -> bool {
+        self.warp.unwrap_or(false)
+    }
+}
+
+impl Default for Mutation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_bool(value: Value) -> Option<bool> {
+    match value {
+        Value::Bool(b) => Some(b),
+        Value::String(s) => Some(s == "true"),
+        _ => None,
+    }
+}
+
+/// Reads a JSON encoded list of strings, tolerating numbers and lists that were never encoded
+fn parse_list(value: Value) -> Result<Vec<String>, String> {
+    let list = match value {
+        Value::String(s) => serde_json::from_str(&s).map_err(|e| e.to_string())?,
+        value => value,
+    };
+    match list {
+        Value::Array(items) => Ok(items
+            .into_iter()
+            .map(|item| match item {
+                Value::String(s) => s,
+                Value::Null => String::new(),
+                item => item.to_string(),
+            })
+            .collect()),
+        _ => Err("Mutation argument list is not a list".into()),
+    }
+}
+
+impl Serialize for Mutation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = self.unknown.clone();
+        let encode = |list: &Vec<String>| Value::String(Value::from(list.clone()).to_string());
+        if let Some(proccode) = &self.proccode {
+            map.insert("proccode".into(), Value::from(proccode.as_str()));
+        }
+        if let Some(ids) = &self.argument_ids {
+            map.insert("argumentids".into(), encode(ids));
+        }
+        if let Some(names) = &self.argument_names {
+            map.insert("argumentnames".into(), encode(names));
+        }
+        if let Some(defaults) = &self.argument_defaults {
+            map.insert("argumentdefaults".into(), encode(defaults));
+        }
+        if let Some(warp) = self.warp {
+            map.insert("warp".into(), Value::from(warp.to_string()));
+        }
+        if let Some(has_next) = self.has_next {
+            map.insert("hasnext".into(), Value::from(has_next.to_string()));
+        }
+        map.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Mutation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut unknown = Map::<String, Value>::deserialize(deserializer)?;
+        let mut list = |key: &str| {
+            unknown
+                .remove(key)
+                .map(parse_list)
+                .transpose()
+                .map_err(D::Error::custom)
+        };
+        let argument_ids = list("argumentids")?;
+        let argument_names = list("argumentnames")?;
+        let argument_defaults = list("argumentdefaults")?;
+
+        Ok(Mutation {
+            proccode: unknown
+                .remove("proccode")
+                .and_then(|p| p.as_str().map(String::from)),
+            argument_ids,
+            argument_names,
+            argument_defaults,
+            warp: unknown.remove("warp").and_then(parse_bool),
+            has_next: unknown.remove("hasnext").and_then(parse_bool),
+            unknown,
+        })
+    }
+}
@@ -0,0 +1,314 @@
+use super::{
+    BlockJson,
+    Field,
+    Input,
+    InputValue,
+    Mutation,
+    Primitive,
+    TargetBlock,
+};
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+/// A stack of blocks hanging from a top-level block, usually a hat
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub x: f64,
+    pub y: f64,
+    pub blocks: Vec<Block>,
+}
+
+impl Script {
+    /// The block that starts this script, if it is a hat
+    pub fn hat(&self) -> Option<&Block> {
+        self.blocks.first().filter(|block| is_hat(&block.opcode))
+    }
+}
+
+/// A block with its reporters and C-block bodies nested inside it
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub id: String,
+    pub opcode: String,
+    pub shadow: bool,
+    pub inputs: HashMap<String, Expr>,
+    pub fields: HashMap<String, Field>,
+    /// The stacks inside C-blocks, keyed by input name like `SUBSTACK` and `SUBSTACK2`
+    pub substacks: HashMap<String, Vec<Block>>,
+    pub mutation: Option<Mutation>,
+}
+
+impl Block {
+    pub fn input(&self, name: &str) -> &Expr {
+        self.inputs.get(name).unwrap_or(&Expr::Empty)
+    }
+
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(|f| f.value.as_str())
+    }
+
+    pub fn substack(&self, name: &str) -> &[Block] {
+        self.substacks.get(name).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+/// What an input evaluates to
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Primitive(Primitive),
+    /// A reporter, or the shadow block of a menu
+    Block(Box<Block>),
+    Empty,
+}
+
+impl Expr {
+    /// The literal text of a primitive, or the chosen option of a menu shadow
+    pub fn literal(&self) -> Option<&str> {
+        match self {
+            Expr::Primitive(primitive) => primitive.literal(),
+            Expr::Block(block) if block.shadow && block.inputs.is_empty() => {
+                block.fields.values().next().map(|f| f.value.as_str())
+            }
+            Expr::Block(_) | Expr::Empty => None,
+        }
+    }
+}
+
+/// Something wrong with how a target's blocks link together
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptIssue {
+    /// A block links back to a block it is nested inside of
+    Cycle { id: String, from: String },
+    /// A block is linked from more than one place
+    Shared { id: String, from: String },
+    /// A link points to a block that does not exist
+    Missing { id: String, from: String },
+    /// A block's `parent` does not match the block that links to it
+    WrongParent { id: String, parent: String },
+    /// A block that is not top-level and that no reachable block links to
+    Orphan { id: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct Scripts {
+    pub scripts: Vec<Script>,
+    pub issues: Vec<ScriptIssue>,
+}
+
+pub fn is_hat(opcode: &str) -> bool {
+    opcode.starts_with("event_when")
+        || opcode == "control_start_as_clone"
+        || opcode == "procedures_definition"
+}
+
+fn is_substack(input: &str) -> bool {
+    input.starts_with("SUBSTACK")
+}
+
+struct Builder<'a> {
+    blocks: &'a HashMap<String, TargetBlock>,
+    visited: HashSet<&'a str>,
+    /// The blocks being built, so links back into them can be told apart from shared blocks
+    path: Vec<&'a str>,
+    issues: Vec<ScriptIssue>,
+}
+
+impl<'a> Builder<'a> {
+    /// Looks up a linked block, reporting it and returning `None` if it can't be visited
+    fn enter(&mut self, id: &'a str, from: &str) -> Option<&'a BlockJson> {
+        let block = match self.blocks.get(id).and_then(TargetBlock::block) {
+            Some(block) => block,
+            None => {
+                self.issues.push(ScriptIssue::Missing {
+                    id: id.to_string(),
+                    from: from.to_string(),
+                });
+                return None;
+            }
+        };
+
+        if !self.visited.insert(id) {
+            let issue = if self.path.contains(&id) {
+                ScriptIssue::Cycle {
+                    id: id.to_string(),
+                    from: from.to_string(),
+                }
+            } else {
+                ScriptIssue::Shared {
+                    id: id.to_string(),
+                    from: from.to_string(),
+                }
+            };
+            self.issues.push(issue);
+            return None;
+        }
+
+        if !from.is_empty() && block.parent.as_deref() != Some(from) {
+            self.issues.push(ScriptIssue::WrongParent {
+                id: id.to_string(),
+                parent: from.to_string(),
+            });
+        }
+
+        Some(block)
+    }
+
+    /// Follows `next` links from `id`, where `from` is the block linking to it
+    fn stack(&mut self, id: &'a str, from: &str) -> Vec<Block> {
+        let depth = self.path.len();
+        let mut stack = Vec::new();
+        let mut next = Some((id, from.to_string()));
+
+        while let Some((id, from)) = next.take() {
+            let json = match self.enter(id, &from) {
+                Some(json) => json,
+                None => break,
+            };
+            self.path.push(id);
+            stack.push(self.block(id, json));
+            next = json.next.as_deref().map(|next| (next, id.to_string()));
+        }
+
+        self.path.truncate(depth);
+        stack
+    }
+
+    fn block(&mut self, id: &'a str, json: &'a BlockJson) -> Block {
+        let mut inputs = HashMap::new();
+        let mut substacks = HashMap::new();
+
+        for (name, input) in json.inputs.iter() {
+            if is_substack(name) {
+                if let Some(first) = input.block() {
+                    substacks.insert(name.clone(), self.stack(first, id));
+                }
+            } else {
+                let expr = self.expr(input, id);
+                inputs.insert(name.clone(), expr);
+            }
+        }
+
+        Block {
+            id: id.to_string(),
+            opcode: json.opcode.clone(),
+            shadow: json.shadow,
+            inputs,
+            fields: json.fields.clone(),
+            substacks,
+            mutation: json.mutation.clone(),
+        }
+    }
+
+    fn expr(&mut self, input: &'a Input, parent: &'a str) -> Expr {
+        // An obscured shadow is never evaluated, but it still belongs to this block
+        if let (Input::Obscured(..), Some(InputValue::Block(shadow))) = (input, input.shadow()) {
+            if self.enter(shadow, parent).is_some() {
+                self.visit_all(shadow);
+            }
+        }
+
+        match input.value() {
+            Some(InputValue::Primitive(primitive)) => Expr::Primitive(primitive.clone()),
+            Some(InputValue::Block(id)) => match self.enter(id, parent) {
+                Some(json) => {
+                    self.path.push(id);
+                    let block = self.block(id, json);
+                    self.path.pop();
+                    Expr::Block(Box::new(block))
+                }
+                None => Expr::Empty,
+            },
+            None => Expr::Empty,
+        }
+    }
+
+    /// Marks everything inside an already entered block as reached, without building it
+    fn visit_all(&mut self, id: &'a str) {
+        if let Some(json) = self.blocks.get(id).and_then(TargetBlock::block) {
+            self.path.push(id);
+            self.block(id, json);
+            self.path.pop();
+        }
+    }
+}
+
+/// Rebuilds the scripts of a target from its flat block map. Loose variable and list reporters aren't part of any script.
+pub fn build_scripts(blocks: &HashMap<String, TargetBlock>) -> Scripts {
+    let mut builder = Builder {
+        blocks,
+        visited: HashSet::new(),
+        path: Vec::new(),
+        issues: Vec::new(),
+    };
+
+    let blocks = blocks
+        .iter()
+        .filter_map(|(id, block)| Some((id.as_str(), block.block()?)))
+        .collect::<HashMap<_, _>>();
+
+    let mut top_level = blocks
+        .iter()
+        .map(|(id, block)| (*id, *block))
+        .filter(|(_, block)| block.top_level)
+        .collect::<Vec<_>>();
+    top_level.sort_by(|(a_id, a), (b_id, b)| {
+        let position = |block: &BlockJson| (block.y.unwrap_or(0.0), block.x.unwrap_or(0.0));
+        position(a)
+            .partial_cmp(&position(b))
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a_id.cmp(b_id))
+    });
+
+    let mut scripts = Vec::new();
+    for (id, block) in top_level {
+        let stack = builder.stack(id, "");
+        if !stack.is_empty() {
+            scripts.push(Script {
+                x: block.x.unwrap_or(0.0),
+                y: block.y.unwrap_or(0.0),
+                blocks: stack,
+            });
+        }
+    }
+
+    // Anything left over either hangs off a parent that doesn't exist or only loops back on itself
+    let mut unreached = blocks
+        .keys()
+        .copied()
+        .filter(|id| !builder.visited.contains(id))
+        .collect::<Vec<_>>();
+    unreached.sort();
+
+    for id in unreached.iter() {
+        let parent = blocks[id].parent.as_deref();
+        if parent
+            .filter(|parent| blocks.contains_key(*parent))
+            .is_none()
+        {
+            builder
+                .issues
+                .push(ScriptIssue::Orphan { id: id.to_string() });
+            builder.stack(id, "");
+        }
+    }
+
+    for id in unreached {
+        if !builder.visited.contains(id) {
+            let issues = builder.issues.len();
+            builder.stack(id, "");
+            // Blocks whose parents point at each other without ever linking to each other
+            if builder.issues.len() == issues {
+                builder
+                    .issues
+                    .push(ScriptIssue::Orphan { id: id.to_string() });
+            }
+        }
+    }
+
+    Scripts {
+        scripts,
+        issues: builder.issues,
+    }
+}
@@ -1,23 +1,72 @@
 use crate::{
     client::Client,
+    download::{
+        DownloadOptions,
+        DownloadProgress,
+        ProgressFn,
+    },
+    ir::{
+        self,
+        BinaryOp,
+        Expr,
+        Hat,
+        Lowered,
+        Procedure,
+        Program,
+        Statement,
+    },
     util::{
         make_command,
         FileCreater,
     },
+    value::Value,
     Project,
-    ProjectData,
     ScratchError,
     ScratchResult,
 };
-use std::process::Stdio;
+use std::{
+    collections::HashMap,
+    process::Stdio,
+};
 
 mod python;
 
+/// Scratch's cast to a number, for values whose type is only known when the script runs, like custom block
+/// arguments. Follows `Value::to_number`: text that isn't a number is 0, but `Infinity` and hex are read.
+const TO_NUMBER_PY: &str = r#"def to_number(value):
+	if isinstance(value, bool):
+		return float(value)
+	if isinstance(value, (int, float)):
+		return 0.0 if value != value else value
+	text = str(value).strip()
+	if text in ('Infinity', '+Infinity'):
+		return float('inf')
+	if text == '-Infinity':
+		return float('-inf')
+	try:
+		if text[:2].lower() in ('0x', '0b', '0o'):
+			return float(int(text, 0))
+		if any(c.isalpha() and c not in 'eE' or c == '_' for c in text):
+			return 0.0
+		return float(text) if text else 0.0
+	except ValueError:
+		return 0.0
+"#;
+
+/// JavaScript's `Math.round`, which Scratch rounds with, following `value::js_round`. Python's own `round` rounds
+/// halves to even, so `repeat 2.5` would only run twice. Needs `math`.
+pub const MATH_ROUND_PY: &str = r#"def math_round(n):
+	if n != n or n in (float('inf'), float('-inf')):
+		return n
+	floor = math.floor(n)
+	return floor + 1 if n - floor >= 0.5 else floor
+"#;
+
 fn test_import(data: &str) -> ScratchResult<bool> {
     Ok(make_command()
         .arg("python")
         .arg("-c")
-        .arg(&format!("import {}", data))
+        .arg(format!("import {}", data))
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .output()
@@ -28,6 +77,10 @@ fn test_import(data: &str) -> ScratchResult<bool> {
 
 pub trait Target {
     fn name() -> &'static str;
+    /// Whether the code this target generates handles a Scratch 3 opcode
+    fn supports(_opcode: &str) -> bool {
+        false
+    }
     fn init(&mut self) -> ScratchResult<()>;
     fn build(&mut self, project: &Project) -> ScratchResult<()>;
     fn run(&mut self, project: &Project) -> ScratchResult<()>;
@@ -36,18 +89,38 @@ pub trait Target {
 pub struct PyGameTarget {
     has_pygame: bool,
     has_pynanosvg: bool,
+    download_options: DownloadOptions,
+    progress: Option<Box<ProgressFn>>,
 }
 
-struct CodeGen {
+struct CodeGen<'a> {
     data: String,
     tab_index: usize,
+    /// Python function names of the sprite's custom blocks, by proccode
+    procedures: &'a HashMap<String, String>,
+    /// Argument names of the custom block being written, which become `arg0`, `arg1`...
+    parameters: Vec<String>,
+    /// Set while writing a custom block that runs without screen refresh, which never yields
+    warp: bool,
 }
 
-impl CodeGen {
-    pub fn new() -> Self {
+impl<'a> CodeGen<'a> {
+    pub fn new(procedures: &'a HashMap<String, String>) -> Self {
         CodeGen {
             data: String::new(),
             tab_index: 0,
+            procedures,
+            parameters: Vec::new(),
+            warp: false,
+        }
+    }
+
+    /// Where a script gives the other scripts a turn
+    fn yield_point(&self) -> &'static str {
+        if self.warp {
+            "pass"
+        } else {
+            "yield"
         }
     }
 
@@ -56,6 +129,213 @@ impl CodeGen {
         self.data += data;
         self.data.push('\n');
     }
+
+    fn write_stack(&mut self, sprite: &str, stack: &[Statement]) {
+        if stack.is_empty() {
+            self.writeln("pass");
+        }
+        for statement in stack {
+            self.write_statement(sprite, statement);
+        }
+    }
+
+    /// Writes a loop whose body yields once per iteration, like Scratch does between frames
+    fn write_loop(&mut self, sprite: &str, header: &str, body: &[Statement]) {
+        self.writeln(header);
+        self.tab_index += 1;
+        self.write_stack(sprite, body);
+        self.writeln(self.yield_point());
+        self.tab_index -= 1;
+    }
+
+    /// Writes a custom block as a generator function, so calls can wait on it with `yield from`
+    fn write_procedure(&mut self, sprite: &str, function: &str, procedure: &Procedure) {
+        let parameters = (0..procedure.parameters.len())
+            .map(|i| format!("arg{}", i))
+            .collect::<Vec<_>>();
+        self.writeln(&format!("def {}({}):", function, parameters.join(", ")));
+        self.tab_index += 1;
+        self.parameters = procedure
+            .parameters
+            .iter()
+            .map(|p| p.name.clone())
+            .collect();
+        self.warp = procedure.warp;
+        self.write_stack(sprite, &procedure.body);
+        self.writeln("return");
+        self.writeln("yield");
+        self.parameters.clear();
+        self.warp = false;
+        self.tab_index -= 1;
+    }
+
+    /// The Python parameter an argument reporter reads
+    fn parameter(&self, name: &str) -> Option<String> {
+        let index = self.parameters.iter().position(|p| p == name)?;
+        Some(format!("arg{}", index))
+    }
+
+    /// A Python expression for a numeric input, or `0` for a reporter there's no code for
+    fn number(&self, sprite: &str, expr: &Expr) -> String {
+        self.reporter(sprite, expr).unwrap_or_else(|| "0".into())
+    }
+
+    /// A Python expression for a numeric input, if there's code for it
+    fn reporter(&self, sprite: &str, expr: &Expr) -> Option<String> {
+        let code = match expr {
+            Expr::Literal(literal) => python_number(Value::from(literal.as_str()).to_number()),
+            Expr::Argument(name) => match self.parameter(name) {
+                Some(parameter) => format!("to_number({})", parameter),
+                None => "0".into(),
+            },
+            Expr::Binary { op, lhs, rhs } => {
+                let op = match op {
+                    BinaryOp::Add => "+",
+                    BinaryOp::Subtract => "-",
+                    BinaryOp::Multiply => "*",
+                    BinaryOp::Divide => "/",
+                    _ => return None,
+                };
+                let lhs = self.number(sprite, lhs);
+                let rhs = self.number(sprite, rhs);
+                format!("({} {} {})", lhs, op, rhs)
+            }
+            Expr::XPosition => format!("{}.x", sprite),
+            Expr::YPosition => format!("{}.y", sprite),
+            Expr::Direction => format!("{}.direction", sprite),
+            _ => return None,
+        };
+        Some(code)
+    }
+
+    /// A Python expression for an input that may be text, like a custom block argument
+    fn value(&self, sprite: &str, expr: &Expr) -> String {
+        match expr {
+            Expr::Literal(literal) => python_string(literal),
+            Expr::Argument(name) => self.parameter(name).unwrap_or_else(|| "''".into()),
+            expr => self.number(sprite, expr),
+        }
+    }
+
+    fn write_statement(&mut self, sprite: &str, statement: &Statement) {
+        if !self.write_supported(sprite, statement) {
+            self.writeln(&format!("print('NOT IMPLEMENTED: {}')", statement.opcode()));
+        }
+    }
+
+    /// Writes a statement there's real code for, giving `false` without writing anything for the rest
+    fn write_supported(&mut self, sprite: &str, statement: &Statement) -> bool {
+        match statement {
+            Statement::Forever(body) => {
+                self.write_loop(sprite, "while True:", body);
+            }
+            Statement::Repeat { times, body } => {
+                let header = format!(
+                    "for _ in range(math_round({})):",
+                    self.number(sprite, times)
+                );
+                self.write_loop(sprite, &header, body);
+            }
+            Statement::PlaySound { sound, wait: true } => {
+                let sound_name = sound.as_literal().unwrap_or("");
+                self.writeln(&format!("sound_list['{}'].play()", sound_name));
+                self.writeln("t0 = time.time()");
+                self.writeln(&format!(
+                    "while time.time() - t0 < sound_list['{}'].get_length():",
+                    sound_name
+                ));
+                self.writeln(&format!("\t{}", self.yield_point()));
+            }
+            Statement::ChangeVolumeBy(change) => {
+                self.writeln("for sound in sound_list:");
+                self.tab_index += 1;
+                self.writeln("current_volume = sound_list[sound].get_volume()");
+                self.writeln(&format!(
+                    "sound_list[sound].set_volume(current_volume + {})",
+                    self.number(sprite, change)
+                ));
+                self.tab_index -= 1;
+            }
+            // Only going to the mouse pointer is supported
+            Statement::GoTo(target) if target.as_literal() == Some("_mouse_") => {
+                self.writeln("pos = pygame.mouse.get_pos()");
+                self.writeln(&format!("{}.x = -(480 / 2) + pos[0]", sprite));
+                self.writeln(&format!("{}.y = (360 / 2) - pos[1]", sprite));
+            }
+            Statement::TurnRight(turn) => {
+                let turn = self.number(sprite, turn);
+                self.writeln(&format!("{}.direction += {}", sprite, turn));
+            }
+            Statement::TurnLeft(turn) => {
+                let turn = self.number(sprite, turn);
+                self.writeln(&format!("{}.direction -= {}", sprite, turn));
+            }
+            Statement::Call { proccode, args } => match self.procedures.get(proccode) {
+                Some(function) => {
+                    let args = args
+                        .iter()
+                        .map(|arg| self.value(sprite, arg))
+                        .collect::<Vec<_>>();
+                    let call = format!("{}({})", function, args.join(", "));
+                    if self.warp {
+                        // Everything a warped custom block runs finishes before the frame ends
+                        self.writeln(&format!("for _ in {}: pass", call));
+                    } else {
+                        self.writeln(&format!("yield from {}", call));
+                    }
+                }
+                // Scratch skips calls to custom blocks that were never defined
+                None => self.writeln("pass"),
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// The event a script under a hat is started by, for the hats there's code for
+fn hat_event(hat: &Hat) -> Option<&'static str> {
+    match hat {
+        Hat::GreenFlag => Some("start"),
+        _ => None,
+    }
+}
+
+/// A Python literal for a number. Python has no literal for the infinities, which a bare `inf` would be read as a name.
+pub fn python_number(n: f64) -> String {
+    if n.is_nan() {
+        "float('nan')".into()
+    } else if n.is_infinite() {
+        if n < 0.0 {
+            "float('-inf')"
+        } else {
+            "float('inf')"
+        }
+        .into()
+    } else {
+        n.to_string()
+    }
+}
+
+fn python_string(text: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in text.chars() {
+        match c {
+            '\\' => quoted += "\\\\",
+            '\'' => quoted += "\\'",
+            '\n' => quoted += "\\n",
+            '\r' => quoted += "\\r",
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+impl Default for PyGameTarget {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PyGameTarget {
@@ -63,9 +343,22 @@ impl PyGameTarget {
         PyGameTarget {
             has_pygame: false,
             has_pynanosvg: false,
+            download_options: DownloadOptions::new(),
+            progress: None,
         }
     }
 
+    pub fn download_options(mut self, options: DownloadOptions) -> Self {
+        self.download_options = options;
+        self
+    }
+
+    /// Called as each asset the build needs finishes downloading
+    pub fn on_progress<F: Fn(&DownloadProgress) + Sync + 'static>(mut self, progress: F) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
     pub fn print_dep_stats(&self) {
         println!("Dependencies:");
         println!("------------------------");
@@ -80,6 +373,19 @@ impl Target for PyGameTarget {
         "python-pygame"
     }
 
+    /// Asks `CodeGen` about the opcode lowered on its own, so this can't drift from what a build writes
+    fn supports(opcode: &str) -> bool {
+        let procedures = HashMap::new();
+        let mut codegen = CodeGen::new(&procedures);
+        match ir::lower_opcode(opcode) {
+            Some(Lowered::Definition) => true,
+            Some(Lowered::Hat(hat)) => hat_event(&hat).is_some(),
+            Some(Lowered::Statement(statement)) => codegen.write_supported("sprite", &statement),
+            Some(Lowered::Reporter(expr)) => codegen.reporter("sprite", &expr).is_some(),
+            None => false,
+        }
+    }
+
     fn init(&mut self) -> ScratchResult<()> {
         self.has_pygame = test_import("pygame")?;
         let _has_cython = test_import("cython")?;
@@ -95,170 +401,98 @@ impl Target for PyGameTarget {
     }
 
     fn build(&mut self, project: &Project) -> ScratchResult<()> {
-        let mut client = Client::new();
+        let client = Client::new();
         let path = project.get_build_path(self);
         let mut file_creater = FileCreater::new(path);
 
-        match &project.data {
-            ProjectData::Scratch3(data) => {
-                let mut index = String::new();
-                index += "import pygame\n";
-                index += "import pygame.midi\n";
-                index += "import time\n";
-                index += "import types\n";
-                index += "from svg import Parser, Rasterizer\n";
-                index += include_str!("./target/event.py");
-                index += include_str!("./target/event_dispatcher.py");
-                index += include_str!("./target/costume.py");
-                index += include_str!("./target/sprite.py");
-                index += "pygame.init()\n";
-                index += "pygame.midi.init()\n";
-                index += "midi_player = pygame.midi.Output(0)\n";
-                index += "midi_player.set_instrument(0)\n";
-                index += "sprite_list = []\n";
-                index += "event_system = EventDispatcher()\n";
-                index += "sound_list = {}\n";
-                index += "block_list = {}\n";
-
-                for s in data.targets.iter().flat_map(|t| t.sounds.iter()) {
-                    index += &format!(
-                        "sound_list['{}'] = pygame.mixer.Sound('assets/{}')\n",
-                        s.name, s.md5ext
-                    );
-                }
+        let program = Program::from(&project.data);
+        let mut index = String::new();
+        index += "import math\n";
+        index += "import pygame\n";
+        index += "import pygame.midi\n";
+        index += "import time\n";
+        index += "import types\n";
+        index += "from svg import Parser, Rasterizer\n";
+        index += include_str!("./target/event.py");
+        index += include_str!("./target/event_dispatcher.py");
+        index += include_str!("./target/costume.py");
+        index += include_str!("./target/sprite.py");
+        index += TO_NUMBER_PY;
+        index += MATH_ROUND_PY;
+        index += "pygame.init()\n";
+        index += "pygame.midi.init()\n";
+        index += "midi_player = pygame.midi.Output(0)\n";
+        index += "midi_player.set_instrument(0)\n";
+        index += "sprite_list = []\n";
+        index += "event_system = EventDispatcher()\n";
+        index += "sound_list = {}\n";
 
-                for (i, target) in data.targets.iter().enumerate() {
-                    let name = format!("{}_{}", target.name, i);
-                    index += &format!(
-                        "{name} = Sprite({x}, {y}, {costume}, {direction}, {})\n",
-                        target.size,
-                        x = 0,
-                        y = 0,
-                        name = name,
-                        costume = target.current_costume,
-                        direction = target.direction
-                    );
-                    for costume in target.costumes.iter() {
-                        index += &format!(
-                            "{name}.costumes.append(Costume({x}, {y}, {}, 'assets/{src}'))\n",
-                            costume.bitmap_resolution.unwrap_or(1.0),
-                            x = costume.rotation_center_x,
-                            y = costume.rotation_center_y,
-                            src = costume.md5ext,
-                            name = name
-                        );
-                    }
+        for s in program.targets().flat_map(|t| t.sounds.iter()) {
+            index += &format!(
+                "sound_list['{}'] = pygame.mixer.Sound('assets/{}')\n",
+                s.name, s.md5ext
+            );
+        }
 
-                    for (i, (id, block)) in target.blocks.iter().enumerate() {
-                        let mut codegen = CodeGen::new();
-                        match block.opcode.as_str() {
-                            "event_whenflagclicked" => {
-                                codegen.writeln(&format!("def block_{}(e):", i));
-                                codegen.tab_index += 1;
-                            }
-                            "control_forever" => {
-                                codegen.writeln(&format!("def block_{}(e):", i));
-                                codegen.tab_index += 1;
-                                codegen.writeln("while True:");
-                                codegen.tab_index += 1;
-                                codegen.writeln(&format!(
-                                    "block = block_list[{}](e)",
-                                    block.inputs["SUBSTACK"][1]
-                                ));
-                                codegen.writeln("if isinstance(block, types.GeneratorType):");
-                                codegen.tab_index += 1;
-                                codegen.writeln(&format!("yield from block"));
-                                codegen.tab_index -= 1;
-                            }
-                            "sound_playuntildone" => {
-                                codegen.writeln(&format!("def block_{}(e):", i));
-                                codegen.tab_index += 1;
-                                let sound_name = target.blocks
-                                    [block.inputs["SOUND_MENU"][1].as_str().unwrap()]
-                                .fields["SOUND_MENU"][0]
-                                    .as_str()
-                                    .unwrap();
-                                codegen.writeln(&format!("sound_list['{}'].play()", sound_name));
-                                codegen.writeln("t0 = time.time()");
-                                codegen.writeln(&format!(
-                                    "while time.time() - t0 < sound_list['{}'].get_length():",
-                                    sound_name
-                                ));
-                                codegen.writeln("\tyield");
-                            }
-                            "sound_changevolumeby" => {
-                                codegen.writeln(&format!("def block_{}(e):", i));
-                                codegen.tab_index += 1;
-                                codegen.writeln("for sound in sound_list:");
-                                codegen.tab_index += 1;
-                                codegen.writeln("current_volume = sound_list[sound].get_volume()");
-                                let change = block.inputs["VOLUME"][1][1].as_str().unwrap();
-                                codegen.writeln(&format!(
-                                    "sound_list[sound].set_volume(current_volume + {})",
-                                    change
-                                ));
-                                codegen.tab_index -= 1;
-                            }
-                            "motion_goto" => {
-                                codegen.writeln(&format!("def block_{}(e):\n", i));
-                                codegen.tab_index += 1;
-                                let loc = target.blocks[block.inputs["TO"][1].as_str().unwrap()]
-                                    .fields["TO"][0]
-                                    .as_str()
-                                    .unwrap();
-                                match loc {
-                                    "_mouse_" => {
-                                        codegen.writeln("pos = pygame.mouse.get_pos()");
-                                        codegen
-                                            .writeln(&format!("{}.x = -(480 / 2) + pos[0]", name));
-                                        codegen
-                                            .writeln(&format!("{}.y = (360 / 2) - pos[1]", name));
-                                    }
-                                    _ => unimplemented!(),
-                                }
-                            }
-                            "motion_turnright" => {
-                                codegen.writeln(&format!("def block_{}(e):\n", i));
-                                codegen.tab_index += 1;
-                                let turn = block.inputs["DEGREES"][1][1].as_str().unwrap();
-                                codegen.writeln(&format!("{}.direction += {}", name, turn));
-                            }
-                            _ => {
-                                index += &format!("def block_{}(e):\n", i);
-                                index += &format!("\tprint('NOT IMPLEMENTED: {}')\n", block.opcode);
-                                index += "\tyield\n";
-                                dbg!(&block);
-                            }
-                        }
-
-                        index += &codegen.data;
-
-                        if let Some(id) = block.next.as_ref() {
-                            index += &format!("\tblock = block_list['{}'](e)\n", id);
-                            index += "\tif isinstance(block, types.GeneratorType):\n";
-                            index += &format!("\t\tyield from block\n");
-                        }
-
-                        index += "\treturn\n";
-                        index += &format!("block_list['{}'] = block_{}\n", id, i);
-
-                        match block.opcode.as_str() {
-                            "event_whenflagclicked" => {
-                                index +=
-                                    &format!("event_system.on('start', block_list['{}'])\n", id);
-                            }
-                            _ => (),
-                        }
-                    }
+        for (i, target) in program.targets().enumerate() {
+            let name = format!("{}_{}", target.name, i);
+            index += &format!(
+                "{name} = Sprite({x}, {y}, {costume}, {direction}, {})\n",
+                target.size,
+                x = 0,
+                y = 0,
+                name = name,
+                costume = target.current_costume,
+                direction = target.direction
+            );
+            for costume in target.costumes.iter() {
+                index += &format!(
+                    "{name}.costumes.append(Costume({x}, {y}, {}, 'assets/{src}'))\n",
+                    costume.bitmap_resolution,
+                    x = costume.rotation_center_x,
+                    y = costume.rotation_center_y,
+                    src = costume.md5ext,
+                    name = name
+                );
+            }
 
-                    index += &format!("sprite_list.append({name})\n", name = name);
-                }
+            let procedures = target
+                .procedures
+                .iter()
+                .enumerate()
+                .map(|(k, p)| (p.proccode.clone(), format!("procedure_{}_{}", i, k)))
+                .collect::<HashMap<_, _>>();
+            for procedure in target.procedures.iter() {
+                let mut codegen = CodeGen::new(&procedures);
+                codegen.write_procedure(&name, &procedures[&procedure.proccode], procedure);
+                index += &codegen.data;
+            }
+
+            for (j, script) in target.scripts.iter().enumerate() {
+                let event = match hat_event(&script.hat) {
+                    Some(event) => event,
+                    None => continue,
+                };
 
-                index += "event_system.fire(Event('start', None))\n";
-                index += "screen = pygame.display.set_mode((480, 360))\n";
-                index += "done = False\n";
-                index += "while not done:\n";
-                index += r#"
+                let function = format!("script_{}_{}", i, j);
+                let mut codegen = CodeGen::new(&procedures);
+                codegen.writeln(&format!("def {}(e):", function));
+                codegen.tab_index += 1;
+                codegen.write_stack(&name, &script.body);
+                codegen.writeln("yield");
+
+                index += &codegen.data;
+                index += &format!("event_system.on('{}', {})\n", event, function);
+            }
+
+            index += &format!("sprite_list.append({name})\n", name = name);
+        }
+
+        index += "event_system.fire(Event('start', None))\n";
+        index += "screen = pygame.display.set_mode((480, 360))\n";
+        index += "done = False\n";
+        index += "while not done:\n";
+        index += r#"
 	for event in pygame.event.get():
 		if event.type == pygame.QUIT:
 			done = True
@@ -270,35 +504,39 @@ impl Target for PyGameTarget {
 	pygame.display.flip()
 	screen.fill((255, 255, 255))
 "#;
-                index += "del midi_player\n";
-                index += "pygame.midi.quit()\n";
-
-                let _ = file_creater
-                    .write_bytes("index.py", &index.into_bytes())
-                    .map_err(|_| ScratchError::Custom("Error Writing index.py".into()))?
-                    .mkdir("assets")
-                    .is_ok();
-
-                for c in data.targets.iter().flat_map(|t| t.costumes.iter()) {
-                    if !file_creater.exists(&c.md5ext) {
-                        println!("Downloading {}...", c.md5ext);
-                        file_creater
-                            .write_bytes(&c.md5ext, &client.get_asset(&c.md5ext)?)
-                            .unwrap();
-                    }
-                }
+        index += "del midi_player\n";
+        index += "pygame.midi.quit()\n";
 
-                for s in data.targets.iter().flat_map(|t| t.sounds.iter()) {
-                    if !file_creater.exists(&s.md5ext) {
-                        println!("Downloading {}...", s.md5ext);
-                        file_creater
-                            .write_bytes(&s.md5ext, &client.get_asset(&s.md5ext)?)
-                            .unwrap();
-                    }
-                }
+        let _ = file_creater
+            .write_bytes("index.py", &index.into_bytes())
+            .map_err(|_| ScratchError::Custom("Error Writing index.py".into()))?
+            .mkdir("assets")
+            .is_ok();
+
+        let costumes = program.targets().flat_map(|t| t.costumes.iter());
+        let sounds = program.targets().flat_map(|t| t.sounds.iter());
+        let mut names = Vec::new();
+        for md5ext in costumes
+            .map(|c| c.md5ext.as_str())
+            .chain(sounds.map(|s| s.md5ext.as_str()))
+        {
+            let dest = file_creater.path.join(md5ext);
+            if !dest.exists() && !project.copy_asset(md5ext, &dest)? {
+                names.push(md5ext);
+            }
+        }
+        let progress = |p: &DownloadProgress| {
+            if let Some(progress) = self.progress.as_ref() {
+                progress(p);
             }
-            _ => unimplemented!(),
+        };
+        let downloads = project.load_assets(&client, &names, &self.download_options, progress);
+        for (md5ext, data) in downloads.assets.iter() {
+            file_creater
+                .write_bytes(md5ext, data)
+                .map_err(ScratchError::Io)?;
         }
+        downloads.into_result()?;
 
         Ok(())
     }
@@ -11,25 +11,45 @@ use serde::{
 };
 use std::collections::HashMap;
 
+mod block;
+
+pub use self::block::{
+    Arg,
+    Block,
+    Stack,
+};
+
 #[derive(Deserialize, Debug)]
 pub struct ProjectInfoJson {
-    comments_allowed: bool,
+    pub comments_allowed: bool,
     //remix
-    public: bool,
+    pub public: bool,
     //images
-    description: String,
-    id: u64,
-    instructions: String,
+    pub description: String,
+    pub id: u64,
+    pub instructions: String,
     //history
-    visibility: String,
-    //author
+    pub visibility: String,
+    #[serde(default)]
+    pub author: Option<AuthorJson>,
     //stats
-    image: String,
-    is_published: bool,
-    title: String,
+    pub image: String,
+    pub is_published: bool,
+    pub title: String,
+    /// Short-lived token needed to download the project body
+    #[serde(default)]
+    pub project_token: Option<String>,
 
     #[serde(flatten)]
-    unknown: HashMap<String, serde_json::Value>,
+    pub unknown: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AuthorJson {
+    pub username: String,
+
+    #[serde(flatten)]
+    pub unknown: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -37,15 +57,21 @@ pub struct ProjectJson {
     //info
     pub children: Vec<SpriteJson>,
     pub costumes: Vec<CostumeJson>,
+    pub sounds: Option<Vec<SoundJson>>,
+    pub scripts: Option<Vec<ScriptJson>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<VariableJson>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lists: Vec<ListJson>,
     #[serde(rename = "videoAlpha")]
-    video_alpha: f32,
+    pub(crate) video_alpha: f32,
     #[serde(rename = "objName")]
     pub name: String,
     #[serde(rename = "currentCostumeIndex")]
     pub current_costume_index: u64,
 
     #[serde(flatten)]
-    unknown: HashMap<String, serde_json::Value>,
+    pub(crate) unknown: HashMap<String, serde_json::Value>,
 }
 
 impl ProjectJson {
@@ -62,8 +88,10 @@ impl ProjectJson {
             direction: 90.0,
             costumes: self.costumes,
             index_in_library: 0,
-            sounds: None,
-            scripts: None,
+            sounds: self.sounds,
+            scripts: self.scripts,
+            variables: self.variables,
+            lists: self.lists,
             unknown: self.unknown,
         }
     }
@@ -80,9 +108,9 @@ pub struct SpriteJson {
     #[serde(rename = "currentCostumeIndex")]
     pub current_costume_index: u64,
     #[serde(rename = "isDraggable")]
-    is_draggable: bool,
+    pub(crate) is_draggable: bool,
     #[serde(rename = "rotationStyle")]
-    rotation_style: String,
+    pub(crate) rotation_style: String,
     pub visible: bool,
     pub scale: f64,
     pub direction: f64,
@@ -92,8 +120,12 @@ pub struct SpriteJson {
 
     pub sounds: Option<Vec<SoundJson>>,
     pub scripts: Option<Vec<ScriptJson>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<VariableJson>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lists: Vec<ListJson>,
     #[serde(flatten)]
-    unknown: HashMap<String, serde_json::Value>,
+    pub(crate) unknown: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -102,6 +134,13 @@ pub struct CostumeJson {
     pub name: String,
     #[serde(rename = "baseLayerMD5")]
     pub src: String,
+    /// Index of the embedded file in an `.sb2` archive, `-1` or missing if not embedded
+    #[serde(
+        rename = "baseLayerID",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub layer_id: Option<i64>,
     #[serde(rename = "bitmapResolution")]
     pub resolution: u8,
     #[serde(rename = "rotationCenterX")]
@@ -118,14 +157,42 @@ pub struct SoundJson {
     pub name: String,
     #[serde(rename = "md5")]
     pub src: String,
+    /// Index of the embedded file in an `.sb2` archive, `-1` or missing if not embedded
+    #[serde(rename = "soundID", default, skip_serializing_if = "Option::is_none")]
+    pub sound_id: Option<i64>,
     #[serde(flatten)]
-    unknown: HashMap<String, serde_json::Value>,
+    pub(crate) unknown: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VariableJson {
+    pub name: String,
+    #[serde(default)]
+    pub value: serde_json::Value,
+    /// Cloud variables are stored on the server
+    #[serde(rename = "isPersistent", default)]
+    pub is_persistent: bool,
+    #[serde(flatten)]
+    pub(crate) unknown: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ListJson {
+    #[serde(rename = "listName")]
+    pub name: String,
+    #[serde(default)]
+    pub contents: Vec<serde_json::Value>,
+    #[serde(rename = "isPersistent", default)]
+    pub is_persistent: bool,
+    /// The list monitor and its position on the stage
+    #[serde(flatten)]
+    pub(crate) unknown: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ScriptJson {
-    x: f32,
-    y: f32,
+    pub(crate) x: f32,
+    pub(crate) y: f32,
     pub blocks: Vec<Block>,
 }
 
@@ -176,111 +243,3 @@ impl<'de> Visitor<'de> for ScriptJsonVisitor {
         Ok(ScriptJson { x, y, blocks })
     }
 }
-
-#[derive(Debug, Clone)]
-pub enum Block {
-    WhenStart,
-    PlaySoundAndWait(String),
-    PlayNote(u32, f32),
-    DoRepeat(u32, Vec<Block>),
-    Unknown(Vec<serde_json::Value>),
-}
-
-impl<'de> Deserialize<'de> for Block {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        deserializer.deserialize_seq(BlockVisitor)
-    }
-}
-
-impl Serialize for Block {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        match self {
-            Block::WhenStart => {
-                let mut seq = serializer.serialize_seq(Some(1))?;
-                seq.serialize_element("whenGreenFlag")?;
-                seq.end()
-            }
-            Block::PlaySoundAndWait(sound) => {
-                let mut seq = serializer.serialize_seq(Some(2))?;
-                seq.serialize_element("doPlaySoundAndWait")?;
-                seq.serialize_element(&sound)?;
-                seq.end()
-            }
-            Block::PlayNote(note, duration) => {
-                let mut seq = serializer.serialize_seq(Some(3))?;
-                seq.serialize_element("noteOn:duration:elapsed:from:")?;
-                seq.serialize_element(note)?;
-                seq.serialize_element(duration)?;
-                seq.end()
-            }
-            Block::DoRepeat(n, blocks) => {
-                let mut seq = serializer.serialize_seq(Some(3))?;
-                seq.serialize_element("doRepeat")?;
-                seq.serialize_element(n)?;
-                seq.serialize_element(blocks)?;
-                seq.end()
-            }
-            Block::Unknown(arr) => {
-                let mut seq = serializer.serialize_seq(Some(arr.len()))?;
-                for e in arr {
-                    seq.serialize_element(e)?;
-                }
-                seq.end()
-            }
-        }
-    }
-}
-
-struct BlockVisitor;
-
-impl<'de> Visitor<'de> for BlockVisitor {
-    type Value = Block;
-
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-        formatter.write_str("Block")
-    }
-
-    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
-    where
-        A: SeqAccess<'de>,
-    {
-        let name: String = seq
-            .next_element()?
-            .ok_or(serde::de::Error::invalid_length(0, &self))?;
-
-        match name.as_str() {
-            "whenGreenFlag" => Ok(Block::WhenStart),
-            "doPlaySoundAndWait" => Ok(Block::PlaySoundAndWait(
-                seq.next_element()?
-                    .ok_or(serde::de::Error::invalid_length(1, &self))?,
-            )),
-            "noteOn:duration:elapsed:from:" => Ok(Block::PlayNote(
-                seq.next_element()?
-                    .ok_or(serde::de::Error::invalid_length(1, &self))?,
-                seq.next_element()?
-                    .ok_or(serde::de::Error::invalid_length(2, &self))?,
-            )),
-            "doRepeat" => Ok(Block::DoRepeat(
-                seq.next_element()?
-                    .ok_or(serde::de::Error::invalid_length(1, &self))?,
-                seq.next_element()?
-                    .ok_or(serde::de::Error::invalid_length(2, &self))?,
-            )),
-            _ => {
-                dbg!(&name);
-                let mut body = vec![serde_json::Value::String(name)];
-                while let Some(data) = seq.next_element()? {
-                    body.push(data);
-                }
-
-                Ok(Block::Unknown(body))
-            }
-        }
-    }
-}
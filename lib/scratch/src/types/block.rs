@@ -0,0 +1,428 @@
+use serde::{
+    de::{
+        SeqAccess,
+        Visitor,
+    },
+    ser::SerializeSeq,
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
+use serde_json::Value;
+
+/// The blocks inside a C-block, which Scratch 2 stores as `null` when empty
+pub type Stack = Option<Vec<Block>>;
+
+/// A block argument, which can hold either a literal or a reporter dropped into it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Value(Value),
+    Block(Box<Block>),
+}
+
+impl Arg {
+    pub fn as_value(&self) -> Option<&Value> {
+        match self {
+            Arg::Value(value) => Some(value),
+            Arg::Block(_) => None,
+        }
+    }
+
+    pub fn as_block(&self) -> Option<&Block> {
+        match self {
+            Arg::Value(_) => None,
+            Arg::Block(block) => Some(block),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_value().and_then(Value::as_str)
+    }
+
+    /// The argument as a number, reading numeric strings the way Scratch does
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.as_value()? {
+            Value::Number(n) => n.as_f64(),
+            Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// How one argument of a block is read from and written to its JSON array
+trait BlockArg: Sized {
+    fn from_value(value: Value) -> Option<Self>;
+    fn to_value(&self) -> Value;
+}
+
+impl BlockArg for Arg {
+    fn from_value(value: Value) -> Option<Self> {
+        // Only a reporter is an array starting with its selector
+        let is_block = matches!(
+            value.as_array().and_then(|values| values.first()),
+            Some(Value::String(_))
+        );
+
+        if is_block {
+            Block::from_value(value).map(|block| Arg::Block(Box::new(block)))
+        } else {
+            Some(Arg::Value(value))
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            Arg::Value(value) => value.clone(),
+            Arg::Block(block) => Value::Array(block.to_values()),
+        }
+    }
+}
+
+impl BlockArg for Stack {
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Null => Some(None),
+            Value::Array(blocks) => blocks
+                .into_iter()
+                .map(Block::from_value)
+                .collect::<Option<Vec<_>>>()
+                .map(Some),
+            _ => None,
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            Some(blocks) => Value::Array(
+                blocks
+                    .iter()
+                    .map(|block| Value::Array(block.to_values()))
+                    .collect(),
+            ),
+            None => Value::Null,
+        }
+    }
+}
+
+macro_rules! serde_arg {
+    ($($ty:ty),*) => {
+        $(
+            impl BlockArg for $ty {
+                fn from_value(value: Value) -> Option<Self> {
+                    serde_json::from_value(value).ok()
+                }
+
+                fn to_value(&self) -> Value {
+                    serde_json::to_value(self).unwrap_or(Value::Null)
+                }
+            }
+        )*
+    };
+}
+
+serde_arg!(String, bool, Vec<String>, Vec<Value>);
+
+/// Declares `Block` with one variant per Scratch 2 selector, along with its parsing and writing
+macro_rules! blocks {
+    ($($selector:literal => $variant:ident $({ $($field:ident: $ty:ty),* $(,)? })?,)*) => {
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum Block {
+            $($variant $({ $($field: $ty),* })?,)*
+            /// A custom block call, whose argument count depends on its proccode
+            Call { proccode: String, args: Vec<Arg> },
+            /// A selector this crate doesn't know, or a known one with the wrong arguments
+            Unknown(Vec<Value>),
+        }
+
+        impl Block {
+            pub fn selector(&self) -> &str {
+                match self {
+                    $(Block::$variant { .. } => $selector,)*
+                    Block::Call { .. } => "call",
+                    Block::Unknown(values) => values.first().and_then(Value::as_str).unwrap_or(""),
+                }
+            }
+
+            fn from_known(selector: &str, values: Vec<Value>) -> Option<Self> {
+                let mut values = values.into_iter();
+                let block = match selector {
+                    $($selector => Block::$variant $({ $($field: <$ty>::from_value(values.next()?)?),* })?,)*
+                    "call" => Block::Call {
+                        proccode: String::from_value(values.next()?)?,
+                        args: values.by_ref().map(Arg::from_value).collect::<Option<_>>()?,
+                    },
+                    _ => return None,
+                };
+
+                match values.next() {
+                    Some(_) => None,
+                    None => Some(block),
+                }
+            }
+
+            fn to_values(&self) -> Vec<Value> {
+                let mut values = vec![Value::from(self.selector())];
+                match self {
+                    $(Block::$variant $({ $($field),* })? => {
+                        $($(values.push($field.to_value());)*)?
+                    })*
+                    Block::Call { proccode, args } => {
+                        values.push(proccode.to_value());
+                        values.extend(args.iter().map(Arg::to_value));
+                    }
+                    Block::Unknown(unknown) => return unknown.clone(),
+                }
+                values
+            }
+        }
+    };
+}
+
+blocks! {
+    // Motion
+    "forward:" => MoveSteps { steps: Arg },
+    "turnRight:" => TurnRight { degrees: Arg },
+    "turnLeft:" => TurnLeft { degrees: Arg },
+    "heading:" => PointInDirection { direction: Arg },
+    "pointTowards:" => PointTowards { target: Arg },
+    "gotoX:y:" => GotoXY { x: Arg, y: Arg },
+    "gotoSpriteOrMouse:" => GotoObject { target: Arg },
+    "glideSecs:toX:y:elapsed:from:" => GlideTo { secs: Arg, x: Arg, y: Arg },
+    "changeXposBy:" => ChangeXBy { dx: Arg },
+    "xpos:" => SetX { x: Arg },
+    "changeYposBy:" => ChangeYBy { dy: Arg },
+    "ypos:" => SetY { y: Arg },
+    "bounceOffEdge" => BounceOffEdge,
+    "setRotationStyle" => SetRotationStyle { style: Arg },
+    "xpos" => XPosition,
+    "ypos" => YPosition,
+    "heading" => Direction,
+    "scrollRight" => ScrollRight { distance: Arg },
+    "scrollUp" => ScrollUp { distance: Arg },
+    "scrollAlign" => ScrollAlign { alignment: Arg },
+    "xScroll" => XScroll,
+    "yScroll" => YScroll,
+
+    // Looks
+    "say:duration:elapsed:from:" => SayForSecs { message: Arg, secs: Arg },
+    "say:" => Say { message: Arg },
+    "think:duration:elapsed:from:" => ThinkForSecs { message: Arg, secs: Arg },
+    "think:" => Think { message: Arg },
+    "show" => Show,
+    "hide" => Hide,
+    "hideAll" => HideAll,
+    "lookLike:" => SwitchCostume { costume: Arg },
+    "nextCostume" => NextCostume,
+    "startScene" => SwitchBackdrop { backdrop: Arg },
+    "startSceneAndWait" => SwitchBackdropAndWait { backdrop: Arg },
+    "nextScene" => NextBackdrop,
+    "changeGraphicEffect:by:" => ChangeEffectBy { effect: Arg, change: Arg },
+    "setGraphicEffect:to:" => SetEffectTo { effect: Arg, value: Arg },
+    "filterReset" => ClearEffects,
+    "changeSizeBy:" => ChangeSizeBy { change: Arg },
+    "setSizeTo:" => SetSizeTo { size: Arg },
+    "changeStretchBy:" => ChangeStretchBy { change: Arg },
+    "setStretchTo:" => SetStretchTo { stretch: Arg },
+    "comeToFront" => GoToFront,
+    "goBackByLayers:" => GoBackLayers { layers: Arg },
+    "costumeIndex" => CostumeIndex,
+    "costumeName" => CostumeName,
+    "backgroundIndex" => BackdropIndex,
+    "sceneName" => BackdropName,
+    "scale" => Size,
+
+    // Sound
+    "playSound:" => PlaySound { sound: Arg },
+    "doPlaySoundAndWait" => PlaySoundAndWait { sound: Arg },
+    "stopAllSounds" => StopAllSounds,
+    "changeVolumeBy:" => ChangeVolumeBy { volume: Arg },
+    "setVolumeTo:" => SetVolumeTo { volume: Arg },
+    "volume" => Volume,
+    "playDrum" => PlayMidiDrum { drum: Arg, beats: Arg },
+    "drum:duration:elapsed:from:" => PlayDrum { drum: Arg, beats: Arg },
+    "rest:elapsed:from:" => Rest { beats: Arg },
+    "noteOn:duration:elapsed:from:" => PlayNote { note: Arg, beats: Arg },
+    "instrument:" => SetInstrument { instrument: Arg },
+    "midiInstrument:" => SetMidiInstrument { instrument: Arg },
+    "changeTempoBy:" => ChangeTempoBy { tempo: Arg },
+    "setTempoTo:" => SetTempoTo { tempo: Arg },
+    "tempo" => Tempo,
+
+    // Pen
+    "clearPenTrails" => ClearPen,
+    "stampCostume" => Stamp,
+    "putPenDown" => PenDown,
+    "putPenUp" => PenUp,
+    "penColor:" => SetPenColor { color: Arg },
+    "changePenHueBy:" => ChangePenHueBy { hue: Arg },
+    "setPenHueTo:" => SetPenHueTo { hue: Arg },
+    "changePenShadeBy:" => ChangePenShadeBy { shade: Arg },
+    "setPenShadeTo:" => SetPenShadeTo { shade: Arg },
+    "changePenSizeBy:" => ChangePenSizeBy { size: Arg },
+    "penSize:" => SetPenSizeTo { size: Arg },
+
+    // Events
+    "whenGreenFlag" => WhenStart,
+    "whenKeyPressed" => WhenKeyPressed { key: Arg },
+    "whenClicked" => WhenClicked,
+    "whenSceneStarts" => WhenBackdropSwitchesTo { backdrop: Arg },
+    "whenSensorGreaterThan" => WhenGreaterThan { sensor: Arg, value: Arg },
+    "whenIReceive" => WhenIReceive { message: Arg },
+    "broadcast:" => Broadcast { message: Arg },
+    "doBroadcastAndWait" => BroadcastAndWait { message: Arg },
+
+    // Control
+    "wait:elapsed:from:" => Wait { secs: Arg },
+    "doRepeat" => DoRepeat { times: Arg, body: Stack },
+    "doForever" => DoForever { body: Stack },
+    "doIf" => DoIf { condition: Arg, body: Stack },
+    "doIfElse" => DoIfElse { condition: Arg, body: Stack, else_body: Stack },
+    "doWaitUntil" => DoWaitUntil { condition: Arg },
+    "doUntil" => DoUntil { condition: Arg, body: Stack },
+    "doWhile" => DoWhile { condition: Arg, body: Stack },
+    "doForLoop" => DoForLoop { variable: Arg, value: Arg, body: Stack },
+    "doReturn" => DoReturn,
+    "stopAll" => StopAll,
+    "stopScripts" => StopScripts { option: Arg },
+    "whenCloned" => WhenCloned,
+    "createCloneOf" => CreateCloneOf { target: Arg },
+    "deleteClone" => DeleteClone,
+    "COUNT" => Counter,
+    "INCR_COUNT" => IncrCounter,
+    "CLR_COUNT" => ClearCounter,
+    "warpSpeed" => WarpSpeed { body: Stack },
+
+    // Sensing
+    "touching:" => Touching { target: Arg },
+    "touchingColor:" => TouchingColor { color: Arg },
+    "color:sees:" => ColorTouchingColor { color: Arg, color2: Arg },
+    "distanceTo:" => DistanceTo { target: Arg },
+    "doAsk" => DoAsk { question: Arg },
+    "answer" => Answer,
+    "keyPressed:" => KeyPressed { key: Arg },
+    "mousePressed" => MouseDown,
+    "mouseX" => MouseX,
+    "mouseY" => MouseY,
+    "soundLevel" => Loudness,
+    "isLoud" => IsLoud,
+    "timer" => Timer,
+    "timerReset" => ResetTimer,
+    "getAttribute:of:" => AttributeOf { attribute: Arg, object: Arg },
+    "timeAndDate" => Current { unit: Arg },
+    "timestamp" => DaysSince2000,
+    "getUserName" => Username,
+    "senseVideoMotion" => VideoMotion { attribute: Arg, target: Arg },
+    "setVideoState" => SetVideoState { state: Arg },
+    "setVideoTransparency" => SetVideoTransparency { transparency: Arg },
+    "sensor:" => SensorValue { sensor: Arg },
+    "sensorPressed:" => SensorPressed { sensor: Arg },
+
+    // Operators
+    "+" => Add { num1: Arg, num2: Arg },
+    "-" => Subtract { num1: Arg, num2: Arg },
+    "*" => Multiply { num1: Arg, num2: Arg },
+    "/" => Divide { num1: Arg, num2: Arg },
+    "randomFrom:to:" => Random { from: Arg, to: Arg },
+    "<" => LessThan { operand1: Arg, operand2: Arg },
+    "=" => Equals { operand1: Arg, operand2: Arg },
+    ">" => GreaterThan { operand1: Arg, operand2: Arg },
+    "&" => And { operand1: Arg, operand2: Arg },
+    "|" => Or { operand1: Arg, operand2: Arg },
+    "not" => Not { operand: Arg },
+    "concatenate:with:" => Join { string1: Arg, string2: Arg },
+    "letter:of:" => LetterOf { letter: Arg, string: Arg },
+    "stringLength:" => Length { string: Arg },
+    "%" => Mod { num1: Arg, num2: Arg },
+    "rounded" => Round { num: Arg },
+    "computeFunction:of:" => MathOp { operator: Arg, num: Arg },
+
+    // Data
+    "readVariable" => ReadVariable { variable: Arg },
+    "setVar:to:" => SetVar { variable: Arg, value: Arg },
+    "changeVar:by:" => ChangeVar { variable: Arg, value: Arg },
+    "showVariable:" => ShowVariable { variable: Arg },
+    "hideVariable:" => HideVariable { variable: Arg },
+    "contentsOfList:" => ContentsOfList { list: Arg },
+    "append:toList:" => AppendToList { item: Arg, list: Arg },
+    "deleteLine:ofList:" => DeleteOfList { index: Arg, list: Arg },
+    "insert:at:ofList:" => InsertAtList { item: Arg, index: Arg, list: Arg },
+    "setLine:ofList:to:" => ReplaceItemOfList { index: Arg, list: Arg, item: Arg },
+    "getLine:ofList:" => ItemOfList { index: Arg, list: Arg },
+    "lineCountOfList:" => LengthOfList { list: Arg },
+    "list:contains:" => ListContains { list: Arg, item: Arg },
+    "showList:" => ShowList { list: Arg },
+    "hideList:" => HideList { list: Arg },
+
+    // Procedures
+    "procDef" => ProcDef { proccode: String, names: Vec<String>, defaults: Vec<Value>, warp: bool },
+    "getParam" => GetParam { name: String, kind: String },
+}
+
+impl Block {
+    /// Parses a block from its JSON array, falling back to `Unknown` so nothing is lost
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Array(values) => Some(Block::from_values(values)),
+            _ => None,
+        }
+    }
+
+    pub fn from_values(values: Vec<Value>) -> Self {
+        let selector = match values.first().and_then(Value::as_str) {
+            Some(selector) => selector.to_string(),
+            None => return Block::Unknown(values),
+        };
+
+        Block::from_known(&selector, values[1..].to_vec()).unwrap_or(Block::Unknown(values))
+    }
+}
+
+impl<'de> Deserialize<'de> for Block {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(BlockVisitor)
+    }
+}
+
+impl Serialize for Block {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let values = self.to_values();
+        let mut seq = serializer.serialize_seq(Some(values.len()))?;
+        for value in values.iter() {
+            seq.serialize_element(value)?;
+        }
+        seq.end()
+    }
+}
+
+struct BlockVisitor;
+
+impl<'de> Visitor<'de> for BlockVisitor {
+    type Value = Block;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("Block")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+
+        if values.is_empty() {
+            return Err(serde::de::Error::invalid_length(0, &self));
+        }
+
+        Ok(Block::from_values(values))
+    }
+}
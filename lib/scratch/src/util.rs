@@ -1,7 +1,10 @@
 use serde::Serialize;
 use std::{
     fs::File,
-    path::PathBuf,
+    path::{
+        Path,
+        PathBuf,
+    },
     process::Command,
 };
 
@@ -30,13 +33,13 @@ impl FileCreater {
         self
     }
 
-    pub fn down(&mut self, path: &str) -> Result<&mut Self, ()> {
-        self.path.push(path); //TODO: Resturn result, see if path exists or not
+    pub fn down(&mut self, path: &str) -> Result<&mut Self, std::io::Error> {
+        self.path.push(path);
         if self.path.exists() {
             Ok(self)
         } else {
             self.up();
-            Err(())
+            Err(std::io::ErrorKind::NotFound.into())
         }
     }
 
@@ -80,3 +83,137 @@ pub fn make_command() -> Command {
     }
     cmd
 }
+
+/// Checks that a name from a project file is a plain file name that can't point outside a directory
+pub fn is_file_name(name: &str) -> bool {
+    Path::new(name)
+        .file_name()
+        .map_or(false, |file| file == name)
+}
+
+/// Where a new copy of `dest` is written before `swap_dir` puts it in place, as a hidden sibling of `dest`
+pub fn staging_dir(dest: &Path) -> PathBuf {
+    sibling(dest, ".", &format!(".new-{}", std::process::id()))
+}
+
+/// `dest`'s sibling named `<prefix><name of dest><suffix>`
+fn sibling(dest: &Path, prefix: &str, suffix: &str) -> PathBuf {
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    dest.with_file_name(format!("{}{}{}", prefix, name, suffix))
+}
+
+/// Puts the directory `new` in place of `dest`, first moving over every entry of `dest` that `new` lacks and `carry` accepts.
+/// `carry` sees paths relative to `dest`. Directories in both are merged entry by entry.
+/// The old directory is set aside as `<dest>.old-<pid>` until the swap is done. It is put back if a step fails, and if
+/// the process dies part way, `restore_dir` puts it back, along with anything already moved into a `staging_dir` `new`.
+pub fn swap_dir<F: Fn(&Path) -> bool>(dest: &Path, new: &Path, carry: F) -> std::io::Result<()> {
+    let backup = sibling(dest, "", &format!(".old-{}", std::process::id()));
+    if backup.exists() {
+        std::fs::remove_dir_all(&backup)?;
+    }
+    std::fs::rename(dest, &backup)?;
+
+    let mut moved = Vec::new();
+    let result = carry_entries(&backup, new, Path::new(""), &carry, &mut moved)
+        .and_then(|_| std::fs::rename(new, dest));
+
+    match result {
+        Ok(()) => {
+            // The new directory is in place, so a leftover backup is only clutter
+            let _ = std::fs::remove_dir_all(&backup);
+            Ok(())
+        }
+        Err(e) => {
+            for (from, to) in moved.iter().rev() {
+                let _ = std::fs::rename(to, from);
+            }
+            let _ = std::fs::rename(&backup, dest);
+            Err(e)
+        }
+    }
+}
+
+/// Cleans up after a `swap_dir` into `dest` that never finished. If `dest` is missing, the old directory set aside for
+/// the swap is put back, with the entries already moved out of it into the `staging_dir`. Otherwise the new directory
+/// made it into place and the old one is removed. Backups of processes that are still running are left alone, since
+/// their swap may still be going.
+pub fn restore_dir(dest: &Path) -> std::io::Result<()> {
+    let parent = match dest.parent().filter(|p| p.is_dir()) {
+        Some(parent) => parent,
+        None => return Ok(()),
+    };
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let prefix = format!("{}.old-", name);
+    for entry in std::fs::read_dir(parent)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let pid = match file_name.strip_prefix(&prefix) {
+            Some(pid) => match pid.parse::<u32>() {
+                Ok(id) if !is_running(id) => pid,
+                _ => continue,
+            },
+            None => continue,
+        };
+
+        let backup = entry.path();
+        if dest.exists() {
+            std::fs::remove_dir_all(&backup)?;
+        } else {
+            let staging = sibling(dest, ".", &format!(".new-{}", pid));
+            if staging.is_dir() {
+                carry_entries(&staging, &backup, Path::new(""), &|_| true, &mut Vec::new())?;
+                std::fs::remove_dir_all(&staging)?;
+            }
+            std::fs::rename(&backup, dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether a process with this id is running. This process counts, since another thread may be saving.
+fn is_running(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
+    }
+    if cfg!(target_os = "linux") {
+        Path::new("/proc").join(pid.to_string()).exists()
+    } else if cfg!(target_os = "windows") {
+        Command::new("tasklist")
+            .args(["/NH", "/FI", &format!("PID eq {}", pid)])
+            .output()
+            .map_or(true, |output| {
+                String::from_utf8_lossy(&output.stdout).contains(&pid.to_string())
+            })
+    } else {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .map_or(true, |status| status.success())
+    }
+}
+
+fn carry_entries<F: Fn(&Path) -> bool>(
+    old: &Path,
+    new: &Path,
+    relative: &Path,
+    carry: &F,
+    moved: &mut Vec<(PathBuf, PathBuf)>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(old.join(relative))? {
+        let entry = entry?;
+        let relative = relative.join(entry.file_name());
+        let from = old.join(&relative);
+        let to = new.join(&relative);
+
+        if !to.exists() {
+            if carry(&relative) {
+                std::fs::rename(&from, &to)?;
+                moved.push((from, to));
+            }
+        } else if from.is_dir() && to.is_dir() {
+            carry_entries(old, new, &relative, carry, moved)?;
+        }
+    }
+    Ok(())
+}
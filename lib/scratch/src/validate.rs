@@ -0,0 +1,442 @@
+use crate::{
+    scratch3::{
+        convert::convert,
+        script::{
+            build_scripts,
+            is_hat,
+            ScriptIssue,
+        },
+        BlockJson,
+        Input,
+        InputValue,
+        Primitive,
+        ProjectJson as ProjectJson3,
+        TargetBlock,
+        TargetJson,
+    },
+    Project,
+    ProjectData,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Scratch copes with it, but the project probably doesn't do what its author meant
+    Warning,
+    /// Scratch can't run this part of the project as it is
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => f.write_str("warning"),
+            Severity::Error => f.write_str("error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    NoStage,
+    DanglingNext,
+    DanglingParent,
+    DanglingInput,
+    LinkCycle,
+    SharedBlock,
+    WrongParent,
+    OrphanBlock,
+    HatNotTopLevel,
+    MissingCostume,
+    MissingSound,
+    MissingVariable,
+    MissingList,
+    MissingBroadcast,
+    MissingAsset,
+}
+
+impl Code {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Code::NoStage => "no-stage",
+            Code::DanglingNext => "dangling-next",
+            Code::DanglingParent => "dangling-parent",
+            Code::DanglingInput => "dangling-input",
+            Code::LinkCycle => "link-cycle",
+            Code::SharedBlock => "shared-block",
+            Code::WrongParent => "wrong-parent",
+            Code::OrphanBlock => "orphan-block",
+            Code::HatNotTopLevel => "hat-not-top-level",
+            Code::MissingCostume => "missing-costume",
+            Code::MissingSound => "missing-sound",
+            Code::MissingVariable => "missing-variable",
+            Code::MissingList => "missing-list",
+            Code::MissingBroadcast => "missing-broadcast",
+            Code::MissingAsset => "missing-asset",
+        }
+    }
+
+    pub fn severity(self) -> Severity {
+        match self {
+            Code::NoStage
+            | Code::DanglingNext
+            | Code::DanglingParent
+            | Code::DanglingInput
+            | Code::LinkCycle
+            | Code::MissingAsset => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Something wrong with a project, and where it is
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: Code,
+    pub severity: Severity,
+    /// The name of the stage or sprite
+    pub target: String,
+    pub block: Option<String>,
+    /// For Scratch 2 projects, whose block IDs are only made up when they are converted, the index of the script
+    /// in the object's `scripts` instead
+    pub script: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[{}] {}", self.severity, self.code, self.target)?;
+        if let Some(block) = &self.block {
+            write!(f, " (block {})", block)?;
+        }
+        if let Some(script) = self.script {
+            write!(f, " (script {})", script + 1)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl Project {
+    /// Checks the project, including its assets if it has any bundled or was loaded from a saved project's `data/assets`
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        if self.assets.is_empty() && self.path.is_none() {
+            return validate(&self.data, None);
+        }
+        let has_asset =
+            |name: &str| self.assets.contains_key(name) || self.asset_path(name).is_some();
+        validate_with(&self.data, Some(&has_asset))
+    }
+}
+
+/// Checks a project for broken block links and references to things that don't exist.
+/// Assets are only checked if `assets` is given.
+/// Scratch 2 projects are checked after conversion, and point at scripts rather than blocks.
+pub fn validate(data: &ProjectData, assets: Option<&HashMap<String, Vec<u8>>>) -> Vec<Diagnostic> {
+    match assets {
+        Some(assets) => validate_with(data, Some(&|name: &str| assets.contains_key(name))),
+        None => validate_with(data, None),
+    }
+}
+
+/// Like `validate`, but asks `has_asset` whether each costume and sound file is there
+fn validate_with(data: &ProjectData, has_asset: Option<&dyn Fn(&str) -> bool>) -> Vec<Diagnostic> {
+    match data {
+        ProjectData::Scratch2(data) => {
+            let conversion = convert(data);
+            let mut diagnostics = validate_scratch3(&conversion.project, has_asset);
+            for diagnostic in diagnostics.iter_mut() {
+                if let Some(block) = diagnostic.block.take() {
+                    diagnostic.script = conversion.scripts.get(&block).copied();
+                }
+            }
+            diagnostics
+        }
+        ProjectData::Scratch3(data) => validate_scratch3(data, has_asset),
+    }
+}
+
+fn validate_scratch3(
+    project: &ProjectJson3,
+    has_asset: Option<&dyn Fn(&str) -> bool>,
+) -> Vec<Diagnostic> {
+    let stage = project.targets.iter().find(|t| t.is_stage);
+    let mut validator = Validator {
+        stage,
+        diagnostics: Vec::new(),
+    };
+
+    if stage.is_none() {
+        validator.report(Code::NoStage, "", None, "The project has no stage".into());
+    }
+
+    for target in project.targets.iter() {
+        validator.links(target);
+        validator.references(target);
+        if let Some(has_asset) = has_asset {
+            validator.assets(target, has_asset);
+        }
+    }
+
+    validator.diagnostics
+}
+
+struct Validator<'a> {
+    stage: Option<&'a TargetJson>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Block IDs in a stable order, so diagnostics come out the same way every time
+fn sorted_blocks(target: &TargetJson) -> Vec<(&String, &BlockJson)> {
+    let mut blocks = target
+        .blocks
+        .iter()
+        .filter_map(|(id, block)| Some((id, block.block()?)))
+        .collect::<Vec<_>>();
+    blocks.sort_by(|a, b| a.0.cmp(b.0));
+    blocks
+}
+
+/// Whether `id` is a block that can be linked to, rather than missing or a loose reporter
+fn is_block(target: &TargetJson, id: &str) -> bool {
+    target.blocks.get(id).and_then(TargetBlock::block).is_some()
+}
+
+fn input_blocks(input: &Input) -> impl Iterator<Item = &str> {
+    let value = input.value();
+    let shadow = input.shadow().filter(|shadow| Some(*shadow) != value);
+    value
+        .into_iter()
+        .chain(shadow)
+        .filter_map(|value| match value {
+            InputValue::Block(id) => Some(id.as_str()),
+            InputValue::Primitive(_) => None,
+        })
+}
+
+fn input_primitives(input: &Input) -> impl Iterator<Item = &Primitive> {
+    let value = input.value();
+    let shadow = input.shadow().filter(|shadow| Some(*shadow) != value);
+    value
+        .into_iter()
+        .chain(shadow)
+        .filter_map(|value| match value {
+            InputValue::Primitive(primitive) => Some(primitive),
+            InputValue::Block(_) => None,
+        })
+}
+
+impl<'a> Validator<'a> {
+    fn report(&mut self, code: Code, target: &str, block: Option<&str>, message: String) {
+        self.diagnostics.push(Diagnostic {
+            code,
+            severity: code.severity(),
+            target: target.to_string(),
+            block: block.map(String::from),
+            script: None,
+            message,
+        });
+    }
+
+    fn links(&mut self, target: &TargetJson) {
+        let name = target.name.as_str();
+
+        for (id, block) in sorted_blocks(target) {
+            if let Some(next) = block.next.as_deref() {
+                if !is_block(target, next) {
+                    let message = format!("`next` points to missing block {}", next);
+                    self.report(Code::DanglingNext, name, Some(id), message);
+                }
+            }
+            if let Some(parent) = block.parent.as_deref() {
+                if !is_block(target, parent) {
+                    let message = format!("`parent` points to missing block {}", parent);
+                    self.report(Code::DanglingParent, name, Some(id), message);
+                }
+            }
+
+            let mut inputs = block.inputs.iter().collect::<Vec<_>>();
+            inputs.sort_by(|a, b| a.0.cmp(b.0));
+            for (input_name, input) in inputs {
+                for linked in input_blocks(input) {
+                    if !is_block(target, linked) {
+                        let message =
+                            format!("Input {} points to missing block {}", input_name, linked);
+                        self.report(Code::DanglingInput, name, Some(id), message);
+                    }
+                }
+            }
+
+            if is_hat(&block.opcode) && !block.top_level {
+                let message = format!("{} is attached below another block", block.opcode);
+                self.report(Code::HatNotTopLevel, name, Some(id), message);
+            }
+        }
+
+        for issue in build_scripts(&target.blocks).issues {
+            match issue {
+                ScriptIssue::Cycle { id, from } => {
+                    let message = format!("Linking to {} from {} makes a loop", id, from);
+                    self.report(Code::LinkCycle, name, Some(&id), message);
+                }
+                ScriptIssue::Shared { id, from } => {
+                    let message = format!("Also linked from {}", from);
+                    self.report(Code::SharedBlock, name, Some(&id), message);
+                }
+                ScriptIssue::WrongParent { id, parent } => {
+                    let message = format!("Linked from {}, which is not its `parent`", parent);
+                    self.report(Code::WrongParent, name, Some(&id), message);
+                }
+                ScriptIssue::Orphan { id } => {
+                    // Orphans with a missing parent are already reported as dangling
+                    let dangling =
+                        match target.blocks[&id].block().and_then(|b| b.parent.as_deref()) {
+                            Some(parent) => !is_block(target, parent),
+                            None => false,
+                        };
+                    if !dangling {
+                        let message = "Not top-level and not linked from any block".into();
+                        self.report(Code::OrphanBlock, name, Some(&id), message);
+                    }
+                }
+                // Already reported as dangling links
+                ScriptIssue::Missing { .. } => {}
+            }
+        }
+    }
+
+    fn has_variable(&self, target: &TargetJson, id: &str) -> bool {
+        target.variables.contains_key(id)
+            || matches!(self.stage, Some(stage) if stage.variables.contains_key(id))
+    }
+
+    fn has_list(&self, target: &TargetJson, id: &str) -> bool {
+        target.lists.contains_key(id)
+            || matches!(self.stage, Some(stage) if stage.lists.contains_key(id))
+    }
+
+    fn has_broadcast(&self, target: &TargetJson, id: &str) -> bool {
+        // Broadcasts belong on the stage, but some projects declare them on sprites
+        target.broadcasts.contains_key(id)
+            || matches!(self.stage, Some(stage) if stage.broadcasts.contains_key(id))
+    }
+
+    fn check_data(&mut self, target: &TargetJson, block: &str, kind: Code, name: &str, id: &str) {
+        let found = match kind {
+            Code::MissingVariable => self.has_variable(target, id),
+            Code::MissingList => self.has_list(target, id),
+            _ => self.has_broadcast(target, id),
+        };
+        if !found {
+            let what = match kind {
+                Code::MissingVariable => "variable",
+                Code::MissingList => "list",
+                _ => "broadcast",
+            };
+            let message = format!(
+                "Refers to {} {:?} ({}), which is not declared",
+                what, name, id
+            );
+            self.report(kind, &target.name, Some(block), message);
+        }
+    }
+
+    fn check_primitive(&mut self, target: &TargetJson, block: &str, primitive: &Primitive) {
+        let (kind, name, id) = match primitive {
+            Primitive::Variable { name, id } => (Code::MissingVariable, name, id),
+            Primitive::List { name, id } => (Code::MissingList, name, id),
+            Primitive::Broadcast { name, id } => (Code::MissingBroadcast, name, id),
+            _ => return,
+        };
+        self.check_data(target, block, kind, name, id);
+    }
+
+    fn references(&mut self, target: &TargetJson) {
+        let backdrops = self
+            .stage
+            .map(|stage| stage.costumes.as_slice())
+            .unwrap_or(&[]);
+
+        let mut loose = target
+            .blocks
+            .iter()
+            .filter_map(|(id, block)| match block {
+                TargetBlock::Primitive(primitive) => Some((id, &primitive.primitive)),
+                TargetBlock::Block(_) => None,
+            })
+            .collect::<Vec<_>>();
+        loose.sort_by(|a, b| a.0.cmp(b.0));
+        for (id, primitive) in loose {
+            self.check_primitive(target, id, primitive);
+        }
+
+        for (id, block) in sorted_blocks(target) {
+            let mut fields = block.fields.iter().collect::<Vec<_>>();
+            fields.sort_by(|a, b| a.0.cmp(b.0));
+            for (field_name, field) in fields {
+                let kind = match field_name.as_str() {
+                    "VARIABLE" => Code::MissingVariable,
+                    "LIST" => Code::MissingList,
+                    "BROADCAST_OPTION" => Code::MissingBroadcast,
+                    _ => continue,
+                };
+                if let Some(data_id) = field.id.as_deref() {
+                    self.check_data(target, id, kind, &field.value, data_id);
+                }
+            }
+
+            for input in block.inputs.values() {
+                for primitive in input_primitives(input) {
+                    self.check_primitive(target, id, primitive);
+                }
+            }
+
+            let field = |name| block.fields.get(name).map(|f| f.value.as_str());
+            match block.opcode.as_str() {
+                "looks_costume" => {
+                    let costume = field("COSTUME").unwrap_or("");
+                    if !target.costumes.iter().any(|c| c.name == costume) {
+                        let message = format!("Switches to missing costume {:?}", costume);
+                        self.report(Code::MissingCostume, &target.name, Some(id), message);
+                    }
+                }
+                "looks_backdrops" => {
+                    let backdrop = field("BACKDROP").unwrap_or("");
+                    let special = ["next backdrop", "previous backdrop", "random backdrop"];
+                    if !special.contains(&backdrop) && !backdrops.iter().any(|c| c.name == backdrop)
+                    {
+                        let message = format!("Switches to missing backdrop {:?}", backdrop);
+                        self.report(Code::MissingCostume, &target.name, Some(id), message);
+                    }
+                }
+                "sound_sounds_menu" => {
+                    let sound = field("SOUND_MENU").unwrap_or("");
+                    if !target.sounds.iter().any(|s| s.name == sound) {
+                        let message = format!("Plays missing sound {:?}", sound);
+                        self.report(Code::MissingSound, &target.name, Some(id), message);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn assets(&mut self, target: &TargetJson, has_asset: &dyn Fn(&str) -> bool) {
+        let costumes = target.costumes.iter().map(|c| (&c.name, &c.md5ext));
+        let sounds = target.sounds.iter().map(|s| (&s.name, &s.md5ext));
+        for (name, md5ext) in costumes.chain(sounds) {
+            if !has_asset(md5ext) {
+                let message = format!("{:?} needs {}, which is not in the project", name, md5ext);
+                self.report(Code::MissingAsset, &target.name, None, message);
+            }
+        }
+    }
+}
@@ -0,0 +1,381 @@
+mod list;
+
+pub use self::list::{
+    List,
+    ListIndex,
+    LIST_ITEM_LIMIT,
+};
+use std::{
+    cmp::Ordering,
+    fmt,
+};
+
+/// A value as Scratch passes it between blocks, converted between numbers, text and booleans on demand
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    String(String),
+    Bool(bool),
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Value::Number(0.0)
+    }
+}
+
+/// Parses text the way JavaScript's `Number()` does, giving NaN for anything that isn't a number
+fn parse_number(text: &str) -> f64 {
+    let text = text.trim();
+    if text.is_empty() {
+        return 0.0;
+    }
+
+    match text {
+        "Infinity" | "+Infinity" => return f64::INFINITY,
+        "-Infinity" => return f64::NEG_INFINITY,
+        _ => {}
+    }
+    let radix = match text.get(..2) {
+        Some("0x") | Some("0X") => Some(16),
+        Some("0b") | Some("0B") => Some(2),
+        Some("0o") | Some("0O") => Some(8),
+        _ => None,
+    };
+    if let Some(radix) = radix {
+        return u64::from_str_radix(&text[2..], radix)
+            .map(|n| n as f64)
+            .unwrap_or(f64::NAN);
+    }
+    // Rust also reads "inf" and "nan" in any case, which JavaScript doesn't
+    if text.contains(|c: char| c.is_ascii_alphabetic() && c != 'e' && c != 'E') {
+        return f64::NAN;
+    }
+    text.parse().unwrap_or(f64::NAN)
+}
+
+/// Writes a number the way JavaScript's `String()` does, switching to exponents past 1e21 and below 1e-6
+fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return "NaN".into();
+    }
+    if n.is_infinite() {
+        return if n < 0.0 { "-Infinity" } else { "Infinity" }.into();
+    }
+    // Also covers -0
+    if n == 0.0 {
+        return "0".into();
+    }
+
+    // Rust's exponent form has the shortest digits that read back as the same number, like JavaScript
+    let exponent_form = format!("{:e}", n.abs());
+    let (mantissa, exponent) = exponent_form.split_at(exponent_form.find('e').expect("Exponent"));
+    let digits = mantissa.replace('.', "");
+    let len = digits.len() as i32;
+    // Where the decimal point goes, counting from the start of the digits
+    let point = exponent[1..].parse::<i32>().expect("Exponent") + 1;
+
+    let number = if len <= point && point <= 21 {
+        digits + &"0".repeat((point - len) as usize)
+    } else if 0 < point && point <= 21 {
+        let (whole, fraction) = digits.split_at(point as usize);
+        format!("{}.{}", whole, fraction)
+    } else if -6 < point && point <= 0 {
+        format!("0.{}{}", "0".repeat(-point as usize), digits)
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let mantissa = if rest.is_empty() {
+            first.to_string()
+        } else {
+            format!("{}.{}", first, rest)
+        };
+        format!("{}e{:+}", mantissa, point - 1)
+    };
+    if n < 0.0 {
+        format!("-{}", number)
+    } else {
+        number
+    }
+}
+
+/// Whether text is empty or only whitespace, which Scratch never treats as a number
+fn is_white_space(text: &str) -> bool {
+    text.trim().is_empty()
+}
+
+impl Value {
+    /// The number this value stands for, with anything that isn't a number counting as 0
+    pub fn to_number(&self) -> f64 {
+        let n = match self {
+            Value::Number(n) => *n,
+            Value::String(s) => parse_number(s),
+            Value::Bool(b) => f64::from(u8::from(*b)),
+        };
+        if n.is_nan() {
+            0.0
+        } else {
+            n
+        }
+    }
+
+    pub fn to_bool(&self) -> bool {
+        match self {
+            Value::Number(n) => *n != 0.0 && !n.is_nan(),
+            Value::String(s) => !(s.is_empty() || s == "0" || s.eq_ignore_ascii_case("false")),
+            Value::Bool(b) => *b,
+        }
+    }
+
+    /// Whether this value is written as a whole number, which makes `pick random` pick whole numbers. Like
+    /// scratch-vm, which checks `parseInt`, NaN counts and numbers written with an exponent, from 1e21 up, don't.
+    pub fn is_int(&self) -> bool {
+        match self {
+            Value::Number(n) => n.is_nan() || (n.fract() == 0.0 && n.abs() < 1e21),
+            Value::String(s) => !s.contains('.'),
+            Value::Bool(_) => true,
+        }
+    }
+
+    /// The number to compare this value as, or `None` if it should be compared as text
+    pub(crate) fn numeric(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) if n.is_nan() => None,
+            Value::Number(n) => Some(*n),
+            Value::String(s) if is_white_space(s) => None,
+            Value::String(s) => Some(parse_number(s)).filter(|n| !n.is_nan()),
+            Value::Bool(b) => Some(f64::from(u8::from(*b))),
+        }
+    }
+
+    /// Compares like Scratch's `<`, `=` and `>`: as numbers if both are numbers, otherwise as case-insensitive text
+    pub fn compare(&self, other: &Value) -> Ordering {
+        match (self.numeric(), other.numeric()) {
+            (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            _ => {
+                let a = self.to_string().to_lowercase();
+                let b = other.to_string().to_lowercase();
+                a.cmp(&b)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", format_number(*n)),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<&serde_json::Value> for Value {
+    fn from(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Number(n) => Value::Number(n.as_f64().unwrap_or(0.0)),
+            serde_json::Value::String(s) => Value::String(s.clone()),
+            serde_json::Value::Bool(b) => Value::Bool(*b),
+            serde_json::Value::Null => Value::String(String::new()),
+            value => Value::String(value.to_string()),
+        }
+    }
+}
+
+/// Saves a value as project.json stores it. Whole numbers are written without a fraction, and NaN and the infinities,
+/// which JSON can't hold, as their text.
+impl From<&Value> for serde_json::Value {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Number(n) if !n.is_finite() => serde_json::Value::from(format_number(*n)),
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => {
+                serde_json::Value::from(*n as i64)
+            }
+            Value::Number(n) => serde_json::Value::from(*n),
+            Value::String(s) => serde_json::Value::from(s.as_str()),
+            Value::Bool(b) => serde_json::Value::from(*b),
+        }
+    }
+}
+
+/// `operator_add`
+pub fn add(lhs: &Value, rhs: &Value) -> Value {
+    Value::from(lhs.to_number() + rhs.to_number())
+}
+
+/// `operator_subtract`
+pub fn subtract(lhs: &Value, rhs: &Value) -> Value {
+    Value::from(lhs.to_number() - rhs.to_number())
+}
+
+/// `operator_multiply`
+pub fn multiply(lhs: &Value, rhs: &Value) -> Value {
+    Value::from(lhs.to_number() * rhs.to_number())
+}
+
+/// `operator_divide`. Dividing by 0 gives an infinity, or NaN for 0 / 0.
+pub fn divide(lhs: &Value, rhs: &Value) -> Value {
+    Value::from(lhs.to_number() / rhs.to_number())
+}
+
+/// `operator_mod`, where the result takes the sign of the modulus
+pub fn modulo(lhs: &Value, rhs: &Value) -> Value {
+    let modulus = rhs.to_number();
+    let result = lhs.to_number() % modulus;
+    Value::from(if result / modulus < 0.0 {
+        result + modulus
+    } else {
+        result
+    })
+}
+
+/// `operator_random`, given a number in `[0, 1)`. Picks a whole number if neither end is written with a decimal point.
+pub fn random(from: &Value, to: &Value, random: f64) -> Value {
+    let (a, b) = (from.to_number(), to.to_number());
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    if low == high {
+        return Value::from(low);
+    }
+    Value::from(if from.is_int() && to.is_int() {
+        low + (random * (high - low + 1.0)).floor()
+    } else {
+        low + random * (high - low)
+    })
+}
+
+/// `operator_lt`
+pub fn lt(lhs: &Value, rhs: &Value) -> Value {
+    Value::from(lhs.compare(rhs) == Ordering::Less)
+}
+
+/// `operator_equals`
+pub fn equals(lhs: &Value, rhs: &Value) -> Value {
+    Value::from(lhs.compare(rhs) == Ordering::Equal)
+}
+
+/// `operator_gt`
+pub fn gt(lhs: &Value, rhs: &Value) -> Value {
+    Value::from(lhs.compare(rhs) == Ordering::Greater)
+}
+
+/// `operator_and`
+pub fn and(lhs: &Value, rhs: &Value) -> Value {
+    Value::from(lhs.to_bool() && rhs.to_bool())
+}
+
+/// `operator_or`
+pub fn or(lhs: &Value, rhs: &Value) -> Value {
+    Value::from(lhs.to_bool() || rhs.to_bool())
+}
+
+/// `operator_not`
+pub fn not(operand: &Value) -> Value {
+    Value::from(!operand.to_bool())
+}
+
+/// `operator_join`, which always gives text, even for two numbers
+pub fn join(lhs: &Value, rhs: &Value) -> Value {
+    Value::from(format!("{}{}", lhs, rhs))
+}
+
+/// `operator_letter_of`, from 1. Gives empty text outside of the string.
+pub fn letter_of(letter: &Value, text: &Value) -> Value {
+    let index = letter.to_number().floor() - 1.0;
+    let letter = if index < 0.0 {
+        None
+    } else {
+        text.to_string().chars().nth(index as usize)
+    };
+    Value::from(letter.map(String::from).unwrap_or_default())
+}
+
+/// `operator_length`
+pub fn length(text: &Value) -> Value {
+    Value::from(text.to_string().chars().count() as f64)
+}
+
+/// `operator_contains`, ignoring case
+pub fn contains(text: &Value, part: &Value) -> Value {
+    let (text, part) = (
+        text.to_string().to_lowercase(),
+        part.to_string().to_lowercase(),
+    );
+    Value::from(text.contains(&part))
+}
+
+/// `operator_round`, which rounds halves up, so -2.5 rounds to -2, like JavaScript's `Math.round`
+pub fn round(num: &Value) -> Value {
+    Value::from(js_round(num.to_number()))
+}
+
+/// `Math.round`. Adding 0.5 and flooring would round 0.49999999999999994 up, as the sum rounds to 1.
+pub fn js_round(n: f64) -> f64 {
+    let floor = n.floor();
+    // Exact, since it only keeps the fraction's bits
+    if n - floor >= 0.5 {
+        floor + 1.0
+    } else {
+        floor
+    }
+}
+
+/// Rounds off the error trig functions leave on exact results, like `sin(180)` being 1.2e-16, as Scratch does
+fn round_trig(n: f64) -> f64 {
+    (n * 1e10).round() / 1e10
+}
+
+/// `operator_mathop`, with angles in degrees. Unknown operators give 0.
+pub fn math_op(op: &str, num: &Value) -> Value {
+    let n = num.to_number();
+    Value::from(match op {
+        "abs" => n.abs(),
+        "floor" => n.floor(),
+        "ceiling" => n.ceil(),
+        "sqrt" => n.sqrt(),
+        "sin" => round_trig(n.to_radians().sin()),
+        "cos" => round_trig(n.to_radians().cos()),
+        "tan" => {
+            let angle = n.rem_euclid(360.0);
+            if angle == 90.0 {
+                f64::INFINITY
+            } else if angle == 270.0 {
+                f64::NEG_INFINITY
+            } else {
+                round_trig(n.to_radians().tan())
+            }
+        }
+        "asin" => n.asin().to_degrees(),
+        "acos" => n.acos().to_degrees(),
+        "atan" => n.atan().to_degrees(),
+        "ln" => n.ln(),
+        // As scratch-vm works it out, so `log 1000` is 2.9999999999999996 like in Scratch
+        "log" => n.ln() / std::f64::consts::LN_10,
+        "e ^" => n.exp(),
+        "10 ^" => 10f64.powf(n),
+        _ => 0.0,
+    })
+}
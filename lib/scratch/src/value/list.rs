@@ -0,0 +1,162 @@
+use super::Value;
+use crate::scratch3::ListJson;
+use std::cmp::Ordering;
+
+/// The most items Scratch lets a list hold. Adding to a full list does nothing.
+pub const LIST_ITEM_LIMIT: usize = 200_000;
+
+/// A place in a list, from `List::index`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListIndex {
+    All,
+    /// 0-based
+    At(usize),
+}
+
+/// The contents of a Scratch list, with the semantics of the `data_*list*` blocks
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct List {
+    items: Vec<Value>,
+}
+
+impl List {
+    pub fn new() -> Self {
+        List::default()
+    }
+
+    pub fn items(&self) -> &[Value] {
+        &self.items
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Reads a list index like Scratch: a 1-based number, `last`, `random` or `any`, or `all` if `all` is set, for a
+    /// list of `len` items. `random` is a number in `[0, 1)` to pick a random item with. Gives `None` if the index is
+    /// out of `1..=len`.
+    pub fn index(index: &Value, len: usize, all: bool, random: f64) -> Option<ListIndex> {
+        let number = match index {
+            Value::String(s) if s == "all" => return Some(ListIndex::All).filter(|_| all),
+            Value::String(s) if s == "last" => len as f64,
+            Value::String(s) if s == "random" || s == "any" => (random * len as f64).floor() + 1.0,
+            index => index.to_number().floor(),
+        };
+        if number < 1.0 || number > len as f64 {
+            return None;
+        }
+        Some(ListIndex::At(number as usize - 1))
+    }
+
+    /// `data_addtolist`
+    pub fn add(&mut self, item: Value) {
+        if self.items.len() < LIST_ITEM_LIMIT {
+            self.items.push(item);
+        }
+    }
+
+    /// `data_deleteoflist`
+    pub fn delete(&mut self, index: &Value, random: f64) {
+        match List::index(index, self.items.len(), true, random) {
+            Some(ListIndex::All) => self.items.clear(),
+            Some(ListIndex::At(index)) => {
+                self.items.remove(index);
+            }
+            None => {}
+        }
+    }
+
+    /// `data_deletealloflist`
+    pub fn delete_all(&mut self) {
+        self.items.clear();
+    }
+
+    /// `data_insertatlist`. Inserting into a full list drops its last item.
+    pub fn insert(&mut self, index: &Value, item: Value, random: f64) {
+        let index = match List::index(index, self.items.len() + 1, false, random) {
+            Some(ListIndex::At(index)) if index < LIST_ITEM_LIMIT => index,
+            _ => return,
+        };
+        self.items.insert(index, item);
+        self.items.truncate(LIST_ITEM_LIMIT);
+    }
+
+    /// `data_replaceitemoflist`
+    pub fn replace(&mut self, index: &Value, item: Value, random: f64) {
+        if let Some(ListIndex::At(index)) = List::index(index, self.items.len(), false, random) {
+            self.items[index] = item;
+        }
+    }
+
+    /// `data_itemoflist`, which gives empty text for an index that isn't in the list
+    pub fn item(&self, index: &Value, random: f64) -> Value {
+        match List::index(index, self.items.len(), false, random) {
+            Some(ListIndex::At(index)) => self.items[index].clone(),
+            _ => Value::from(""),
+        }
+    }
+
+    /// `data_itemnumoflist`: the 1-based position of the first item equal to `item`, ignoring case, or 0
+    pub fn item_num(&self, item: &Value) -> Value {
+        let position = self.position(item);
+        Value::from(position.map_or(0.0, |p| p as f64 + 1.0))
+    }
+
+    /// `data_lengthoflist`
+    pub fn length(&self) -> Value {
+        Value::from(self.items.len() as f64)
+    }
+
+    /// `data_listcontainsitem`, ignoring case
+    pub fn contains(&self, item: &Value) -> Value {
+        Value::from(self.position(item).is_some())
+    }
+
+    /// `data_listcontents`: the items joined with spaces, or without them if every item is a single character of text
+    pub fn contents(&self) -> Value {
+        let letters = self.items.iter().all(|item| match item {
+            Value::String(s) => s.chars().count() == 1,
+            _ => false,
+        });
+        let separator = if letters { "" } else { " " };
+        let items = self.items.iter().map(Value::to_string).collect::<Vec<_>>();
+        Value::from(items.join(separator))
+    }
+
+    fn position(&self, item: &Value) -> Option<usize> {
+        self.items
+            .iter()
+            .position(|i| i.compare(item) == Ordering::Equal)
+    }
+
+    /// Saves the list as it is declared in `TargetJson::lists`
+    pub fn to_json<S: Into<String>>(&self, name: S) -> ListJson {
+        ListJson::new(
+            name,
+            self.items.iter().map(serde_json::Value::from).collect(),
+        )
+    }
+}
+
+impl From<Vec<Value>> for List {
+    fn from(mut items: Vec<Value>) -> Self {
+        items.truncate(LIST_ITEM_LIMIT);
+        List { items }
+    }
+}
+
+impl From<&[serde_json::Value]> for List {
+    fn from(items: &[serde_json::Value]) -> Self {
+        List::from(items.iter().map(Value::from).collect::<Vec<_>>())
+    }
+}
+
+impl From<&ListJson> for List {
+    fn from(list: &ListJson) -> Self {
+        List::from(list.items.as_slice())
+    }
+}
@@ -0,0 +1,644 @@
+mod eval;
+mod exec;
+mod thread;
+
+use self::thread::{
+    Moment,
+    Thread,
+};
+pub use crate::value::{
+    List,
+    Value,
+};
+use crate::{
+    ir::{
+        self,
+        DataRef,
+        Hat,
+        Program,
+        Sprite,
+    },
+    scratch3::ListJson,
+    Project,
+};
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+        HashSet,
+    },
+    rc::Rc,
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+/// Frames run per second, like Scratch
+pub const FPS: u32 = 30;
+
+/// The most clones Scratch lets exist at once
+pub const MAX_CLONES: usize = 300;
+
+/// Seconds from 1970 to 2000, when a headless VM's calendar starts
+const EPOCH_2000: f64 = 946_684_800.0;
+
+/// Loop passes a thread in warp mode may make in one turn before it has to give the others a turn anyway
+const WARP_LIMIT: usize = 100_000;
+
+/// Blocks that lower to the IR but that the VM only stands in for: it has no costume bounds to bounce or touch with,
+/// no audio, no microphone and nobody to answer questions
+const STUBBED_OPCODES: &[&str] = &[
+    "motion_ifonedgebounce",
+    "sound_play",
+    "sound_playuntildone",
+    "sound_stopallsounds",
+    "music_playNoteForBeats",
+    "sensing_askandwait",
+    "sensing_touchingobject",
+    "sensing_loudness",
+];
+
+/// Whether the VM runs a Scratch 3 opcode like Scratch does, including hats and reporters
+pub fn supports(opcode: &str) -> bool {
+    ir::lowers(opcode) && !STUBBED_OPCODES.contains(&opcode)
+}
+
+/// The target at an index in `Program::targets`
+fn target(program: &Program, index: usize) -> &Sprite {
+    match index {
+        0 => &program.stage,
+        i => &program.sprites[i - 1],
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariableState {
+    pub id: String,
+    pub name: String,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListState {
+    pub id: String,
+    pub name: String,
+    pub items: List,
+}
+
+/// A variable or list, which blocks refer to by ID and name
+trait Data {
+    fn id(&self) -> &str;
+    fn name(&self) -> &str;
+}
+
+impl Data for VariableState {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Data for ListState {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Looks a variable or list up by ID, or else by name like Scratch does
+fn find<T: Data>(items: &[T], data: &DataRef) -> Option<usize> {
+    items
+        .iter()
+        .position(|item| item.id() == data.id)
+        .or_else(|| items.iter().position(|item| item.name() == data.name))
+}
+
+/// The runtime state of the stage, a sprite or a clone
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteState {
+    id: u64,
+    /// The index in `Program::targets` of the sprite this is, or was cloned from
+    target: usize,
+    pub name: String,
+    pub is_stage: bool,
+    pub is_clone: bool,
+    pub x: f64,
+    pub y: f64,
+    pub direction: f64,
+    pub size: f64,
+    pub visible: bool,
+    /// Index of the current costume, or backdrop for the stage
+    pub costume: usize,
+    pub rotation_style: String,
+    /// Graphic effects by lowercase name, left out while 0
+    pub effects: BTreeMap<String, f64>,
+    pub volume: f64,
+    /// What the sprite is saying or thinking
+    pub bubble: Option<String>,
+    pub variables: Vec<VariableState>,
+    pub lists: Vec<ListState>,
+}
+
+impl SpriteState {
+    fn new(id: u64, target: usize, sprite: &Sprite) -> Self {
+        SpriteState {
+            id,
+            target,
+            name: sprite.name.clone(),
+            is_stage: sprite.is_stage,
+            is_clone: false,
+            x: sprite.x,
+            y: sprite.y,
+            direction: sprite.direction,
+            size: sprite.size,
+            visible: sprite.visible,
+            costume: sprite.current_costume,
+            rotation_style: "all around".into(),
+            effects: BTreeMap::new(),
+            volume: 100.0,
+            bubble: None,
+            variables: sprite
+                .variables
+                .iter()
+                .map(|v| VariableState {
+                    id: v.id.clone(),
+                    name: v.name.clone(),
+                    value: Value::from(&v.value),
+                })
+                .collect(),
+            lists: sprite
+                .lists
+                .iter()
+                .map(|l| ListState {
+                    id: l.id.clone(),
+                    name: l.name.clone(),
+                    items: List::from(l.items.as_slice()),
+                })
+                .collect(),
+        }
+    }
+
+    /// A variable of this sprite or the stage itself, by name
+    pub fn variable(&self, name: &str) -> Option<&Value> {
+        self.variables
+            .iter()
+            .find(|v| v.name == name)
+            .map(|v| &v.value)
+    }
+
+    /// A list of this sprite or the stage itself, by name
+    pub fn list(&self, name: &str) -> Option<&List> {
+        self.lists.iter().find(|l| l.name == name).map(|l| &l.items)
+    }
+
+    /// The sprite's lists as they are declared in `TargetJson::lists`, so they can be saved with their current items
+    pub fn lists_json(&self) -> HashMap<String, ListJson> {
+        self.lists
+            .iter()
+            .map(|l| (l.id.clone(), l.items.to_json(l.name.as_str())))
+            .collect()
+    }
+}
+
+/// Called with a sprite's name and what it says or thinks
+pub type SayFn = dyn FnMut(&str, &str);
+
+/// Where a VM gets the time from
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Clock {
+    /// Wall-clock time since the VM was made
+    Real(Instant),
+    /// Time that only passes as frames are run, `1 / FPS` seconds each, starting at midnight on 2000-01-01 UTC
+    Virtual,
+}
+
+/// Runs a project's scripts as cooperative threads, a frame at a time like Scratch.
+///
+/// Every running thread gets one turn per frame, which lasts until it waits, reaches the end of a loop outside of
+/// warp mode, or finishes.
+pub struct Vm {
+    /// Shared so statements can be borrowed while threads and sprites change
+    program: Rc<Program>,
+    /// The stage first, then every sprite and clone
+    sprites: Vec<SpriteState>,
+    threads: Vec<Thread>,
+    next_id: u64,
+    clock: Clock,
+    /// Frames run so far
+    frames: u64,
+    timer_start: f64,
+    /// Held keys, by lowercase Scratch key name
+    keys: HashSet<String>,
+    mouse: (f64, f64),
+    mouse_down: bool,
+    answer: String,
+    /// `when ... > ...` hats whose condition held last frame, by sprite and script, so they only start when it
+    /// becomes true
+    edges: HashSet<(u64, usize)>,
+    rng: u64,
+    on_say: Option<Box<SayFn>>,
+}
+
+impl Vm {
+    pub fn new(project: &Project) -> Self {
+        Self::from_program(Program::from(&project.data))
+    }
+
+    pub fn from_program(program: Program) -> Self {
+        let sprites = program
+            .targets()
+            .enumerate()
+            .map(|(i, sprite)| SpriteState::new(i as u64, i, sprite))
+            .collect::<Vec<_>>();
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        Vm {
+            program: Rc::new(program),
+            next_id: sprites.len() as u64,
+            sprites,
+            threads: Vec::new(),
+            clock: Clock::Real(Instant::now()),
+            frames: 0,
+            timer_start: 0.0,
+            keys: HashSet::new(),
+            mouse: (0.0, 0.0),
+            mouse_down: false,
+            answer: String::new(),
+            edges: HashSet::new(),
+            rng: 0,
+            on_say: None,
+        }
+        .seed(seed)
+    }
+
+    /// A VM for tests and other runs without a display. Time only passes as frames are run, and `pick random` gives
+    /// the same numbers every run, so the same inputs always lead to the same sprite states.
+    pub fn headless(project: &Project) -> Self {
+        Self::from_program(Program::from(&project.data))
+            .virtual_clock()
+            .seed(1)
+    }
+
+    /// Makes time only pass as frames are run, so `run` never sleeps and waits last a set number of frames
+    pub fn virtual_clock(mut self) -> Self {
+        self.clock = Clock::Virtual;
+        self
+    }
+
+    /// Seeds `pick random` and the other random choices, which are otherwise different every run
+    pub fn seed(mut self, seed: u64) -> Self {
+        // Xorshift gets stuck at 0
+        self.rng = seed | 1;
+        self
+    }
+
+    /// Called with a sprite's name and message whenever it starts saying or thinking something
+    pub fn on_say<F: FnMut(&str, &str) + 'static>(mut self, on_say: F) -> Self {
+        self.on_say = Some(Box::new(on_say));
+        self
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn stage(&self) -> &SpriteState {
+        &self.sprites[0]
+    }
+
+    /// Every sprite and clone, without the stage
+    pub fn sprites(&self) -> &[SpriteState] {
+        &self.sprites[1..]
+    }
+
+    /// A sprite by name, not counting its clones
+    pub fn sprite(&self, name: &str) -> Option<&SpriteState> {
+        self.sprites()
+            .iter()
+            .find(|s| !s.is_clone && s.name == name)
+    }
+
+    /// Frames run so far
+    pub fn frame_count(&self) -> u64 {
+        self.frames
+    }
+
+    /// Seconds on the VM's clock, which only counts frames run if it is virtual
+    pub fn time(&self) -> f64 {
+        match self.clock {
+            Clock::Real(start) => start.elapsed().as_secs_f64(),
+            Clock::Virtual => self.frames as f64 / f64::from(FPS),
+        }
+    }
+
+    fn now(&self) -> Moment {
+        match self.clock {
+            Clock::Real(_) => Moment::Secs(self.time()),
+            Clock::Virtual => Moment::Frame(self.frames),
+        }
+    }
+
+    /// Seconds passed since `start`
+    fn secs_since(&self, start: Moment) -> f64 {
+        match start {
+            Moment::Secs(start) => self.time() - start,
+            Moment::Frame(start) => (self.frames - start) as f64 / f64::from(FPS),
+        }
+    }
+
+    /// Whether `secs` seconds have passed since `start`. On a virtual clock that takes the fewest whole frames that
+    /// last at least that long, so a 1 second wait always takes `FPS` frames.
+    fn has_passed(&self, start: Moment, secs: f64) -> bool {
+        match start {
+            Moment::Secs(_) => self.secs_since(start) >= secs,
+            Moment::Frame(start) => {
+                let frames = secs * f64::from(FPS);
+                // Rounding error in `secs` shouldn't cost a whole frame, like 0.1 s being 3.0000000000000004 frames
+                let frames = if (frames - frames.round()).abs() < 1e-9 {
+                    frames.round()
+                } else {
+                    frames.ceil()
+                };
+                (self.frames - start) as f64 >= frames
+            }
+        }
+    }
+
+    /// Whether any script is still running
+    pub fn is_running(&self) -> bool {
+        self.threads.iter().any(|t| !t.done)
+    }
+
+    /// Stops everything, then starts every `when green flag clicked` script
+    pub fn green_flag(&mut self) {
+        self.stop_all();
+        self.timer_start = self.time();
+        self.edges.clear();
+        self.start_hats(None, true, |hat| *hat == Hat::GreenFlag);
+    }
+
+    /// Starts every script waiting for a message, restarting the ones already running
+    pub fn broadcast(&mut self, message: &str) {
+        self.start_broadcast(message);
+    }
+
+    /// Presses a key by its Scratch name, like `space`, `up arrow` or `a`, until `key_up`
+    pub fn key_down(&mut self, key: &str) {
+        let key = key.to_lowercase();
+        self.start_hats(None, false, |hat| match hat {
+            Hat::KeyPressed(k) => k == "any" || k.to_lowercase() == key,
+            _ => false,
+        });
+        self.keys.insert(key);
+    }
+
+    pub fn key_up(&mut self, key: &str) {
+        self.keys.remove(&key.to_lowercase());
+    }
+
+    /// Clicks a sprite, or the stage by its name
+    pub fn click(&mut self, name: &str) {
+        if let Some(sprite) = self.sprites.iter().find(|s| !s.is_clone && s.name == name) {
+            let id = sprite.id;
+            self.start_hats(Some(id), true, |hat| *hat == Hat::Clicked);
+        }
+    }
+
+    /// Moves the mouse, in stage coordinates
+    pub fn set_mouse(&mut self, x: f64, y: f64, down: bool) {
+        self.mouse = (x, y);
+        self.mouse_down = down;
+    }
+
+    /// Stops every script and deletes every clone, like the stop sign
+    pub fn stop_all(&mut self) {
+        // Threads are only marked, as this can be called from one of them
+        for thread in self.threads.iter_mut() {
+            thread.done = true;
+        }
+        self.sprites.retain(|s| !s.is_clone);
+        for sprite in self.sprites.iter_mut() {
+            sprite.bubble = None;
+            sprite.effects.clear();
+        }
+    }
+
+    /// Gives every running thread a turn. Threads started during the frame get theirs in it too.
+    pub fn frame(&mut self) {
+        self.start_edge_hats();
+
+        let mut index = 0;
+        while index < self.threads.len() {
+            self.run_thread(index);
+            index += 1;
+        }
+        self.threads.retain(|t| !t.done);
+        self.frames += 1;
+    }
+
+    /// Runs a number of frames, without waiting between them
+    pub fn step(&mut self, frames: u64) {
+        for _ in 0..frames {
+            self.frame();
+        }
+    }
+
+    /// Runs frames until every script has finished, in real time unless the clock is virtual
+    pub fn run(&mut self) {
+        let frame_time = Duration::from_secs(1) / FPS;
+        while self.is_running() {
+            let start = Instant::now();
+            self.frame();
+            if let Clock::Virtual = self.clock {
+                continue;
+            }
+            if let Some(rest) = frame_time.checked_sub(start.elapsed()) {
+                std::thread::sleep(rest);
+            }
+        }
+    }
+
+    /// Seconds since 1970 in UTC, for the date and time reporters
+    fn unix_time(&self) -> f64 {
+        match self.clock {
+            Clock::Real(_) => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs_f64())
+                .unwrap_or(0.0),
+            Clock::Virtual => EPOCH_2000 + self.time(),
+        }
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// A number in `[0, 1)`, from xorshift64*
+    fn random(&mut self) -> f64 {
+        let mut x = self.rng;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng = x;
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Index in `sprites` of a sprite by name, or the stage by `_stage_`
+    fn sprite_named(&self, name: &str) -> Option<usize> {
+        if name == "_stage_" {
+            return Some(0);
+        }
+        self.sprites
+            .iter()
+            .position(|s| !s.is_stage && !s.is_clone && s.name == name)
+    }
+
+    /// Starts the scripts under matching hats, on one sprite or all of them. Scripts already running are restarted
+    /// if `restart` is set and left alone if not. Gives the IDs of the threads running them.
+    fn start_hats<F: Fn(&Hat) -> bool>(
+        &mut self,
+        only: Option<u64>,
+        restart: bool,
+        matches: F,
+    ) -> Vec<u64> {
+        let program = Rc::clone(&self.program);
+        let sprites = self
+            .sprites
+            .iter()
+            .filter(|s| only.map_or(true, |id| id == s.id))
+            .map(|s| (s.id, s.target))
+            .collect::<Vec<_>>();
+
+        let mut started = Vec::new();
+        for (id, index) in sprites {
+            for (script, _) in target(&program, index)
+                .scripts
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| matches(&s.hat))
+            {
+                started.extend(self.start_script(id, index, script, restart));
+            }
+        }
+        started
+    }
+
+    fn start_script(
+        &mut self,
+        sprite: u64,
+        target: usize,
+        script: usize,
+        restart: bool,
+    ) -> Option<u64> {
+        let running = self
+            .threads
+            .iter_mut()
+            .find(|t| !t.done && t.sprite == sprite && t.script == (target, script));
+        if let Some(thread) = running {
+            return if restart {
+                thread.restart();
+                Some(thread.id)
+            } else {
+                None
+            };
+        }
+
+        let id = self.next_id();
+        self.threads.push(Thread::new(id, sprite, target, script));
+        Some(id)
+    }
+
+    fn start_broadcast(&mut self, message: &str) -> Vec<u64> {
+        let message = message.to_lowercase();
+        self.start_hats(None, true, |hat| match hat {
+            Hat::BroadcastReceived(name) => name.to_lowercase() == message,
+            _ => false,
+        })
+    }
+
+    /// Starts `when timer > ...` and `when loudness > ...` scripts whose condition just became true
+    fn start_edge_hats(&mut self) {
+        let program = Rc::clone(&self.program);
+        for i in 0..self.sprites.len() {
+            let (id, index) = (self.sprites[i].id, self.sprites[i].target);
+            for (script, s) in target(&program, index).scripts.iter().enumerate() {
+                if let Hat::GreaterThan { sensor, value } = &s.hat {
+                    let current = match sensor.to_lowercase().as_str() {
+                        "timer" => self.time() - self.timer_start,
+                        // There's no microphone
+                        _ => -1.0,
+                    };
+                    let threshold = self.eval(None, i, value).to_number();
+                    if current <= threshold {
+                        self.edges.remove(&(id, script));
+                    } else if self.edges.insert((id, script)) {
+                        self.start_script(id, index, script, false);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sets what a sprite is saying, or clears it for `None` or an empty message
+    fn say(&mut self, i: usize, message: Option<String>) {
+        let message = message.filter(|m| !m.is_empty());
+        if let (Some(on_say), Some(message)) = (self.on_say.as_mut(), message.as_ref()) {
+            on_say(&self.sprites[i].name, message);
+        }
+        self.sprites[i].bubble = message;
+    }
+
+    /// The variable a block in a sprite refers to, from the sprite, else the stage, else made on the stage like
+    /// Scratch does
+    fn variable_mut(&mut self, i: usize, data: &DataRef) -> &mut Value {
+        let (owner, index) = match find(&self.sprites[i].variables, data) {
+            Some(index) => (i, index),
+            None => match find(&self.sprites[0].variables, data) {
+                Some(index) => (0, index),
+                None => {
+                    let variables = &mut self.sprites[0].variables;
+                    variables.push(VariableState {
+                        id: data.id.clone(),
+                        name: data.name.clone(),
+                        value: Value::default(),
+                    });
+                    (0, variables.len() - 1)
+                }
+            },
+        };
+        &mut self.sprites[owner].variables[index].value
+    }
+
+    /// The list a block in a sprite refers to, found or made like `variable_mut`
+    fn list_mut(&mut self, i: usize, data: &DataRef) -> &mut List {
+        let (owner, index) = match find(&self.sprites[i].lists, data) {
+            Some(index) => (i, index),
+            None => match find(&self.sprites[0].lists, data) {
+                Some(index) => (0, index),
+                None => {
+                    let lists = &mut self.sprites[0].lists;
+                    lists.push(ListState {
+                        id: data.id.clone(),
+                        name: data.name.clone(),
+                        items: List::new(),
+                    });
+                    (0, lists.len() - 1)
+                }
+            },
+        };
+        &mut self.sprites[owner].lists[index].items
+    }
+}
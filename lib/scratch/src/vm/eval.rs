@@ -0,0 +1,223 @@
+use super::{
+    exec::TEMPO,
+    target,
+    Value,
+    Vm,
+    EPOCH_2000,
+};
+use crate::{
+    ir::{
+        BinaryOp,
+        Expr,
+    },
+    value,
+};
+
+/// Snaps coordinates that are within floating point error of a whole number to it, like Scratch's reporters do
+fn limit_precision(n: f64) -> f64 {
+    let rounded = n.round();
+    if (n - rounded).abs() < 1e-9 {
+        rounded
+    } else {
+        n
+    }
+}
+
+/// The UTC date and time at a Unix time, for `current ...`, as (year, month, day, weekday from 1 for Sunday, hour,
+/// minute, second)
+fn current_time(unix_time: f64) -> (i64, i64, i64, i64, i64, i64, i64) {
+    let secs = unix_time.floor() as i64;
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Howard Hinnant's civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    // 1970-01-01 was a Thursday
+    let weekday = (days + 4).rem_euclid(7) + 1;
+    (
+        year,
+        month,
+        day,
+        weekday,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+    )
+}
+
+impl Vm {
+    /// Works out a reporter for a sprite, in a thread if it needs custom block arguments
+    pub(super) fn eval(&mut self, t: Option<usize>, i: usize, expr: &Expr) -> Value {
+        match expr {
+            Expr::Literal(text) => Value::from(text.as_str()),
+            Expr::Variable(data) => self.variable_mut(i, data).clone(),
+            Expr::ListContents(data) => self.list_mut(i, data).contents(),
+            Expr::Argument(name) => t
+                .and_then(|t| {
+                    self.threads[t]
+                        .args()
+                        .iter()
+                        .find(|(n, _)| n == name)
+                        .map(|(_, value)| value.clone())
+                })
+                .unwrap_or_default(),
+
+            Expr::Binary { op, lhs, rhs } => {
+                let lhs = self.eval(t, i, lhs);
+                let rhs = self.eval(t, i, rhs);
+                self.binary(*op, &lhs, &rhs)
+            }
+            Expr::Not(operand) => value::not(&self.eval(t, i, operand)),
+            Expr::Round(num) => value::round(&self.eval(t, i, num)),
+            Expr::Length(text) => value::length(&self.eval(t, i, text)),
+            Expr::MathOp { op, num } => value::math_op(op, &self.eval(t, i, num)),
+
+            Expr::XPosition => Value::from(limit_precision(self.sprites[i].x)),
+            Expr::YPosition => Value::from(limit_precision(self.sprites[i].y)),
+            Expr::Direction => Value::from(self.sprites[i].direction),
+            Expr::Size => Value::from(value::js_round(self.sprites[i].size)),
+            Expr::Costume { number } => self.costume(i, *number),
+            Expr::Backdrop { number } => self.costume(0, *number),
+            Expr::Volume => Value::from(self.sprites[i].volume),
+            Expr::Tempo => Value::from(TEMPO),
+
+            Expr::Touching(object) => {
+                // Without costume bounds only the edge can be touched, by the sprite's centre
+                let object = self.eval(t, i, object).to_string();
+                let sprite = &self.sprites[i];
+                Value::from(
+                    object == "_edge_"
+                        && !sprite.is_stage
+                        && (sprite.x.abs() >= 240.0 || sprite.y.abs() >= 180.0),
+                )
+            }
+            Expr::DistanceTo(object) => {
+                let object = self.eval(t, i, object).to_string();
+                let sprite = &self.sprites[i];
+                let distance = match self.position_of(&object) {
+                    Some((x, y)) if !sprite.is_stage => (x - sprite.x).hypot(y - sprite.y),
+                    _ => 10000.0,
+                };
+                Value::from(distance)
+            }
+            Expr::KeyPressed(key) => {
+                let key = self.eval(t, i, key).to_string().to_lowercase();
+                Value::from(if key == "any" {
+                    !self.keys.is_empty()
+                } else {
+                    self.keys.contains(&key)
+                })
+            }
+            Expr::MouseDown => Value::from(self.mouse_down),
+            Expr::MouseX => Value::from(self.mouse.0),
+            Expr::MouseY => Value::from(self.mouse.1),
+            // There's no microphone
+            Expr::Loudness => Value::from(-1.0),
+            Expr::Timer => Value::from(self.time() - self.timer_start),
+            Expr::Answer => Value::from(self.answer.as_str()),
+            Expr::Of { property, object } => {
+                let object = self.eval(t, i, object).to_string();
+                match self.sprite_named(&object) {
+                    Some(object) => self.property(object, property),
+                    None => Value::from(0.0),
+                }
+            }
+            Expr::Current(menu) => {
+                let (year, month, day, weekday, hour, minute, second) =
+                    current_time(self.unix_time());
+                let value = match menu.to_lowercase().as_str() {
+                    "year" => year,
+                    "month" => month,
+                    "date" => day,
+                    "dayofweek" => weekday,
+                    "hour" => hour,
+                    "minute" => minute,
+                    "second" => second,
+                    _ => 0,
+                };
+                Value::from(value as f64)
+            }
+            Expr::DaysSince2000 => Value::from((self.unix_time() - EPOCH_2000) / 86400.0),
+            Expr::Username => Value::from(""),
+
+            Expr::ItemOfList { list, index } => {
+                let index = self.eval(t, i, index);
+                let random = self.random();
+                self.list_mut(i, list).item(&index, random)
+            }
+            Expr::ItemNumOfList { list, item } => {
+                let item = self.eval(t, i, item);
+                self.list_mut(i, list).item_num(&item)
+            }
+            Expr::LengthOfList(list) => self.list_mut(i, list).length(),
+            Expr::ListContains { list, item } => {
+                let item = self.eval(t, i, item);
+                self.list_mut(i, list).contains(&item)
+            }
+
+            Expr::Other { .. } => Value::from(""),
+        }
+    }
+
+    fn binary(&mut self, op: BinaryOp, lhs: &Value, rhs: &Value) -> Value {
+        match op {
+            BinaryOp::Add => value::add(lhs, rhs),
+            BinaryOp::Subtract => value::subtract(lhs, rhs),
+            BinaryOp::Multiply => value::multiply(lhs, rhs),
+            BinaryOp::Divide => value::divide(lhs, rhs),
+            BinaryOp::Mod => value::modulo(lhs, rhs),
+            BinaryOp::Random => {
+                let random = self.random();
+                value::random(lhs, rhs, random)
+            }
+            BinaryOp::LessThan => value::lt(lhs, rhs),
+            BinaryOp::Equals => value::equals(lhs, rhs),
+            BinaryOp::GreaterThan => value::gt(lhs, rhs),
+            BinaryOp::And => value::and(lhs, rhs),
+            BinaryOp::Or => value::or(lhs, rhs),
+            BinaryOp::Join => value::join(lhs, rhs),
+            BinaryOp::LetterOf => value::letter_of(lhs, rhs),
+            BinaryOp::Contains => value::contains(lhs, rhs),
+        }
+    }
+
+    /// A sprite's costume, or the stage's backdrop, by 1-based number or name
+    fn costume(&self, i: usize, number: bool) -> Value {
+        let sprite = &self.sprites[i];
+        if number {
+            return Value::from(sprite.costume as f64 + 1.0);
+        }
+        let name = target(&self.program, sprite.target)
+            .costumes
+            .get(sprite.costume)
+            .map(|c| c.name.as_str())
+            .unwrap_or("");
+        Value::from(name)
+    }
+
+    /// `... of ...`, with variables by name
+    fn property(&self, i: usize, property: &str) -> Value {
+        let sprite = &self.sprites[i];
+        match (sprite.is_stage, property) {
+            (true, "backdrop #") => self.costume(i, true),
+            (true, "backdrop name") => self.costume(i, false),
+            (false, "x position") => Value::from(limit_precision(sprite.x)),
+            (false, "y position") => Value::from(limit_precision(sprite.y)),
+            (false, "direction") => Value::from(sprite.direction),
+            (false, "costume #") => self.costume(i, true),
+            (false, "costume name") => self.costume(i, false),
+            (false, "size") => Value::from(value::js_round(sprite.size)),
+            (_, "volume") => Value::from(sprite.volume),
+            // Only the target's own variables, not the stage's
+            (_, name) => sprite.variable(name).cloned().unwrap_or_default(),
+        }
+    }
+}
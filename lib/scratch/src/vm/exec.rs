@@ -0,0 +1,623 @@
+use super::{
+    target,
+    thread::{
+        self,
+        Body,
+        Frame,
+        Wait,
+    },
+    Value,
+    Vm,
+    MAX_CLONES,
+    WARP_LIMIT,
+};
+use crate::{
+    ir::{
+        Expr,
+        Hat,
+        Statement,
+    },
+    value::js_round,
+};
+use std::rc::Rc;
+
+/// Beats per minute for `rest for` and `play note`, which nothing can change yet
+pub(super) const TEMPO: f64 = 60.0;
+
+/// What a thread does after a statement
+enum Flow {
+    Next,
+    /// Runs a branch or custom block, coming back to this statement afterwards
+    Enter(Frame),
+    /// Like `Enter`, but gives the other threads a turn first, as Scratch does for recursive custom blocks
+    EnterLater(Frame),
+    /// Ends the turn and runs this statement again next frame
+    Yield,
+    /// Leaves the custom block being run, or ends the thread outside of one
+    Return,
+    End,
+}
+
+/// Wraps a direction into `(-180, 180]`
+pub(super) fn wrap_direction(direction: f64) -> f64 {
+    (direction + 179.0).rem_euclid(360.0) - 179.0
+}
+
+/// The index of a 1-based costume number, wrapped around the costumes like Scratch
+fn wrap_costume(number: f64, len: usize) -> usize {
+    (js_round(number) as i64 - 1).rem_euclid(len as i64) as usize
+}
+
+impl Vm {
+    /// Gives a thread its turn
+    pub(super) fn run_thread(&mut self, t: usize) {
+        let program = Rc::clone(&self.program);
+        self.threads[t].restarted = false;
+        let mut passes = 0;
+
+        loop {
+            let thread = &mut self.threads[t];
+            if thread.done {
+                return;
+            }
+            let i = match self.sprites.iter().position(|s| s.id == thread.sprite) {
+                Some(i) => i,
+                None => {
+                    thread.done = true;
+                    return;
+                }
+            };
+
+            let body = thread::body(&program, &thread.frames);
+            let pc = thread.frames.last().map_or(0, |f| f.pc);
+            let statement = match body.get(pc) {
+                Some(statement) => statement,
+                None => {
+                    let frame = thread.frames.pop().expect("Running Thread");
+                    let parent = match thread.frames.last_mut() {
+                        Some(parent) => parent,
+                        None => {
+                            thread.done = true;
+                            return;
+                        }
+                    };
+                    if frame.is_loop {
+                        passes += 1;
+                        if !frame.warp || passes >= WARP_LIMIT {
+                            return;
+                        }
+                    } else {
+                        parent.next();
+                    }
+                    continue;
+                }
+            };
+
+            let flow = self.execute(t, i, statement);
+            let thread = &mut self.threads[t];
+            if thread.done || thread.restarted {
+                return;
+            }
+            match flow {
+                Flow::Next => thread.frames.last_mut().expect("Running Thread").next(),
+                Flow::Enter(frame) => thread.frames.push(frame),
+                Flow::EnterLater(frame) => {
+                    thread.frames.push(frame);
+                    return;
+                }
+                Flow::Yield => return,
+                Flow::Return => {
+                    let procedure = thread
+                        .frames
+                        .iter()
+                        .rposition(|f| matches!(f.body, Body::Procedure { .. }));
+                    match procedure {
+                        Some(index) if index > 0 => {
+                            thread.frames.truncate(index);
+                            thread.frames[index - 1].next();
+                        }
+                        _ => thread.done = true,
+                    }
+                }
+                Flow::End => thread.done = true,
+            }
+        }
+    }
+
+    fn top(&mut self, t: usize) -> &mut Frame {
+        self.threads[t].frames.last_mut().expect("Running Thread")
+    }
+
+    fn value(&mut self, t: usize, i: usize, expr: &Expr) -> Value {
+        self.eval(Some(t), i, expr)
+    }
+
+    /// A frame for a branch of the statement the thread is on
+    fn branch(&mut self, t: usize, index: usize, is_loop: bool) -> Frame {
+        let mut frame = Frame::new(Body::Branch(index), self.top(t).warp);
+        frame.is_loop = is_loop;
+        frame
+    }
+
+    /// Waits `secs` seconds, worked out the first time the statement runs. Always takes at least one frame.
+    fn wait(&mut self, t: usize, secs: impl FnOnce(&mut Self) -> f64) -> Flow {
+        match self.top(t).wait.clone() {
+            Some(Wait::Secs { start, secs }) if !self.has_passed(start, secs) => Flow::Yield,
+            Some(_) => Flow::Next,
+            None => {
+                let start = self.now();
+                let secs = secs(self);
+                self.top(t).wait = Some(Wait::Secs { start, secs });
+                Flow::Yield
+            }
+        }
+    }
+
+    /// Waits for threads started the first time the statement runs. If none start, there's nothing to wait for.
+    fn wait_for(&mut self, t: usize, start: impl FnOnce(&mut Self) -> Vec<u64>) -> Flow {
+        let running = match &self.top(t).wait {
+            Some(Wait::Threads(ids)) => ids.clone(),
+            _ => {
+                let ids = start(self);
+                if ids.is_empty() {
+                    return Flow::Next;
+                }
+                self.top(t).wait = Some(Wait::Threads(ids));
+                return Flow::Yield;
+            }
+        };
+        if self
+            .threads
+            .iter()
+            .any(|thread| !thread.done && running.contains(&thread.id))
+        {
+            Flow::Yield
+        } else {
+            Flow::Next
+        }
+    }
+
+    fn execute(&mut self, t: usize, i: usize, statement: &Statement) -> Flow {
+        match statement {
+            Statement::MoveSteps(steps) => {
+                let steps = self.value(t, i, steps).to_number();
+                let sprite = &mut self.sprites[i];
+                let radians = (90.0 - sprite.direction).to_radians();
+                sprite.x += steps * radians.cos();
+                sprite.y += steps * radians.sin();
+            }
+            Statement::TurnRight(degrees) => {
+                let degrees = self.value(t, i, degrees).to_number();
+                let sprite = &mut self.sprites[i];
+                sprite.direction = wrap_direction(sprite.direction + degrees);
+            }
+            Statement::TurnLeft(degrees) => {
+                let degrees = self.value(t, i, degrees).to_number();
+                let sprite = &mut self.sprites[i];
+                sprite.direction = wrap_direction(sprite.direction - degrees);
+            }
+            Statement::PointInDirection(direction) => {
+                let direction = self.value(t, i, direction).to_number();
+                self.sprites[i].direction = wrap_direction(direction);
+            }
+            Statement::PointTowards(towards) => {
+                let towards = self.value(t, i, towards).to_string();
+                let direction = if towards == "_random_" {
+                    Some(js_round(self.random() * 360.0) - 180.0)
+                } else {
+                    self.position_of(&towards).and_then(|(x, y)| {
+                        let (dx, dy) = (x - self.sprites[i].x, y - self.sprites[i].y);
+                        if dx == 0.0 && dy == 0.0 {
+                            None
+                        } else {
+                            Some(90.0 - dy.atan2(dx).to_degrees())
+                        }
+                    })
+                };
+                if let Some(direction) = direction {
+                    self.sprites[i].direction = wrap_direction(direction);
+                }
+            }
+            Statement::GoTo(to) => {
+                let to = self.value(t, i, to).to_string();
+                let position = if to == "_random_" {
+                    Some((
+                        js_round(self.random() * 480.0 - 240.0),
+                        js_round(self.random() * 360.0 - 180.0),
+                    ))
+                } else {
+                    self.position_of(&to)
+                };
+                if let Some((x, y)) = position {
+                    self.sprites[i].x = x;
+                    self.sprites[i].y = y;
+                }
+            }
+            Statement::GoToXY { x, y } => {
+                let x = self.value(t, i, x).to_number();
+                let y = self.value(t, i, y).to_number();
+                self.sprites[i].x = x;
+                self.sprites[i].y = y;
+            }
+            Statement::GlideToXY { secs, x, y } => {
+                let now = self.now();
+                if self.top(t).wait.is_none() {
+                    let secs = self.value(t, i, secs).to_number();
+                    let to = (
+                        self.value(t, i, x).to_number(),
+                        self.value(t, i, y).to_number(),
+                    );
+                    let from = (self.sprites[i].x, self.sprites[i].y);
+                    self.top(t).wait = Some(Wait::Glide {
+                        start: now,
+                        secs,
+                        from,
+                        to,
+                    });
+                }
+                if let Some(Wait::Glide {
+                    start,
+                    secs,
+                    from,
+                    to,
+                }) = self.top(t).wait
+                {
+                    let fraction = self.secs_since(start) / secs;
+                    let passed = self.has_passed(start, secs);
+                    let sprite = &mut self.sprites[i];
+                    // Gliding for no time at all jumps straight there
+                    if secs > 0.0 && !passed {
+                        sprite.x = from.0 + (to.0 - from.0) * fraction;
+                        sprite.y = from.1 + (to.1 - from.1) * fraction;
+                        return Flow::Yield;
+                    }
+                    sprite.x = to.0;
+                    sprite.y = to.1;
+                }
+            }
+            Statement::ChangeXBy(dx) => {
+                let dx = self.value(t, i, dx).to_number();
+                self.sprites[i].x += dx;
+            }
+            Statement::SetX(x) => {
+                let x = self.value(t, i, x).to_number();
+                self.sprites[i].x = x;
+            }
+            Statement::ChangeYBy(dy) => {
+                let dy = self.value(t, i, dy).to_number();
+                self.sprites[i].y += dy;
+            }
+            Statement::SetY(y) => {
+                let y = self.value(t, i, y).to_number();
+                self.sprites[i].y = y;
+            }
+            Statement::BounceOffEdge => {
+                // Without costume bounds the sprite bounces as a point
+                let sprite = &mut self.sprites[i];
+                if sprite.x.abs() > 240.0 {
+                    sprite.x = sprite.x.clamp(-240.0, 240.0);
+                    sprite.direction = wrap_direction(-sprite.direction);
+                }
+                if sprite.y.abs() > 180.0 {
+                    sprite.y = sprite.y.clamp(-180.0, 180.0);
+                    sprite.direction = wrap_direction(180.0 - sprite.direction);
+                }
+            }
+            Statement::SetRotationStyle(style) => self.sprites[i].rotation_style = style.clone(),
+
+            Statement::Say { message, secs } | Statement::Think { message, secs } => {
+                let secs = match secs {
+                    Some(secs) => secs,
+                    None => {
+                        let message = self.value(t, i, message).to_string();
+                        self.say(i, Some(message));
+                        return Flow::Next;
+                    }
+                };
+                if self.top(t).wait.is_none() {
+                    let message = self.value(t, i, message).to_string();
+                    self.say(i, Some(message));
+                }
+                let flow = self.wait(t, |vm| vm.value(t, i, secs).to_number());
+                if let Flow::Next = flow {
+                    self.say(i, None);
+                }
+                return flow;
+            }
+            Statement::Show => self.sprites[i].visible = true,
+            Statement::Hide => self.sprites[i].visible = false,
+            Statement::SwitchCostume(costume) => {
+                let costume = self.value(t, i, costume);
+                self.set_costume(i, &costume);
+            }
+            Statement::NextCostume => {
+                let costume = Value::from(self.sprites[i].costume as f64 + 2.0);
+                self.set_costume(i, &costume);
+            }
+            Statement::SwitchBackdrop { backdrop, wait } => {
+                let backdrop = |vm: &mut Self| {
+                    let backdrop = vm.value(t, i, backdrop);
+                    vm.set_backdrop(&backdrop)
+                };
+                if !wait {
+                    backdrop(self);
+                    return Flow::Next;
+                }
+                return self.wait_for(t, backdrop);
+            }
+            Statement::NextBackdrop => {
+                let backdrop = Value::from(self.sprites[0].costume as f64 + 2.0);
+                self.set_backdrop(&backdrop);
+            }
+            Statement::ChangeEffectBy { effect, change } => {
+                let change = self.value(t, i, change).to_number();
+                let effect = effect.to_lowercase();
+                let value = self.sprites[i].effects.get(&effect).copied().unwrap_or(0.0);
+                self.set_effect(i, effect, value + change);
+            }
+            Statement::SetEffectTo { effect, value } => {
+                let value = self.value(t, i, value).to_number();
+                self.set_effect(i, effect.to_lowercase(), value);
+            }
+            Statement::ClearEffects => self.sprites[i].effects.clear(),
+            Statement::ChangeSizeBy(change) => {
+                let change = self.value(t, i, change).to_number();
+                let sprite = &mut self.sprites[i];
+                sprite.size = (sprite.size + change).max(0.0);
+            }
+            Statement::SetSizeTo(size) => {
+                let size = self.value(t, i, size).to_number();
+                self.sprites[i].size = size.max(0.0);
+            }
+
+            // There's no audio, so sounds finish straight away
+            Statement::PlaySound { .. } | Statement::StopAllSounds => {}
+            Statement::ChangeVolumeBy(change) => {
+                let change = self.value(t, i, change).to_number();
+                let sprite = &mut self.sprites[i];
+                sprite.volume = (sprite.volume + change).clamp(0.0, 100.0);
+            }
+            Statement::SetVolumeTo(volume) => {
+                let volume = self.value(t, i, volume).to_number();
+                self.sprites[i].volume = volume.clamp(0.0, 100.0);
+            }
+            Statement::PlayNote { beats, .. } | Statement::Rest(beats) => {
+                return self.wait(t, |vm| vm.value(t, i, beats).to_number() * 60.0 / TEMPO);
+            }
+
+            Statement::Broadcast { message, wait } => {
+                let broadcast = |vm: &mut Self| {
+                    let message = vm.value(t, i, message).to_string();
+                    vm.start_broadcast(&message)
+                };
+                if !wait {
+                    broadcast(self);
+                    return Flow::Next;
+                }
+                return self.wait_for(t, broadcast);
+            }
+
+            Statement::Wait(secs) => return self.wait(t, |vm| vm.value(t, i, secs).to_number()),
+            Statement::Repeat { times, .. } => {
+                let count = match self.top(t).count {
+                    Some(count) => count,
+                    None => js_round(self.value(t, i, times).to_number()).max(0.0) as u64,
+                };
+                if count == 0 {
+                    return Flow::Next;
+                }
+                self.top(t).count = Some(count - 1);
+                return Flow::Enter(self.branch(t, 0, true));
+            }
+            Statement::Forever(_) => return Flow::Enter(self.branch(t, 0, true)),
+            Statement::If { condition, .. } => {
+                let index = if self.value(t, i, condition).to_bool() {
+                    0
+                } else {
+                    1
+                };
+                return Flow::Enter(self.branch(t, index, false));
+            }
+            Statement::WaitUntil(condition) => {
+                if !self.value(t, i, condition).to_bool() {
+                    return Flow::Yield;
+                }
+            }
+            Statement::RepeatUntil { condition, .. } => {
+                if !self.value(t, i, condition).to_bool() {
+                    return Flow::Enter(self.branch(t, 0, true));
+                }
+            }
+            Statement::Stop(option) => match option.as_str() {
+                "all" => {
+                    self.stop_all();
+                    return Flow::End;
+                }
+                "other scripts in sprite" | "other scripts in stage" => {
+                    let (id, sprite) = (self.threads[t].id, self.threads[t].sprite);
+                    for thread in self.threads.iter_mut() {
+                        if thread.sprite == sprite && thread.id != id {
+                            thread.done = true;
+                        }
+                    }
+                }
+                _ => return Flow::Return,
+            },
+            Statement::CreateClone(of) => {
+                let of = self.value(t, i, of).to_string();
+                let parent = if of == "_myself_" {
+                    Some(i)
+                } else {
+                    self.sprite_named(&of)
+                };
+                let clones = self.sprites.iter().filter(|s| s.is_clone).count();
+                if let Some(parent) = parent.filter(|&p| p != 0 && clones < MAX_CLONES) {
+                    let mut clone = self.sprites[parent].clone();
+                    clone.id = self.next_id();
+                    clone.is_clone = true;
+                    clone.bubble = None;
+                    let id = clone.id;
+                    self.sprites.push(clone);
+                    self.start_hats(Some(id), false, |hat| *hat == Hat::CloneStart);
+                }
+            }
+            Statement::DeleteClone => {
+                if self.sprites[i].is_clone {
+                    let id = self.sprites.remove(i).id;
+                    for thread in self.threads.iter_mut().filter(|thread| thread.sprite == id) {
+                        thread.done = true;
+                    }
+                    return Flow::End;
+                }
+            }
+
+            // There's no one to answer
+            Statement::Ask(_) => self.answer.clear(),
+            Statement::ResetTimer => self.timer_start = self.time(),
+
+            Statement::SetVariable { variable, value } => {
+                let value = self.value(t, i, value);
+                *self.variable_mut(i, variable) = value;
+            }
+            Statement::ChangeVariable { variable, value } => {
+                let change = self.value(t, i, value).to_number();
+                let variable = self.variable_mut(i, variable);
+                *variable = Value::from(variable.to_number() + change);
+            }
+            Statement::AddToList { list, item } => {
+                let item = self.value(t, i, item);
+                self.list_mut(i, list).add(item);
+            }
+            Statement::DeleteOfList { list, index } => {
+                let index = self.value(t, i, index);
+                let random = self.random();
+                self.list_mut(i, list).delete(&index, random);
+            }
+            Statement::DeleteAllOfList(list) => self.list_mut(i, list).delete_all(),
+            Statement::InsertAtList { list, index, item } => {
+                let index = self.value(t, i, index);
+                let item = self.value(t, i, item);
+                let random = self.random();
+                self.list_mut(i, list).insert(&index, item, random);
+            }
+            Statement::ReplaceItemOfList { list, index, item } => {
+                let index = self.value(t, i, index);
+                let item = self.value(t, i, item);
+                let random = self.random();
+                self.list_mut(i, list).replace(&index, item, random);
+            }
+
+            Statement::Call { proccode, args } => {
+                let program = Rc::clone(&self.program);
+                let index = self.sprites[i].target;
+                let procedures = &target(&program, index).procedures;
+                let procedure = match procedures.iter().position(|p| p.proccode == *proccode) {
+                    Some(procedure) => procedure,
+                    None => return Flow::Next,
+                };
+                let definition = &procedures[procedure];
+
+                let warp = definition.warp || self.top(t).warp;
+                let mut frame = Frame::new(
+                    Body::Procedure {
+                        target: index,
+                        procedure,
+                    },
+                    warp,
+                );
+                for (n, parameter) in definition.parameters.iter().enumerate() {
+                    let value = match args.get(n) {
+                        Some(arg) => self.value(t, i, arg),
+                        None => Value::from(parameter.default.as_str()),
+                    };
+                    frame.args.push((parameter.name.clone(), value));
+                }
+
+                let recursive = self.threads[t].frames.iter().any(|f| f.body == frame.body);
+                return if recursive && !warp {
+                    Flow::EnterLater(frame)
+                } else {
+                    Flow::Enter(frame)
+                };
+            }
+
+            Statement::Other { .. } => {}
+        }
+        Flow::Next
+    }
+
+    /// Where a sprite is by name, or the mouse by `_mouse_`
+    pub(super) fn position_of(&self, name: &str) -> Option<(f64, f64)> {
+        if name == "_mouse_" {
+            return Some(self.mouse);
+        }
+        self.sprite_named(name)
+            .filter(|&i| i != 0)
+            .map(|i| (self.sprites[i].x, self.sprites[i].y))
+    }
+
+    /// Switches costume like `switch costume to`: by name first, then the next and previous options, then by number
+    fn set_costume(&mut self, i: usize, costume: &Value) {
+        let program = Rc::clone(&self.program);
+        let costumes = &target(&program, self.sprites[i].target).costumes;
+        let (len, current) = (costumes.len(), self.sprites[i].costume);
+        if len == 0 {
+            return;
+        }
+
+        let index = match costume {
+            Value::Number(n) => Some(wrap_costume(*n, len)),
+            costume => {
+                let name = costume.to_string();
+                let is_stage = self.sprites[i].is_stage;
+                match costumes.iter().position(|c| c.name == name) {
+                    Some(index) => Some(index),
+                    None => match name.as_str() {
+                        "next costume" if !is_stage => Some((current + 1) % len),
+                        "previous costume" if !is_stage => Some((current + len - 1) % len),
+                        "next backdrop" if is_stage => Some((current + 1) % len),
+                        "previous backdrop" if is_stage => Some((current + len - 1) % len),
+                        "random backdrop" if is_stage && len > 1 => {
+                            let index = (self.random() * (len - 1) as f64) as usize;
+                            Some(if index >= current { index + 1 } else { index })
+                        }
+                        _ => costume.numeric().map(|n| wrap_costume(n, len)),
+                    },
+                }
+            }
+        };
+        if let Some(index) = index {
+            self.sprites[i].costume = index;
+        }
+    }
+
+    /// Switches the stage's backdrop and starts the scripts waiting for it, giving their threads
+    fn set_backdrop(&mut self, backdrop: &Value) -> Vec<u64> {
+        self.set_costume(0, backdrop);
+        let program = Rc::clone(&self.program);
+        let name = program
+            .stage
+            .costumes
+            .get(self.sprites[0].costume)
+            .map(|c| c.name.to_lowercase())
+            .unwrap_or_default();
+        self.start_hats(None, false, |hat| match hat {
+            Hat::BackdropSwitchesTo(backdrop) => backdrop.to_lowercase() == name,
+            _ => false,
+        })
+    }
+
+    fn set_effect(&mut self, i: usize, effect: String, value: f64) {
+        let value = match effect.as_str() {
+            "ghost" => value.clamp(0.0, 100.0),
+            "brightness" => value.clamp(-100.0, 100.0),
+            _ => value,
+        };
+        let effects = &mut self.sprites[i].effects;
+        if value == 0.0 {
+            effects.remove(&effect);
+        } else {
+            effects.insert(effect, value);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use super::{
+    target,
+    Value,
+};
+use crate::ir::{
+    Program,
+    Statement,
+};
+
+/// Where a frame's statements come from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Body {
+    /// A script of the target at this index in `Program::targets`
+    Script {
+        target: usize,
+        script: usize,
+    },
+    Procedure {
+        target: usize,
+        procedure: usize,
+    },
+    /// The first or, for if-else, second branch of the statement the frame below is on
+    Branch(usize),
+}
+
+/// A point on the VM's clock. A virtual clock counts whole frames, so waits on it never pick up rounding error.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Moment {
+    Secs(f64),
+    Frame(u64),
+}
+
+/// What the statement a frame is on is waiting for
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Wait {
+    /// Until `secs` seconds have passed since `start`
+    Secs { start: Moment, secs: f64 },
+    /// Until these threads finish
+    Threads(Vec<u64>),
+    Glide {
+        start: Moment,
+        secs: f64,
+        from: (f64, f64),
+        to: (f64, f64),
+    },
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Frame {
+    pub body: Body,
+    pub pc: usize,
+    /// Loop bodies give the other threads a turn at the end of each pass, unless in warp mode
+    pub is_loop: bool,
+    pub warp: bool,
+    /// The arguments of a custom block, on the frame running its body
+    pub args: Vec<(String, Value)>,
+    /// Passes left for the `repeat` the frame is on
+    pub count: Option<u64>,
+    pub wait: Option<Wait>,
+}
+
+impl Frame {
+    pub fn new(body: Body, warp: bool) -> Self {
+        Frame {
+            body,
+            pc: 0,
+            is_loop: false,
+            warp,
+            args: Vec::new(),
+            count: None,
+            wait: None,
+        }
+    }
+
+    /// Moves on to the next statement, dropping what the last one kept between frames
+    pub fn next(&mut self) {
+        self.pc += 1;
+        self.count = None;
+        self.wait = None;
+    }
+}
+
+/// A running script
+#[derive(Debug, Clone)]
+pub(super) struct Thread {
+    pub id: u64,
+    /// The `SpriteState::id` of the sprite or clone running it
+    pub sprite: u64,
+    /// The target and script it was started from, so a hat can restart it instead of starting it twice
+    pub script: (usize, usize),
+    /// Never empty while the thread is running
+    pub frames: Vec<Frame>,
+    pub done: bool,
+    /// Set when a hat restarts the thread during its own turn, which ends the turn
+    pub restarted: bool,
+}
+
+impl Thread {
+    pub fn new(id: u64, sprite: u64, target: usize, script: usize) -> Self {
+        Thread {
+            id,
+            sprite,
+            script: (target, script),
+            frames: vec![Frame::new(Body::Script { target, script }, false)],
+            done: false,
+            restarted: false,
+        }
+    }
+
+    pub fn restart(&mut self) {
+        let (target, script) = self.script;
+        self.frames = vec![Frame::new(Body::Script { target, script }, false)];
+        self.restarted = true;
+    }
+
+    /// The arguments of the innermost custom block being run
+    pub fn args(&self) -> &[(String, Value)] {
+        self.frames
+            .iter()
+            .rev()
+            .find(|f| matches!(f.body, Body::Procedure { .. }))
+            .map(|f| f.args.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+/// The branch of a C-block a `Body::Branch` runs
+fn branch(statement: &Statement, index: usize) -> &[Statement] {
+    match (statement, index) {
+        (Statement::Repeat { body, .. }, 0)
+        | (Statement::Forever(body), 0)
+        | (Statement::RepeatUntil { body, .. }, 0)
+        | (Statement::If { then: body, .. }, 0)
+        | (
+            Statement::If {
+                otherwise: body, ..
+            },
+            1,
+        ) => body,
+        _ => &[],
+    }
+}
+
+/// The statements the top frame runs, found by following the branches down from the script or custom block below it
+pub(super) fn body<'p>(program: &'p Program, frames: &[Frame]) -> &'p [Statement] {
+    let root = frames
+        .iter()
+        .rposition(|f| !matches!(f.body, Body::Branch(_)))
+        .expect("Threads start with a script");
+
+    let mut body: &[Statement] = match frames[root].body {
+        Body::Script { target: t, script } => &target(program, t).scripts[script].body,
+        Body::Procedure {
+            target: t,
+            procedure,
+        } => &target(program, t).procedures[procedure].body,
+        Body::Branch(_) => unreachable!(),
+    };
+    for pair in frames[root..].windows(2) {
+        if let Body::Branch(index) = pair[1].body {
+            body = branch(&body[pair[0].pc], index);
+        }
+    }
+    body
+}
@@ -0,0 +1,137 @@
+use scratch::{
+    archive,
+    client::Client,
+    ProjectData,
+};
+use std::io::{
+    Cursor,
+    Write,
+};
+use zip::{
+    write::FileOptions,
+    ZipWriter,
+};
+
+const PROJECT_2: &str = include_str!("fixtures/project2.json");
+const PROJECT_3: &str = include_str!("fixtures/project3.json");
+
+fn make_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data) in files {
+        writer.start_file(*name, FileOptions::default()).unwrap();
+        writer.write_all(data).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[test]
+pub fn read_sb3() {
+    let sb3 = make_zip(&[
+        ("project.json", PROJECT_3.as_bytes()),
+        ("bcf454acf82e4504149f7ffe07081dbc.svg", b"<svg></svg>"),
+        ("83c36d806dc92327b9e7049a565c6bff.wav", b"RIFF"),
+    ]);
+
+    let project = archive::read_sb3(Cursor::new(sb3)).unwrap();
+    let data = match &project.data {
+        ProjectData::Scratch3(data) => data,
+        ProjectData::Scratch2(_) => panic!("Expected a Scratch 3 project"),
+    };
+    assert_eq!(data.targets.len(), 2);
+    assert_eq!(data.targets[1].name, "Sprite1");
+
+    assert_eq!(project.assets.len(), 2);
+    assert_eq!(
+        project.assets["bcf454acf82e4504149f7ffe07081dbc.svg"],
+        b"<svg></svg>"
+    );
+}
+
+#[test]
+pub fn read_sb3_without_project_json() {
+    let sb3 = make_zip(&[("bcf454acf82e4504149f7ffe07081dbc.svg", b"<svg></svg>")]);
+    assert!(archive::read_sb3(Cursor::new(sb3)).is_err());
+}
+
+#[test]
+pub fn read_sb2() {
+    let sb2 = make_zip(&[
+        ("project.json", PROJECT_2.as_bytes()),
+        ("0.svg", b"<svg></svg>"),
+        ("1.png", b"PNG"),
+        ("0.wav", b"meow"),
+        ("1.wav", b"pop"),
+    ]);
+
+    let project = archive::read_sb2(Cursor::new(sb2)).unwrap();
+    let data = project.data.as_ref2().expect("Scratch 2 Project");
+    assert_eq!(data.children[0].name, "Sprite1");
+
+    assert_eq!(project.assets.len(), 4);
+    assert_eq!(
+        project.assets["09dc888b0b7df19f70d81588ae73420e.svg"],
+        b"<svg></svg>"
+    );
+    assert_eq!(
+        project.assets["739b5e2a2435f6e1ec2993791b423146.png"],
+        b"PNG"
+    );
+    assert_eq!(
+        project.assets["83c36d806dc92327b9e7049a565c6bff.wav"],
+        b"meow"
+    );
+    assert_eq!(
+        project.assets["83a9787d4cb6f3b7632b4ddfebf74367.wav"],
+        b"pop"
+    );
+}
+
+#[test]
+pub fn read_sb2_other_extensions() {
+    // The sprite's costume is stored as 0.png rather than the 0.svg its md5 names, next to the sound 0.wav
+    let sb2 = make_zip(&[
+        ("project.json", PROJECT_2.as_bytes()),
+        ("0.wav", b"meow"),
+        ("0.png", b"PNG"),
+        ("1.wav", b"pop"),
+    ]);
+
+    let project = archive::read_sb2(Cursor::new(sb2)).unwrap();
+    assert_eq!(project.assets.len(), 3);
+    assert_eq!(
+        project.assets["09dc888b0b7df19f70d81588ae73420e.svg"],
+        b"PNG"
+    );
+    assert_eq!(
+        project.assets["83c36d806dc92327b9e7049a565c6bff.wav"],
+        b"meow"
+    );
+    assert!(!project
+        .assets
+        .contains_key("739b5e2a2435f6e1ec2993791b423146.png"));
+}
+
+#[test]
+pub fn write_sb3_round_trip() {
+    let sb3 = make_zip(&[
+        ("project.json", PROJECT_3.as_bytes()),
+        ("cd21514d0531fdffb22204e0ec5ed84a.svg", b"<svg>stage</svg>"),
+        ("bcf454acf82e4504149f7ffe07081dbc.svg", b"<svg></svg>"),
+        ("83c36d806dc92327b9e7049a565c6bff.wav", b"RIFF"),
+    ]);
+    let project = archive::read_sb3(Cursor::new(sb3)).unwrap();
+
+    let mut client = Client::new();
+    let written = archive::write_sb3(&project, &mut client, Cursor::new(Vec::new()))
+        .unwrap()
+        .into_inner();
+
+    let mut zip = zip::ZipArchive::new(Cursor::new(written.clone())).unwrap();
+    let json: serde_json::Value =
+        serde_json::from_reader(zip.by_name("project.json").unwrap()).unwrap();
+    assert_eq!(json["targets"][0]["tempo"], 60);
+    assert_eq!(json["meta"]["semver"], "3.0.0");
+
+    let reloaded = archive::read_sb3(Cursor::new(written)).unwrap();
+    assert_eq!(reloaded.assets, project.assets);
+}
@@ -0,0 +1,125 @@
+use scratch::types::{
+    Arg,
+    Block,
+    ProjectJson,
+};
+use serde_json::{
+    json,
+    Value,
+};
+
+const PROJECT_2: &str = include_str!("fixtures/project2.json");
+
+fn parse(value: Value) -> Block {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+pub fn parse_nested_blocks() {
+    let block = parse(json!([
+        "doIfElse",
+        ["<", ["xpos"], 10],
+        [["forward:", ["*", ["readVariable", "speed"], 2]]],
+        null
+    ]));
+
+    let (condition, body, else_body) = match &block {
+        Block::DoIfElse {
+            condition,
+            body,
+            else_body,
+        } => (condition, body, else_body),
+        other => panic!("Expected doIfElse, got {:?}", other),
+    };
+    assert_eq!(else_body, &None);
+
+    match condition.as_block() {
+        Some(Block::LessThan { operand1, operand2 }) => {
+            assert_eq!(operand1.as_block(), Some(&Block::XPosition));
+            assert_eq!(operand2.as_f64(), Some(10.0));
+        }
+        other => panic!("Expected <, got {:?}", other),
+    }
+
+    let body = body.as_ref().unwrap();
+    match &body[0] {
+        Block::MoveSteps { steps } => match steps.as_block() {
+            Some(Block::Multiply { num1, .. }) => assert_eq!(
+                num1,
+                &Arg::Block(Box::new(Block::ReadVariable {
+                    variable: Arg::Value(json!("speed"))
+                }))
+            ),
+            other => panic!("Expected *, got {:?}", other),
+        },
+        other => panic!("Expected forward:, got {:?}", other),
+    }
+}
+
+#[test]
+pub fn parse_procedures() {
+    let definition = parse(json!(["procDef", "jump %n", ["height"], [1], false]));
+    assert_eq!(
+        definition,
+        Block::ProcDef {
+            proccode: "jump %n".into(),
+            names: vec!["height".into()],
+            defaults: vec![json!(1)],
+            warp: false,
+        }
+    );
+
+    let call = parse(json!([
+        "call",
+        "jump %n %b",
+        ["getParam", "height", "r"],
+        true
+    ]));
+    match call {
+        Block::Call { proccode, args } => {
+            assert_eq!(proccode, "jump %n %b");
+            assert_eq!(args.len(), 2);
+            assert_eq!(
+                args[0].as_block(),
+                Some(&Block::GetParam {
+                    name: "height".into(),
+                    kind: "r".into()
+                })
+            );
+        }
+        other => panic!("Expected call, got {:?}", other),
+    }
+}
+
+#[test]
+pub fn unknown_blocks_are_kept() {
+    let extension = json!(["LEGO WeDo\u{1f}motorOn"]);
+    assert_eq!(
+        parse(extension.clone()),
+        Block::Unknown(vec![json!("LEGO WeDo\u{1f}motorOn")])
+    );
+
+    // A known selector with the wrong arguments is left alone rather than guessed at
+    let malformed = json!(["forward:", 10, 20]);
+    assert!(matches!(parse(malformed.clone()), Block::Unknown(_)));
+    assert_eq!(
+        serde_json::to_value(parse(malformed.clone())).unwrap(),
+        malformed
+    );
+}
+
+#[test]
+pub fn blocks_round_trip() {
+    let original: Value = serde_json::from_str(PROJECT_2).unwrap();
+    let project: ProjectJson = serde_json::from_value(original.clone()).unwrap();
+
+    let scripts = project.children[0].scripts.as_ref().unwrap();
+    assert_eq!(scripts[0].blocks[0], Block::WhenStart);
+    assert!(matches!(scripts[0].blocks[1], Block::DoRepeat { .. }));
+
+    let json = serde_json::to_value(&project).unwrap();
+    assert_eq!(
+        json["children"][0]["scripts"][0][2],
+        original["children"][0]["scripts"][0][2]
+    );
+}
@@ -0,0 +1,81 @@
+mod util;
+
+use scratch::cache::{
+    self,
+    AssetCache,
+    CachedAsset,
+};
+use std::path::PathBuf;
+use util::TempDir;
+
+const SVG: &[u8] = b"<svg></svg>";
+const SVG_MD5EXT: &str = "7b56e1eab00ec8000da9331a4888cb35.svg";
+
+fn entry(cache: &AssetCache, md5ext: &str) -> PathBuf {
+    cache.path().join(md5ext)
+}
+
+#[test]
+pub fn hashes_are_checked() {
+    assert!(cache::is_valid(SVG_MD5EXT, SVG));
+    assert!(cache::is_valid(&SVG_MD5EXT.to_uppercase(), SVG));
+    assert!(!cache::is_valid(SVG_MD5EXT, b"<svg/>"));
+    assert!(!cache::is_valid("", SVG));
+}
+
+#[test]
+pub fn insert_and_get() {
+    let dir = TempDir::new("cache-insert");
+    let cache = AssetCache::new(dir.to_path_buf());
+    assert_eq!(cache.get(SVG_MD5EXT).unwrap(), None);
+    assert!(cache.list().unwrap().is_empty());
+
+    cache.insert(SVG_MD5EXT, SVG).unwrap();
+    assert!(cache.contains(SVG_MD5EXT));
+    assert_eq!(cache.get(SVG_MD5EXT).unwrap().as_deref(), Some(SVG));
+    assert_eq!(
+        cache.list().unwrap(),
+        vec![CachedAsset {
+            md5ext: SVG_MD5EXT.into(),
+            size: SVG.len() as u64,
+        }]
+    );
+
+    assert!(cache.insert(SVG_MD5EXT, b"<svg/>").is_err());
+    assert!(cache.insert("../escape.svg", SVG).is_err());
+    assert_eq!(cache.get("../escape.svg").unwrap(), None);
+}
+
+#[test]
+pub fn damaged_entries_are_dropped() {
+    let dir = TempDir::new("cache-damaged");
+    let cache = AssetCache::new(dir.to_path_buf());
+    cache.insert(SVG_MD5EXT, SVG).unwrap();
+    std::fs::write(entry(&cache, SVG_MD5EXT), b"<svg/>").unwrap();
+
+    assert_eq!(cache.verify().unwrap(), vec![SVG_MD5EXT.to_string()]);
+    assert_eq!(cache.get(SVG_MD5EXT).unwrap(), None);
+    assert!(!cache.contains(SVG_MD5EXT));
+    assert!(cache.verify().unwrap().is_empty());
+}
+
+#[test]
+pub fn prune() {
+    let dir = TempDir::new("cache-prune");
+    let cache = AssetCache::new(dir.to_path_buf());
+    let wav = b"RIFF";
+    let wav_md5ext = format!("{:x}.wav", md5::compute(wav));
+    cache.insert(SVG_MD5EXT, SVG).unwrap();
+    cache.insert(&wav_md5ext, wav).unwrap();
+    let damaged = "0123456789abcdef0123456789abcdef.png";
+    std::fs::write(entry(&cache, damaged), b"not a png").unwrap();
+    std::fs::write(entry(&cache, ".left.over.tmp"), b"").unwrap();
+
+    assert_eq!(cache.prune(|_| true).unwrap(), vec![damaged.to_string()]);
+    assert_eq!(cache.list().unwrap().len(), 2);
+    assert!(!entry(&cache, ".left.over.tmp").exists());
+
+    let removed = cache.prune(|md5ext| md5ext == SVG_MD5EXT).unwrap();
+    assert_eq!(removed, vec![wav_md5ext]);
+    assert!(cache.contains(SVG_MD5EXT));
+}
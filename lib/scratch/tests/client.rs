@@ -0,0 +1,305 @@
+mod util;
+
+use scratch::{
+    cache::AssetCache,
+    client::{
+        Client,
+        ClientConfig,
+        DirTransport,
+        Endpoints,
+        MemoryTransport,
+    },
+    scratch3::NetworkProject as NetworkProject3,
+    NetworkProject,
+    ScratchError,
+};
+use std::{
+    collections::HashMap,
+    io::{
+        BufRead,
+        BufReader,
+        Write,
+    },
+    net::TcpListener,
+    path::PathBuf,
+    sync::{
+        Arc,
+        Mutex,
+    },
+    thread,
+};
+use util::TempDir;
+
+const PROJECT_2: &str = include_str!("fixtures/project2.json");
+const PROJECT_3: &str = include_str!("fixtures/project3.json");
+
+/// A stand-in for the Scratch servers that answers fixed paths and records every request
+struct Server {
+    base: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+/// The path of a request, and its headers with lowercased names
+struct Request {
+    path: String,
+    headers: HashMap<String, String>,
+}
+
+impl Server {
+    fn start(routes: &[(&str, &str)]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let routes: HashMap<String, String> = routes
+            .iter()
+            .map(|(path, body)| (path.to_string(), body.to_string()))
+            .collect();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let log = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line
+                    .split_whitespace()
+                    .nth(1)
+                    .unwrap_or("")
+                    .to_string();
+                let mut headers = HashMap::new();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    if let Some((name, value)) = line.split_once(':') {
+                        headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+                    }
+                    line.clear();
+                }
+                log.lock().unwrap().push(Request {
+                    path: path.clone(),
+                    headers,
+                });
+
+                let (status, body) = match routes.get(&path) {
+                    Some(body) => ("200 OK", body.as_str()),
+                    None => ("404 Not Found", ""),
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+
+        Server { base, requests }
+    }
+
+    fn client(&self) -> Client {
+        Client::with_endpoints(Endpoints::from_base(&self.base))
+    }
+
+    fn requests(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| request.path.clone())
+            .collect()
+    }
+
+    /// The value of a header on every request so far
+    fn header(&self, name: &str) -> Vec<Option<String>> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| request.headers.get(name).cloned())
+            .collect()
+    }
+}
+
+fn info_json(id: u64, token: &str) -> String {
+    serde_json::json!({
+        "id": id,
+        "title": "Test Project",
+        "description": "",
+        "instructions": "",
+        "visibility": "visible",
+        "public": true,
+        "comments_allowed": true,
+        "is_published": true,
+        "image": "",
+        "project_token": token,
+    })
+    .to_string()
+}
+
+#[test]
+pub fn get_data_3_uses_project_token() {
+    let info = info_json(42, "abc123");
+    let server = Server::start(&[
+        ("/projects/42/", info.as_str()),
+        ("/42?token=abc123", PROJECT_3),
+    ]);
+    let mut client = server.client();
+
+    let project = NetworkProject3::new("42".into());
+    let info = client.get_stats_3(&project).unwrap();
+    assert_eq!(info.project_token.as_deref(), Some("abc123"));
+
+    let data = client.get_data_3(&project).unwrap();
+    assert_eq!(data.targets[1].name, "Sprite1");
+    assert_eq!(
+        server.requests(),
+        vec!["/projects/42/", "/projects/42/", "/42?token=abc123"]
+    );
+}
+
+#[test]
+pub fn get_project_detects_scratch_2() {
+    let info = info_json(7, "xyz");
+    let server = Server::start(&[("/projects/7/", info.as_str()), ("/7?token=xyz", PROJECT_2)]);
+    let mut client = server.client();
+
+    let data = client
+        .get_project(&NetworkProject::new("7".into()))
+        .unwrap();
+    assert!(data.as_ref2().is_some());
+}
+
+#[test]
+pub fn missing_project_is_not_found() {
+    let server = Server::start(&[]);
+    let mut client = server.client();
+
+    let result = client.get_project(&NetworkProject::new("1".into()));
+    assert!(result.is_err());
+    assert_eq!(server.requests(), vec!["/projects/1/"]);
+    assert!(matches!(
+        client.get_data_3(&NetworkProject3::new("1".into())),
+        Err(ScratchError::Status(404))
+    ));
+}
+
+#[test]
+pub fn get_asset_from_mirror() {
+    let server = Server::start(&[("/internalapi/asset/abc.svg/get", "<svg></svg>")]);
+    let mut client = server.client();
+    assert_eq!(client.get_asset("abc.svg").unwrap(), b"<svg></svg>");
+}
+
+#[test]
+pub fn get_asset_fills_cache() {
+    let dir = TempDir::new("client-cache");
+    let cache = AssetCache::new(dir.to_path_buf());
+
+    let md5ext = "7b56e1eab00ec8000da9331a4888cb35.svg";
+    let url = format!("/internalapi/asset/{}/get", md5ext);
+    let server = Server::start(&[(url.as_str(), "<svg></svg>")]);
+    let mut client = server.client().with_cache(cache.clone());
+    assert_eq!(client.get_asset(md5ext).unwrap(), b"<svg></svg>");
+    assert!(cache.contains(md5ext));
+
+    let offline = Server::start(&[]);
+    let mut client = offline.client().with_cache(cache);
+    assert_eq!(client.get_asset(md5ext).unwrap(), b"<svg></svg>");
+    assert!(offline.requests().is_empty());
+}
+
+#[test]
+pub fn mismatched_asset_is_not_cached() {
+    let dir = TempDir::new("client-mismatch");
+    let cache = AssetCache::new(dir.to_path_buf());
+
+    let md5ext = "0123456789abcdef0123456789abcdef.svg";
+    let url = format!("/internalapi/asset/{}/get", md5ext);
+    let server = Server::start(&[(url.as_str(), "<svg></svg>")]);
+    let mut client = server.client().with_cache(cache.clone());
+    assert_eq!(client.get_asset(md5ext).unwrap(), b"<svg></svg>");
+    assert!(!cache.contains(md5ext));
+}
+
+#[test]
+pub fn broken_cache_still_downloads() {
+    // A file where the cache directory should be, so it can be neither read nor written
+    let dir = TempDir::new("client-broken");
+    let path = dir.join("cache");
+    std::fs::write(&path, "").unwrap();
+    let cache = AssetCache::new(path);
+
+    let md5ext = "7b56e1eab00ec8000da9331a4888cb35.svg";
+    let url = format!("/internalapi/asset/{}/get", md5ext);
+    let server = Server::start(&[(url.as_str(), "<svg></svg>")]);
+    let mut client = server.client().with_cache(cache);
+    assert_eq!(client.get_asset(md5ext).unwrap(), b"<svg></svg>");
+}
+
+#[test]
+pub fn memory_transport_records_requests() {
+    let transport = Arc::new(
+        MemoryTransport::new()
+            .with("/projects/42/", info_json(42, "abc123"))
+            .with("/42?token=abc123", PROJECT_3),
+    );
+    let mut client = Client::with_transport(transport.clone(), Endpoints::default());
+
+    let data = client
+        .get_data_3(&NetworkProject3::new("42".into()))
+        .unwrap();
+    assert_eq!(data.targets[1].name, "Sprite1");
+    assert_eq!(
+        transport.requests(),
+        vec!["/projects/42/", "/42?token=abc123"]
+    );
+    assert!(matches!(
+        client.get_asset("missing.svg"),
+        Err(ScratchError::Status(404))
+    ));
+}
+
+#[test]
+pub fn dir_transport_ignores_host_and_query() {
+    let transport = DirTransport::new(PathBuf::from("tests/fixtures"));
+    let mut client = Client::with_transport(transport, Endpoints::default());
+
+    let data = client
+        .get_url("https://projects.scratch.mit.edu/project3.json?token=abc")
+        .unwrap();
+    assert_eq!(data, PROJECT_3.as_bytes());
+    assert!(matches!(
+        client.get_url("https://projects.scratch.mit.edu/missing.json"),
+        Err(ScratchError::Status(404))
+    ));
+    assert!(matches!(
+        client.get_url("/../Cargo.toml"),
+        Err(ScratchError::Status(404))
+    ));
+}
+
+#[test]
+pub fn config_sets_user_agent() {
+    let config = ClientConfig {
+        user_agent: "bad\nagent".into(),
+        ..ClientConfig::default()
+    };
+    assert!(Client::with_config(config).is_err());
+
+    let server = Server::start(&[("/internalapi/asset/abc.svg/get", "<svg></svg>")]);
+    let config = ClientConfig {
+        endpoints: Endpoints::from_base(&server.base),
+        user_agent: "test-agent/1.0".into(),
+        timeout: None,
+        ..ClientConfig::default()
+    };
+    let mut client = Client::with_config(config).unwrap();
+    assert_eq!(client.get_asset("abc.svg").unwrap(), b"<svg></svg>");
+    assert_eq!(
+        server.header("user-agent"),
+        vec![Some("test-agent/1.0".to_string())]
+    );
+}
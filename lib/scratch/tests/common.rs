@@ -1,31 +1,78 @@
+mod util;
+
 use scratch::{
-    client::Client,
+    client::{
+        Client,
+        Endpoints,
+        MemoryTransport,
+    },
     scratch3::NetworkProject,
     target::PyGameTarget,
     Project,
 };
-use std::path::PathBuf;
+use util::TempDir;
+
+const ASSETS: &[&str] = &[
+    "83c36d806dc92327b9e7049a565c6bff.wav",
+    "bcf454acf82e4504149f7ffe07081dbc.svg",
+    "cd21514d0531fdffb22204e0ec5ed84a.svg",
+];
+
+/// Serves project 309320008 from the fixtures, with stand-ins for its assets
+fn fixture_client() -> Client {
+    let mut transport = MemoryTransport::new()
+        .with("/projects/309320008/", include_str!("fixtures/info3.json"))
+        .with(
+            "/309320008?token=abc",
+            include_str!("fixtures/project3.json"),
+        );
+    for asset in ASSETS {
+        transport.insert(&format!("/internalapi/asset/{}/get", asset), *asset);
+    }
+    Client::with_transport(transport, Endpoints::default())
+}
 
 #[test]
 pub fn common() {
-    let path = PathBuf::from("projects/scratch_309320008");
+    let client = fixture_client();
+    let data = scratch::ProjectData::from_slice(include_bytes!("fixtures/project3.json")).unwrap();
+    let mut project: Project = data.into();
+    project.name = Some("scratch_309320008".into());
+    let dir = TempDir::new("common");
+    project
+        .save_with_client(&client, dir.to_path_buf(), scratch::SaveOptions::new())
+        .unwrap();
+
+    let path = dir.join("scratch_309320008");
     let project: Project = Project::load(path.clone()).unwrap();
     let mut target = PyGameTarget::new();
     project.build(&mut target).unwrap();
-    project.run(&mut target).unwrap();
+
+    let build = path.join("target").join("python-pygame");
+    assert!(build.join("index.py").is_file());
+    for asset in ASSETS {
+        assert!(build.join("assets").join(asset).is_file());
+    }
 }
 
 #[test]
 pub fn save_scratch_3() {
-    let path = PathBuf::from("projects");
+    let dir = TempDir::new("save");
+
     let code = "309320008";
-    let mut client = Client::new();
+    let mut client = fixture_client();
     let net_project = NetworkProject::new(String::from(code));
     let project_data = client.get_data_3(&net_project).unwrap();
     //dbg!(project);
     let mut project: Project = project_data.into();
     project.name = Some(format!("scratch_{}", code));
     project
-        .save(path.clone(), scratch::SaveOptions::new())
+        .save_with_client(&client, dir.to_path_buf(), scratch::SaveOptions::new())
         .unwrap();
+    let path = dir.join("scratch_309320008");
+    assert!(path.join("data").join("project.json").is_file());
+    for asset in ASSETS {
+        let saved = std::fs::read(path.join("data").join("assets").join(asset)).unwrap();
+        assert_eq!(saved, asset.as_bytes());
+    }
 }
@@ -0,0 +1,211 @@
+use scratch::{
+    scratch3::convert::convert,
+    types::ProjectJson as ProjectJson2,
+};
+use serde_json::{
+    json,
+    Value,
+};
+
+const PROJECT_2: &str = include_str!("fixtures/project2.json");
+
+fn find_opcode<'a>(blocks: &'a Value, opcode: &str) -> (&'a String, &'a Value) {
+    blocks
+        .as_object()
+        .unwrap()
+        .iter()
+        .find(|(_, block)| block["opcode"] == opcode)
+        .unwrap_or_else(|| panic!("No {} block", opcode))
+}
+
+#[test]
+pub fn convert_project() {
+    let project: ProjectJson2 = serde_json::from_str(PROJECT_2).unwrap();
+    let conversion = convert(&project);
+    assert!(conversion.unsupported.is_empty());
+
+    let json = serde_json::to_value(&conversion.project).unwrap();
+    assert_eq!(json["extensions"], json!(["music"]));
+
+    let stage = &json["targets"][0];
+    assert_eq!(stage["isStage"], true);
+    assert_eq!(
+        stage["sounds"][0]["md5ext"],
+        "83a9787d4cb6f3b7632b4ddfebf74367.wav"
+    );
+    assert_eq!(stage["sounds"][0]["rate"], 11025);
+    assert_eq!(stage["costumes"][0]["dataFormat"], "png");
+    assert_eq!(stage["costumes"][0]["rotationCenterX"], 240.0);
+    assert_eq!(stage["videoTransparency"], 50.0);
+
+    let sprite = &json["targets"][1];
+    assert_eq!(sprite["name"], "Sprite1");
+    assert_eq!(sprite["size"], 100.0);
+    assert_eq!(sprite["rotationStyle"], "all around");
+    assert_eq!(
+        sprite["costumes"][0]["assetId"],
+        "09dc888b0b7df19f70d81588ae73420e"
+    );
+    assert_eq!(sprite["costumes"][0]["bitmapResolution"], 1.0);
+
+    let blocks = &sprite["blocks"];
+    let (hat_id, hat) = find_opcode(blocks, "event_whenflagclicked");
+    assert_eq!(hat["topLevel"], true);
+    assert_eq!(hat["x"], 48.0);
+
+    let repeat_id = hat["next"].as_str().unwrap();
+    let repeat = &blocks[repeat_id];
+    assert_eq!(repeat["opcode"], "control_repeat");
+    assert_eq!(repeat["parent"], json!(hat_id));
+    assert_eq!(repeat["inputs"]["TIMES"], json!([1, [6, "10"]]));
+
+    let note = &blocks[repeat["inputs"]["SUBSTACK"][1].as_str().unwrap()];
+    assert_eq!(note["opcode"], "music_playNoteForBeats");
+    assert_eq!(note["parent"], json!(repeat_id));
+    assert_eq!(note["inputs"]["BEATS"], json!([1, [4, "0.5"]]));
+    let menu = &blocks[note["inputs"]["NOTE"][1].as_str().unwrap()];
+    assert_eq!(menu["opcode"], "note");
+    assert_eq!(menu["shadow"], true);
+    assert_eq!(menu["fields"]["NOTE"], json!(["60", null]));
+
+    let sound = &blocks[repeat["next"].as_str().unwrap()];
+    assert_eq!(sound["opcode"], "sound_playuntildone");
+    assert_eq!(sound["next"], Value::Null);
+}
+
+#[test]
+pub fn convert_data_and_procedures() {
+    let mut project: Value = serde_json::from_str(PROJECT_2).unwrap();
+    project["variables"] = json!([{ "name": "score", "value": 0, "isPersistent": false }]);
+    project["children"][0]["scripts"] = json!([
+        [
+            0,
+            0,
+            [
+                ["whenIReceive", "go"],
+                [
+                    "changeVar:by:",
+                    "score",
+                    ["+", ["readVariable", "score"], 1]
+                ],
+                ["call", "jump %n %b", 10, ["mousePressed"]],
+                ["senseVideoMotion", "motion"]
+            ]
+        ],
+        [0, 200, [["whenSensorGreaterThan", "loudness", 10]]],
+        [
+            0,
+            100,
+            [
+                [
+                    "procDef",
+                    "jump %n %b",
+                    ["height", "fast"],
+                    [1, false],
+                    true
+                ],
+                ["changeYposBy:", ["getParam", "height", "r"]],
+                ["broadcast:", "go"]
+            ]
+        ]
+    ]);
+    let project: ProjectJson2 = serde_json::from_value(project).unwrap();
+    let conversion = convert(&project);
+
+    assert_eq!(conversion.unsupported.len(), 1);
+    assert_eq!(conversion.unsupported[0].target, "Sprite1");
+    assert_eq!(conversion.unsupported[0].selector, "senseVideoMotion");
+
+    let json = serde_json::to_value(&conversion.project).unwrap();
+    let stage = &json["targets"][0];
+    let (variable_id, variable) = stage["variables"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .next()
+        .unwrap();
+    assert_eq!(variable, &json!(["score", 0]));
+    let (broadcast_id, broadcast) = stage["broadcasts"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .next()
+        .unwrap();
+    assert_eq!(broadcast, "go");
+
+    let blocks = &json["targets"][1]["blocks"];
+    let (_, receive) = find_opcode(blocks, "event_whenbroadcastreceived");
+    assert_eq!(
+        receive["fields"]["BROADCAST_OPTION"],
+        json!(["go", broadcast_id])
+    );
+
+    let change = &blocks[receive["next"].as_str().unwrap()];
+    assert_eq!(change["fields"]["VARIABLE"], json!(["score", variable_id]));
+    let add = &blocks[change["inputs"]["VALUE"][1].as_str().unwrap()];
+    assert_eq!(add["opcode"], "operator_add");
+    assert_eq!(
+        add["inputs"]["NUM1"],
+        json!([3, [12, "score", variable_id], [4, ""]])
+    );
+
+    let call = &blocks[change["next"].as_str().unwrap()];
+    assert_eq!(call["opcode"], "procedures_call");
+    assert_eq!(call["next"], Value::Null);
+    assert_eq!(call["mutation"]["proccode"], "jump %s %b");
+    assert_eq!(call["mutation"]["warp"], "true");
+
+    let (_, prototype) = find_opcode(blocks, "procedures_prototype");
+    assert_eq!(prototype["mutation"]["warp"], "true");
+    assert_eq!(prototype["mutation"]["proccode"], "jump %s %b");
+    assert_eq!(
+        prototype["mutation"]["argumentnames"],
+        "[\"height\",\"fast\"]"
+    );
+    assert_eq!(
+        prototype["mutation"]["argumentids"],
+        call["mutation"]["argumentids"]
+    );
+
+    let (_, sensor) = find_opcode(blocks, "event_whengreaterthan");
+    assert_eq!(
+        sensor["fields"]["WHENGREATERTHANMENU"],
+        json!(["LOUDNESS", null])
+    );
+
+    let (_, definition) = find_opcode(blocks, "procedures_definition");
+    assert_eq!(definition["topLevel"], true);
+    let change_y = &blocks[definition["next"].as_str().unwrap()];
+    let param = &blocks[change_y["inputs"]["DY"][1].as_str().unwrap()];
+    assert_eq!(param["opcode"], "argument_reporter_string_number");
+    assert_eq!(param["fields"]["VALUE"], json!(["height", null]));
+}
+
+#[test]
+pub fn convert_drums() {
+    let mut project: Value = serde_json::from_str(PROJECT_2).unwrap();
+    project["children"][0]["scripts"] = json!([[
+        0,
+        0,
+        [
+            ["whenGreenFlag"],
+            ["drum:duration:elapsed:from:", 3, 0.25],
+            ["playDrum", 40, 0.5]
+        ]
+    ]]);
+    let project: ProjectJson2 = serde_json::from_value(project).unwrap();
+    let conversion = convert(&project);
+    assert!(conversion.unsupported.is_empty());
+
+    let json = serde_json::to_value(&conversion.project).unwrap();
+    let blocks = &json["targets"][1]["blocks"];
+    let (_, drum) = find_opcode(blocks, "music_playDrumForBeats");
+    let menu = &blocks[drum["inputs"]["DRUM"][1].as_str().unwrap()];
+    assert_eq!(menu["opcode"], "music_menu_DRUM");
+    assert_eq!(menu["fields"]["DRUM"], json!(["3", null]));
+
+    let midi = &blocks[drum["next"].as_str().unwrap()];
+    assert_eq!(midi["opcode"], "music_midiPlayDrumForBeats");
+    assert_eq!(midi["inputs"]["DRUM"], json!([1, [4, "40"]]));
+    assert_eq!(midi["inputs"]["BEATS"], json!([1, [4, "0.5"]]));
+}
@@ -0,0 +1,172 @@
+mod util;
+
+use scratch::{
+    coverage::Coverage,
+    ir::{
+        self,
+        Lowered,
+    },
+    scratch3::ProjectJson as ProjectJson3,
+    target::{
+        PyGameTarget,
+        Target,
+    },
+    types::ProjectJson as ProjectJson2,
+    vm,
+    Project,
+    ProjectData,
+    SaveOptions,
+};
+use serde_json::{
+    json,
+    Value,
+};
+use util::TempDir;
+
+const PROJECT_2: &str = include_str!("fixtures/project2.json");
+const PROJECT_3: &str = include_str!("fixtures/project3.json");
+
+/// Statements and reporters to try the targets with, supported or not
+const OPCODES: &[&str] = &[
+    "motion_movesteps",
+    "motion_turnright",
+    "motion_turnleft",
+    "motion_goto",
+    "motion_gotoxy",
+    "motion_changexby",
+    "motion_ifonedgebounce",
+    "motion_xposition",
+    "motion_yposition",
+    "motion_direction",
+    "looks_say",
+    "looks_show",
+    "looks_switchcostumeto",
+    "looks_size",
+    "sound_play",
+    "sound_playuntildone",
+    "sound_changevolumeby",
+    "sound_volume",
+    "event_broadcast",
+    "control_wait",
+    "control_repeat",
+    "control_if",
+    "control_stop",
+    "sensing_timer",
+    "operator_add",
+    "operator_subtract",
+    "operator_multiply",
+    "operator_divide",
+    "operator_mod",
+    "operator_join",
+    "data_setvariableto",
+    "procedures_call",
+    "argument_reporter_string_number",
+    "argument_reporter_boolean",
+    "music_playNoteForBeats",
+    // Last, since nothing can follow it
+    "control_forever",
+];
+
+fn block(opcode: &str, parent: Option<&str>) -> Value {
+    json!({
+        "opcode": opcode,
+        "parent": parent,
+        "next": null,
+        "inputs": {},
+        "fields": {},
+        "shadow": false,
+        "topLevel": parent.is_none(),
+    })
+}
+
+#[test]
+pub fn count_and_cross_reference() {
+    let project: ProjectJson2 = serde_json::from_str(PROJECT_2).unwrap();
+    let coverage = Coverage::new(&ProjectData::Scratch2(project))
+        .target::<PyGameTarget>()
+        .target_with("none", |_| false);
+
+    assert_eq!(coverage.targets, vec!["python-pygame", "none"]);
+    assert_eq!(coverage.sprites[0].name, "Stage");
+
+    let sprite = &coverage.sprites[1];
+    let opcodes = sprite
+        .opcodes
+        .iter()
+        .map(|o| (o.opcode.as_str(), o.count))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        opcodes,
+        vec![
+            ("control_repeat", 1),
+            ("event_whenflagclicked", 1),
+            ("music_playNoteForBeats", 1),
+            ("sound_playuntildone", 1),
+        ]
+    );
+
+    assert_eq!(sprite.blocks(), 4);
+    assert_eq!(sprite.supported_blocks("python-pygame"), 3);
+    assert_eq!(sprite.supported_blocks("none"), 0);
+}
+
+#[test]
+pub fn supported_opcodes_build() {
+    // One script running every supported statement, with each supported reporter in a turn block
+    let mut blocks = serde_json::Map::new();
+    blocks.insert("flag".into(), block("event_whenflagclicked", None));
+    let mut last = String::from("flag");
+    for (i, opcode) in OPCODES.iter().enumerate() {
+        if !PyGameTarget::supports(opcode) {
+            continue;
+        }
+        let id = format!("block{}", i);
+        let statement = match ir::lower_opcode(opcode) {
+            Some(Lowered::Statement(_)) => block(opcode, Some(&last)),
+            Some(Lowered::Reporter(_)) => {
+                let reporter = format!("reporter{}", i);
+                blocks.insert(reporter.clone(), block(opcode, Some(&id)));
+                let mut turn = block("motion_turnright", Some(&last));
+                turn["inputs"] = json!({ "DEGREES": [3, reporter, [4, "15"]] });
+                turn
+            }
+            lowered => panic!("{} lowers to {:?}", opcode, lowered),
+        };
+        blocks[&last]["next"] = json!(id);
+        blocks.insert(id.clone(), statement);
+        last = id;
+    }
+
+    let mut data: Value = serde_json::from_str(PROJECT_3).unwrap();
+    data["targets"][0]["blocks"] = json!({});
+    data["targets"][1]["blocks"] = Value::Object(blocks);
+    let data: ProjectJson3 = serde_json::from_value(data).unwrap();
+    let mut project: Project = data.into();
+    project.name = Some("supported".into());
+    let names = project
+        .data
+        .asset_names()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    for name in names {
+        project.assets.insert(name.clone(), name.into_bytes());
+    }
+    let dir = TempDir::new("coverage-build");
+    project.save(dir.to_path_buf(), SaveOptions::new()).unwrap();
+
+    let path = dir.join("supported");
+    let project = Project::load(path.clone()).unwrap();
+    project.build(&mut PyGameTarget::new()).unwrap();
+    let index = std::fs::read_to_string(path.join("target").join("python-pygame").join("index.py"))
+        .unwrap();
+    assert!(!index.contains("NOT IMPLEMENTED"), "{}", index);
+    assert!(index.contains("while True:"));
+    assert!(!PyGameTarget::supports("motion_goto"));
+    assert!(!PyGameTarget::supports("operator_mod"));
+
+    assert!(vm::supports("procedures_definition"));
+    assert!(vm::supports("data_listcontents"));
+    assert!(!vm::supports("sound_play"));
+    assert!(!vm::supports("pen_clear"));
+}
@@ -0,0 +1,92 @@
+use scratch::{
+    ir::{
+        DataRef,
+        Program,
+        Scope,
+    },
+    scratch3::{
+        ListJson,
+        VariableJson,
+    },
+    types::ProjectJson as ProjectJson2,
+    ProjectData,
+};
+use serde_json::json;
+
+const PROJECT_2: &str = include_str!("fixtures/project2.json");
+
+#[test]
+pub fn parse_declarations() {
+    let variable: VariableJson = serde_json::from_value(json!(["score", 10])).unwrap();
+    assert_eq!(variable, VariableJson::new("score", json!(10)));
+
+    let cloud: VariableJson = serde_json::from_value(json!(["☁ high", "5", true])).unwrap();
+    assert!(cloud.is_cloud);
+    assert_eq!(
+        serde_json::to_value(&cloud).unwrap(),
+        json!(["☁ high", "5", true])
+    );
+
+    let list: ListJson = serde_json::from_value(json!(["items", ["a", 1]])).unwrap();
+    assert_eq!(list, ListJson::new("items", vec![json!("a"), json!(1)]));
+    assert_eq!(
+        serde_json::to_value(&list).unwrap(),
+        json!(["items", ["a", 1]])
+    );
+}
+
+#[test]
+pub fn scratch2_declarations() {
+    let mut project: serde_json::Value = serde_json::from_str(PROJECT_2).unwrap();
+    project["variables"] = json!([
+        { "name": "score", "value": 3, "isPersistent": false },
+        { "name": "☁ high", "value": 7, "isPersistent": true },
+    ]);
+    project["children"][0]["variables"] = json!([{ "name": "score", "value": "local" }]);
+    project["children"][0]["lists"] = json!([{
+        "listName": "items",
+        "contents": ["a", "b"],
+        "isPersistent": false,
+        "x": 5,
+        "y": 5,
+        "width": 100,
+        "height": 200,
+        "visible": true
+    }]);
+    let project: ProjectJson2 = serde_json::from_value(project).unwrap();
+    assert_eq!(project.variables.len(), 2);
+    assert_eq!(project.children[0].lists[0].contents.len(), 2);
+
+    let program = Program::from(&ProjectData::Scratch2(project));
+    let high = &program.stage.variables[1];
+    assert_eq!(high.name, "☁ high");
+    assert!(high.is_cloud);
+    assert_eq!(high.scope, Scope::Stage);
+
+    let sprite = &program.sprites[0];
+    let items = &sprite.lists[0];
+    assert_eq!(items.items, vec![json!("a"), json!("b")]);
+    assert_eq!(items.scope, Scope::Sprite);
+
+    // The sprite's own variable shadows the stage's variable of the same name
+    let by_name = DataRef {
+        id: String::new(),
+        name: "score".into(),
+    };
+    let score = program.variable(sprite, &by_name).unwrap();
+    assert_eq!(score.value, json!("local"));
+    assert_eq!(score.scope, Scope::Sprite);
+
+    let global = program.variable(&program.stage, &by_name).unwrap();
+    assert_eq!(global.value, json!(3));
+    assert!(program
+        .list(&program.stage, &items_ref(&items.id))
+        .is_none());
+}
+
+fn items_ref(id: &str) -> DataRef {
+    DataRef {
+        id: id.into(),
+        name: "items".into(),
+    }
+}
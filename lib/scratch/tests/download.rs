@@ -0,0 +1,133 @@
+use scratch::{
+    client::{
+        Client,
+        Endpoints,
+        MemoryTransport,
+    },
+    download::{
+        download,
+        DownloadOptions,
+    },
+    scratch3::ProjectJson,
+    Project,
+    ScratchError,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+        Arc,
+        Mutex,
+    },
+    time::Duration,
+};
+
+fn quick() -> DownloadOptions {
+    DownloadOptions::new().backoff(Duration::from_millis(1))
+}
+
+#[test]
+pub fn retries_transient_errors() {
+    let attempts = Mutex::new(HashMap::new());
+    let fetch = |name: &str| {
+        let mut attempts = attempts.lock().unwrap();
+        let count = attempts.entry(name.to_string()).or_insert(0);
+        *count += 1;
+        match name {
+            "flaky.svg" if *count < 3 => Err(ScratchError::Network),
+            "bad.svg" => Err(ScratchError::Custom("Not An Asset".into())),
+            "down.svg" => Err(ScratchError::Status(503)),
+            "gone.svg" => Err(ScratchError::Status(404)),
+            _ => Ok(name.as_bytes().to_vec()),
+        }
+    };
+
+    let names = [
+        "ok.svg",
+        "flaky.svg",
+        "bad.svg",
+        "down.svg",
+        "gone.svg",
+        "ok.svg",
+    ];
+    let downloads = download(&names, &quick().retries(2), fetch, |_| {});
+
+    let attempts = attempts.into_inner().unwrap();
+    assert_eq!(attempts["ok.svg"], 1);
+    assert_eq!(attempts["flaky.svg"], 3);
+    assert_eq!(attempts["bad.svg"], 1);
+    assert_eq!(attempts["down.svg"], 3);
+    assert_eq!(attempts["gone.svg"], 1);
+
+    assert_eq!(downloads.assets.len(), 2);
+    assert_eq!(downloads.assets["flaky.svg"], b"flaky.svg");
+    let failed = downloads
+        .failed
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(failed, vec!["bad.svg", "down.svg", "gone.svg"]);
+    assert!(!downloads.is_complete());
+
+    let error = format!("{:?}", downloads.into_result().unwrap_err());
+    assert!(error.contains("Failed to download 3 asset(s)"));
+    assert!(error.contains("gone.svg"));
+}
+
+#[test]
+pub fn bounded_jobs_and_progress() {
+    let running = AtomicUsize::new(0);
+    let peak = AtomicUsize::new(0);
+    let fetch = |name: &str| {
+        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+        peak.fetch_max(now, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(5));
+        running.fetch_sub(1, Ordering::SeqCst);
+        Ok(name.as_bytes().to_vec())
+    };
+
+    let names = (0..20).map(|i| format!("{}.wav", i)).collect::<Vec<_>>();
+    let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+    let reports = Mutex::new(Vec::new());
+    let downloads = download(&names, &quick().jobs(3), fetch, |p| {
+        assert_eq!(p.total, 20);
+        assert!(p.error.is_none());
+        reports.lock().unwrap().push(p.done);
+    });
+
+    assert!(downloads.is_complete());
+    assert_eq!(downloads.assets.len(), 20);
+    assert!(peak.load(Ordering::SeqCst) <= 3);
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort_unstable();
+    assert_eq!(reports, (1..=20).collect::<Vec<_>>());
+}
+
+#[test]
+pub fn bundled_assets_are_not_downloaded() {
+    let transport = Arc::new(
+        MemoryTransport::new().with("/internalapi/asset/remote.svg/get", "<svg>remote</svg>"),
+    );
+    let client = Client::with_transport(transport.clone(), Endpoints::default());
+    let data: ProjectJson = serde_json::from_str(include_str!("fixtures/project3.json")).unwrap();
+    let mut project: Project = data.into();
+    project
+        .assets
+        .insert("local.svg".into(), b"<svg>local</svg>".to_vec());
+
+    let downloads = project
+        .load_assets(&client, &["local.svg", "remote.svg"], &quick(), |p| {
+            assert_eq!(p.name, "remote.svg");
+        })
+        .into_result()
+        .unwrap();
+    assert_eq!(downloads["local.svg"], b"<svg>local</svg>");
+    assert_eq!(downloads["remote.svg"], b"<svg>remote</svg>");
+    assert_eq!(
+        transport.requests(),
+        vec!["/internalapi/asset/remote.svg/get"]
+    );
+}
@@ -0,0 +1,150 @@
+use scratch::scratch3::{
+    Field,
+    Input,
+    InputValue,
+    Mutation,
+    Primitive,
+    ProjectJson,
+    TargetBlock,
+};
+use serde_json::json;
+
+const PROJECT_3: &str = include_str!("fixtures/project3.json");
+
+fn input(value: serde_json::Value) -> Input {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+pub fn parse_inputs() {
+    let literal = input(json!([1, [4, 15]]));
+    assert_eq!(
+        literal,
+        Input::Shadow(Some(Primitive::Number("15".into()).into()))
+    );
+    assert_eq!(literal.primitive().and_then(|p| p.literal()), Some("15"));
+
+    let substack = input(json!([2, "a"]));
+    assert_eq!(substack.block(), Some("a"));
+    assert_eq!(substack.shadow(), None);
+
+    let obscured = input(json!([3, [12, "score", "id"], [10, ""]]));
+    assert_eq!(
+        obscured.primitive(),
+        Some(&Primitive::Variable {
+            name: "score".into(),
+            id: "id".into()
+        })
+    );
+    assert_eq!(
+        obscured.shadow(),
+        Some(&InputValue::Primitive(Primitive::Text("".into())))
+    );
+
+    let broadcast = input(json!([1, [11, "go", "b1"]]));
+    assert_eq!(
+        serde_json::to_value(&broadcast).unwrap(),
+        json!([1, [11, "go", "b1"]])
+    );
+    assert_eq!(
+        serde_json::to_value(input(json!([2, null]))).unwrap(),
+        json!([2, null])
+    );
+
+    assert!(serde_json::from_value::<Input>(json!([1, [99, "x"]])).is_err());
+    assert!(serde_json::from_value::<Input>(json!(["1"])).is_err());
+}
+
+#[test]
+pub fn parse_fields() {
+    let field: Field = serde_json::from_value(json!(["score", "id"])).unwrap();
+    assert_eq!(field, Field::with_id("score", "id"));
+
+    let field: Field = serde_json::from_value(json!([60, null])).unwrap();
+    assert_eq!(field, Field::new("60"));
+    assert_eq!(serde_json::to_value(&field).unwrap(), json!(["60", null]));
+}
+
+#[test]
+pub fn parse_mutations() {
+    let value = json!({
+        "tagName": "mutation",
+        "children": [],
+        "proccode": "jump %n %b",
+        "argumentids": "[\"a\",\"b\"]",
+        "argumentnames": "[\"height\",\"fast\"]",
+        "argumentdefaults": "[1,\"false\"]",
+        "warp": "true"
+    });
+    let mutation: Mutation = serde_json::from_value(value.clone()).unwrap();
+    assert_eq!(mutation.proccode(), "jump %n %b");
+    assert_eq!(mutation.argument_ids(), ["a", "b"]);
+    assert_eq!(mutation.argument_names(), ["height", "fast"]);
+    assert_eq!(mutation.argument_defaults(), ["1", "false"]);
+    assert!(mutation.warp());
+    assert_eq!(mutation.has_next, None);
+
+    let mut expected = value;
+    expected["argumentdefaults"] = json!("[\"1\",\"false\"]");
+    assert_eq!(serde_json::to_value(&mutation).unwrap(), expected);
+
+    let stop: Mutation = serde_json::from_value(json!({
+        "tagName": "mutation",
+        "children": [],
+        "hasnext": "false"
+    }))
+    .unwrap();
+    assert_eq!(stop.has_next, Some(false));
+    assert_eq!(stop.proccode, None);
+}
+
+#[test]
+pub fn blocks_round_trip() {
+    let project: ProjectJson = serde_json::from_str(PROJECT_3).unwrap();
+    let turn = project.targets[1]
+        .blocks
+        .values()
+        .filter_map(TargetBlock::block)
+        .find(|b| b.opcode == "motion_turnright")
+        .unwrap();
+    assert_eq!(
+        turn.inputs["DEGREES"].primitive(),
+        Some(&Primitive::Number("15".into()))
+    );
+
+    let json = serde_json::to_value(&project).unwrap();
+    let original: serde_json::Value = serde_json::from_str(PROJECT_3).unwrap();
+    assert_eq!(
+        json["targets"][1]["blocks"]["b"],
+        original["targets"][1]["blocks"]["b"]
+    );
+}
+
+#[test]
+pub fn top_level_reporters() {
+    let project: ProjectJson = serde_json::from_str(PROJECT_3).unwrap();
+    let reporter = match &project.targets[0].blocks["r"] {
+        TargetBlock::Primitive(reporter) => reporter,
+        TargetBlock::Block(_) => panic!("Expected a top-level primitive"),
+    };
+    assert_eq!(
+        reporter.primitive,
+        Primitive::Variable {
+            name: "my variable".into(),
+            id: "`jEk@4|i[#Fk?(8x)AV.-my variable".into()
+        }
+    );
+    assert_eq!((reporter.x, reporter.y), (48.0, 64.0));
+
+    let json = serde_json::to_value(&project).unwrap();
+    assert_eq!(
+        json["targets"][0]["blocks"]["r"],
+        json!([
+            12,
+            "my variable",
+            "`jEk@4|i[#Fk?(8x)AV.-my variable",
+            48.0,
+            64.0
+        ])
+    );
+}
@@ -0,0 +1,226 @@
+use scratch::{
+    ir::{
+        BinaryOp,
+        DataRef,
+        Expr,
+        Hat,
+        Parameter,
+        Program,
+        Statement,
+    },
+    scratch3::ProjectJson as ProjectJson3,
+    types::ProjectJson as ProjectJson2,
+    ProjectData,
+};
+use serde_json::json;
+
+const PROJECT_2: &str = include_str!("fixtures/project2.json");
+const PROJECT_3: &str = include_str!("fixtures/project3.json");
+
+fn literal(text: &str) -> Expr {
+    Expr::Literal(text.into())
+}
+
+#[test]
+pub fn lower_scratch2() {
+    let project: ProjectJson2 = serde_json::from_str(PROJECT_2).unwrap();
+    let program = Program::from(&ProjectData::Scratch2(project));
+
+    assert!(program.stage.is_stage);
+    assert_eq!(program.sprites.len(), 1);
+
+    let sprite = &program.sprites[0];
+    assert_eq!(sprite.name, "Sprite1");
+    assert_eq!(sprite.scripts.len(), 1);
+    assert_eq!(sprite.scripts[0].hat, Hat::GreenFlag);
+    assert_eq!(
+        sprite.scripts[0].body,
+        vec![
+            Statement::Repeat {
+                times: literal("10"),
+                body: vec![Statement::PlayNote {
+                    note: literal("60"),
+                    beats: literal("0.5"),
+                }],
+            },
+            Statement::PlaySound {
+                sound: literal("meow"),
+                wait: true,
+            },
+        ]
+    );
+}
+
+#[test]
+pub fn lower_scratch3() {
+    let project: ProjectJson3 = serde_json::from_str(PROJECT_3).unwrap();
+    let program = Program::from(&ProjectData::Scratch3(project));
+
+    assert_eq!(program.stage.variables.len(), 1);
+    assert_eq!(program.stage.variables[0].name, "my variable");
+
+    let sprite = &program.sprites[0];
+    assert_eq!(sprite.scripts.len(), 1);
+    assert_eq!(sprite.scripts[0].hat, Hat::GreenFlag);
+    assert!(matches!(sprite.scripts[0].body[0], Statement::TurnRight(_)));
+}
+
+#[test]
+pub fn lower_expressions_and_procedures() {
+    let project: ProjectJson3 = serde_json::from_value(json!({
+        "targets": [{
+            "isStage": false,
+            "name": "Sprite1",
+            "variables": { "var": ["score", 0] },
+            "lists": { "list": ["items", ["a", "b"]] },
+            "broadcasts": {},
+            "blocks": {
+                "def": {
+                    "opcode": "procedures_definition",
+                    "next": "set",
+                    "parent": null,
+                    "inputs": { "custom_block": [1, "proto"] },
+                    "fields": {},
+                    "shadow": false,
+                    "topLevel": true,
+                    "x": 0,
+                    "y": 0
+                },
+                "proto": {
+                    "opcode": "procedures_prototype",
+                    "next": null,
+                    "parent": "def",
+                    "inputs": {},
+                    "fields": {},
+                    "shadow": true,
+                    "topLevel": false,
+                    "mutation": {
+                        "tagName": "mutation",
+                        "children": [],
+                        "proccode": "add %s",
+                        "argumentids": "[\"arg\"]",
+                        "argumentnames": "[\"amount\"]",
+                        "argumentdefaults": "[\"\"]",
+                        "warp": "true"
+                    }
+                },
+                "set": {
+                    "opcode": "data_setvariableto",
+                    "next": null,
+                    "parent": "def",
+                    "inputs": { "VALUE": [3, "add", [10, ""]] },
+                    "fields": { "VARIABLE": ["score", "var"] },
+                    "shadow": false,
+                    "topLevel": false
+                },
+                "add": {
+                    "opcode": "operator_add",
+                    "next": null,
+                    "parent": "set",
+                    "inputs": {
+                        "NUM1": [3, [12, "score", "var"], [4, ""]],
+                        "NUM2": [3, "param", [4, ""]]
+                    },
+                    "fields": {},
+                    "shadow": false,
+                    "topLevel": false
+                },
+                "param": {
+                    "opcode": "argument_reporter_string_number",
+                    "next": null,
+                    "parent": "add",
+                    "inputs": {},
+                    "fields": { "VALUE": ["amount", null] },
+                    "shadow": false,
+                    "topLevel": false
+                }
+            },
+            "comments": {},
+            "currentCostume": 0,
+            "costumes": [],
+            "sounds": [],
+            "volume": 100,
+            "layerOrder": 1,
+            "visible": true,
+            "x": 0,
+            "y": 0,
+            "size": 100,
+            "direction": 90,
+            "draggable": false,
+            "rotationStyle": "all around"
+        }],
+        "monitors": [],
+        "extensions": [],
+        "meta": { "semver": "3.0.0" }
+    }))
+    .unwrap();
+    let program = Program::from(&ProjectData::Scratch3(project));
+
+    let sprite = &program.sprites[0];
+    assert_eq!(sprite.lists[0].items, vec![json!("a"), json!("b")]);
+    assert!(sprite.scripts.is_empty());
+
+    let procedure = &sprite.procedures[0];
+    assert_eq!(procedure.proccode, "add %s");
+    assert_eq!(
+        procedure.parameters,
+        vec![Parameter {
+            id: "arg".into(),
+            name: "amount".into(),
+            default: String::new(),
+        }]
+    );
+    assert!(procedure.warp);
+
+    let score = DataRef {
+        id: "var".into(),
+        name: "score".into(),
+    };
+    assert_eq!(
+        procedure.body,
+        vec![Statement::SetVariable {
+            variable: score.clone(),
+            value: Expr::Binary {
+                op: BinaryOp::Add,
+                lhs: Box::new(Expr::Variable(score)),
+                rhs: Box::new(Expr::Argument("amount".into())),
+            },
+        }]
+    );
+}
+
+#[test]
+pub fn lower_scratch2_procedures() {
+    let mut project: serde_json::Value = serde_json::from_str(PROJECT_2).unwrap();
+    project["children"][0]["scripts"] = json!([
+        [0, 0, [["whenGreenFlag"], ["call", "jump %n", 10]]],
+        [
+            0,
+            100,
+            [
+                ["procDef", "jump %n", ["height"], [1], true],
+                ["changeYposBy:", ["getParam", "height", "r"]]
+            ]
+        ]
+    ]);
+    let project: ProjectJson2 = serde_json::from_value(project).unwrap();
+    let program = Program::from(&ProjectData::Scratch2(project));
+
+    let sprite = &program.sprites[0];
+    let procedure = sprite.procedure("jump %s").unwrap();
+    assert!(procedure.warp);
+    assert_eq!(procedure.parameters[0].name, "height");
+    assert_eq!(procedure.parameters[0].default, "1");
+    assert_eq!(
+        procedure.body,
+        vec![Statement::ChangeYBy(Expr::Argument("height".into()))]
+    );
+
+    assert_eq!(
+        sprite.scripts[0].body,
+        vec![Statement::Call {
+            proccode: "jump %s".into(),
+            args: vec![literal("10")],
+        }]
+    );
+}
@@ -0,0 +1,102 @@
+use scratch::{
+    scratch3::ListJson,
+    value::{
+        List,
+        Value,
+        LIST_ITEM_LIMIT,
+    },
+};
+use serde_json::json;
+
+fn list(items: serde_json::Value) -> List {
+    let list: ListJson = serde_json::from_value(json!(["list", items])).unwrap();
+    List::from(&list)
+}
+
+fn text(s: &str) -> Value {
+    Value::from(s)
+}
+
+#[test]
+pub fn indices() {
+    let list = list(json!(["a", "b", "c"]));
+    assert_eq!(list.item(&Value::Number(1.0), 0.0), text("a"));
+    assert_eq!(list.item(&text("2.9"), 0.0), text("b"));
+    assert_eq!(list.item(&text("last"), 0.0), text("c"));
+    assert_eq!(list.item(&text("random"), 0.5), text("b"));
+    assert_eq!(list.item(&text("any"), 0.99), text("c"));
+    assert_eq!(list.item(&Value::Number(0.0), 0.0), text(""));
+    assert_eq!(list.item(&Value::Number(4.0), 0.0), text(""));
+    assert_eq!(list.item(&text("all"), 0.0), text(""));
+    assert_eq!(list.item(&text("first"), 0.0), text(""));
+
+    let empty = List::new();
+    assert_eq!(empty.item(&text("last"), 0.0), text(""));
+    assert_eq!(empty.item(&text("random"), 0.0), text(""));
+}
+
+#[test]
+pub fn edits() {
+    let mut list = list(json!(["a", "b", "c"]));
+    list.delete(&Value::Number(5.0), 0.0);
+    list.replace(&Value::Number(0.0), text("x"), 0.0);
+    list.insert(&Value::Number(5.0), text("x"), 0.0);
+    assert_eq!(list.items(), &[text("a"), text("b"), text("c")]);
+
+    list.delete(&text("last"), 0.0);
+    list.insert(&Value::Number(3.0), text("d"), 0.0);
+    list.insert(&Value::Number(1.0), text("z"), 0.0);
+    list.replace(&Value::Number(2.0), Value::Number(1.0), 0.0);
+    list.delete(&text("random"), 0.0);
+    assert_eq!(list.items(), &[Value::Number(1.0), text("b"), text("d")]);
+
+    list.delete(&text("all"), 0.0);
+    assert!(list.is_empty());
+}
+
+#[test]
+pub fn item_limit() {
+    let mut list = List::from(vec![Value::Number(0.0); LIST_ITEM_LIMIT]);
+    list.add(text("over"));
+    assert_eq!(list.len(), LIST_ITEM_LIMIT);
+
+    list.insert(&Value::Number(1.0), text("first"), 0.0);
+    assert_eq!(list.len(), LIST_ITEM_LIMIT);
+    assert_eq!(list.item(&Value::Number(1.0), 0.0), text("first"));
+
+    list.insert(&text("last"), text("past the end"), 0.0);
+    assert_eq!(list.item(&text("last"), 0.0), Value::Number(0.0));
+}
+
+#[test]
+pub fn search_and_contents() {
+    let list = list(json!(["Apple", "10", 2]));
+    assert_eq!(list.item_num(&text("apple")), Value::Number(1.0));
+    assert_eq!(list.item_num(&Value::Number(10.0)), Value::Number(2.0));
+    assert_eq!(list.item_num(&text("2.0")), Value::Number(3.0));
+    assert_eq!(list.item_num(&text("pear")), Value::Number(0.0));
+    assert_eq!(list.contains(&text("APPLE")), Value::Bool(true));
+    assert_eq!(list.length(), Value::Number(3.0));
+    assert_eq!(list.contents(), text("Apple 10 2"));
+
+    assert_eq!(self::list(json!(["a", "b", "c"])).contents(), text("abc"));
+    assert_eq!(self::list(json!([1, 2, 3])).contents(), text("1 2 3"));
+    assert_eq!(self::list(json!(["a", "bc"])).contents(), text("a bc"));
+    assert_eq!(List::new().contents(), text(""));
+}
+
+#[test]
+pub fn save_lists() {
+    let mut list = list(json!(["a", 1.5, true]));
+    list.add(Value::Number(2.0));
+    list.add(Value::Number(f64::INFINITY));
+    list.add(text("b"));
+
+    let saved = serde_json::to_value(list.to_json("items")).unwrap();
+    assert_eq!(
+        saved,
+        json!(["items", ["a", 1.5, true, 2, "Infinity", "b"]])
+    );
+    let loaded: ListJson = serde_json::from_value(saved).unwrap();
+    assert_eq!(List::from(&loaded).contents(), list.contents());
+}
@@ -0,0 +1,106 @@
+mod util;
+
+use scratch::{
+    client::{
+        Client,
+        Endpoints,
+        MemoryTransport,
+    },
+    manifest::{
+        Format,
+        Manifest,
+        ProjectInfo,
+    },
+    target::PyGameTarget,
+    NetworkProject,
+    Project,
+    SaveOptions,
+};
+use util::TempDir;
+
+const ASSETS: &[&str] = &[
+    "83c36d806dc92327b9e7049a565c6bff.wav",
+    "bcf454acf82e4504149f7ffe07081dbc.svg",
+    "cd21514d0531fdffb22204e0ec5ed84a.svg",
+];
+
+#[test]
+pub fn round_trip() {
+    let mut manifest = Manifest::fetched("42", None);
+    manifest.name = "scratch_42".into();
+    manifest.format = Some(Format::Scratch2);
+    manifest.info = Some(ProjectInfo {
+        title: "Test".into(),
+        author: Some("someone".into()),
+        instructions: "Click \"go\"\nthen wait".into(),
+        thumbnail: None,
+    });
+    manifest
+        .target_settings_mut("python-pygame")
+        .insert("fps".into(), toml::Value::Integer(30));
+
+    let text = manifest.to_toml().unwrap();
+    assert!(text.contains("format = \"scratch2\""));
+    assert!(text.contains("[targets.python-pygame]"));
+    assert_eq!(Manifest::parse(&text).unwrap(), manifest);
+
+    assert!(Manifest::parse("id = 42").is_err());
+    assert_eq!(Manifest::parse("").unwrap(), Manifest::new());
+}
+
+#[test]
+pub fn saved_and_loaded() {
+    let transport = MemoryTransport::new()
+        .with("/projects/309320008/", include_str!("fixtures/info3.json"))
+        .with(
+            "/309320008?token=abc",
+            include_str!("fixtures/project3.json"),
+        );
+    let transport = ASSETS.iter().fold(transport, |transport, asset| {
+        transport.with(&format!("/internalapi/asset/{}/get", asset), *asset)
+    });
+    let mut client = Client::with_transport(transport, Endpoints::default());
+    let mut project = client
+        .get_full_project(&NetworkProject::new("309320008".into()))
+        .unwrap();
+    project.name = Some("saved".into());
+    project
+        .manifest
+        .target_settings_mut("python-pygame")
+        .insert("fullscreen".into(), toml::Value::Boolean(true));
+
+    let dir = TempDir::new("manifest-saved");
+    project
+        .save_with_client(&client, dir.to_path_buf(), SaveOptions::new())
+        .unwrap();
+
+    let loaded = Project::load(dir.join("saved")).unwrap();
+    assert_eq!(loaded.get_name(), "saved");
+    let manifest = &loaded.manifest;
+    assert_eq!(manifest.name, "saved");
+    assert_eq!(manifest.format, Some(Format::Scratch3));
+    assert_eq!(manifest.id.as_deref(), Some("309320008"));
+    assert!(manifest.fetched_at.is_some());
+
+    let info = manifest.info.as_ref().unwrap();
+    assert_eq!(info.title, "Test Project");
+    assert_eq!(info.author.as_deref(), Some("griffpatch"));
+    assert_eq!(info.instructions, "Press the green flag");
+    assert!(info.thumbnail.as_ref().unwrap().ends_with(".png"));
+
+    let settings = loaded.target_settings::<PyGameTarget>().unwrap();
+    assert_eq!(settings["fullscreen"].as_bool(), Some(true));
+}
+
+#[test]
+pub fn missing_manifest_uses_dir_name() {
+    let dir = TempDir::new("manifest-legacy");
+    let path = dir.join("old_project");
+    std::fs::create_dir_all(path.join("data")).unwrap();
+    let data = serde_json::json!({ "Scratch3": serde_json::from_str::<serde_json::Value>(include_str!("fixtures/project3.json")).unwrap() });
+    std::fs::write(path.join("data").join("project.json"), data.to_string()).unwrap();
+
+    let loaded = Project::load(path).unwrap();
+    assert_eq!(loaded.get_name(), "old_project");
+    assert_eq!(loaded.manifest, Manifest::new());
+}
@@ -0,0 +1,155 @@
+mod util;
+
+use scratch::{
+    client::{
+        Client,
+        Endpoints,
+        MemoryTransport,
+    },
+    manifest::Format,
+    scratch3::ProjectJson,
+    Project,
+    ProjectData,
+    SaveOptions,
+};
+use serde_json::Value;
+use std::path::Path;
+use util::TempDir;
+
+const PROJECT_2: &str = include_str!("fixtures/project2.json");
+const PROJECT_3: &str = include_str!("fixtures/project3.json");
+
+fn read_json(path: &Path) -> Value {
+    serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap()
+}
+
+/// Serves stand-ins for every asset of a project
+fn asset_client(data: &ProjectData) -> Client {
+    let transport = data
+        .asset_names()
+        .into_iter()
+        .fold(MemoryTransport::new(), |transport, name| {
+            transport.with(&format!("/internalapi/asset/{}/get", name), name)
+        });
+    Client::with_transport(transport, Endpoints::default())
+}
+
+#[test]
+pub fn serialized_as_plain_json() {
+    for fixture in [PROJECT_2, PROJECT_3].iter() {
+        let data = ProjectData::from_slice(fixture.as_bytes()).unwrap();
+        let json = serde_json::to_value(&data).unwrap();
+        assert!(json.get("Scratch2").is_none() && json.get("Scratch3").is_none());
+
+        let again: ProjectData = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(again.format(), data.format());
+        assert_eq!(serde_json::to_value(&again).unwrap(), json);
+
+        let key = match data.format() {
+            Format::Scratch2 => "Scratch2",
+            Format::Scratch3 => "Scratch3",
+        };
+        let wrapped = serde_json::json!({ key: json });
+        let unwrapped: ProjectData = serde_json::from_value(wrapped).unwrap();
+        assert_eq!(unwrapped.format(), data.format());
+    }
+}
+
+#[test]
+pub fn saved_as_plain_json() {
+    let dir = TempDir::new("migrate-plain");
+    let data: ProjectJson = serde_json::from_str(PROJECT_3).unwrap();
+    let mut project: Project = data.into();
+    project.name = Some("plain".into());
+    let client = asset_client(&project.data);
+    project
+        .save_with_client(&client, dir.to_path_buf(), SaveOptions::new())
+        .unwrap();
+
+    let path = dir.join("plain");
+    let json = read_json(&path.join("data").join("project.json"));
+    assert_eq!(json["targets"][1]["name"], "Sprite1");
+    assert!(!Project::migrate(&client, path).unwrap());
+}
+
+#[test]
+pub fn wrapped_project_is_migrated() {
+    let dir = TempDir::new("migrate-wrapped");
+    let path = dir.join("old");
+    std::fs::create_dir_all(path.join("data")).unwrap();
+    std::fs::create_dir_all(path.join("target").join("python-pygame")).unwrap();
+    let wrapped =
+        serde_json::json!({ "Scratch3": serde_json::from_str::<Value>(PROJECT_3).unwrap() });
+    std::fs::write(path.join("data").join("project.json"), wrapped.to_string()).unwrap();
+
+    let loaded = Project::load(path.clone()).unwrap();
+    assert_eq!(loaded.data.format(), Format::Scratch3);
+
+    let client = asset_client(&loaded.data);
+    assert!(Project::migrate(&client, path.clone()).unwrap());
+    let json = read_json(&path.join("data").join("project.json"));
+    assert!(json.get("Scratch3").is_none());
+    assert_eq!(json["targets"][1]["name"], "Sprite1");
+    assert!(path.join("data").join("manifest.toml").is_file());
+    assert!(path.join("target").join("python-pygame").is_dir());
+    for name in loaded.data.asset_names() {
+        assert!(path.join("data").join("assets").join(name).is_file());
+    }
+
+    let migrated = Project::load(path.clone()).unwrap();
+    assert_eq!(migrated.get_name(), "old");
+    assert_eq!(migrated.manifest.format, Some(Format::Scratch3));
+    assert!(!Project::migrate(&client, path).unwrap());
+}
+
+#[test]
+pub fn legacy_layout_is_migrated() {
+    let dir = TempDir::new("migrate-legacy");
+    let path = dir.join("scratch_42");
+    let data = ProjectData::from_slice(PROJECT_2.as_bytes()).unwrap();
+    let names = data
+        .asset_names()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    std::fs::create_dir_all(path.join("metadata")).unwrap();
+    std::fs::create_dir_all(path.join("assets").join("images")).unwrap();
+    std::fs::create_dir_all(path.join("assets").join("audio")).unwrap();
+    std::fs::write(path.join("metadata").join("project.json"), PROJECT_2).unwrap();
+    std::fs::write(
+        path.join("project.json"),
+        serde_json::json!({ "code": "42", "name": null, "sound_list": [], "img_list": [] })
+            .to_string(),
+    )
+    .unwrap();
+    for name in names.iter() {
+        let kind = if name.ends_with(".wav") {
+            "audio"
+        } else {
+            "images"
+        };
+        std::fs::write(path.join("assets").join(kind).join(name), name).unwrap();
+    }
+
+    let loaded = Project::load(path.clone()).unwrap();
+    assert_eq!(loaded.get_name(), "scratch_42");
+    assert_eq!(loaded.data.format(), Format::Scratch2);
+    assert_eq!(loaded.manifest.id.as_deref(), Some("42"));
+    assert_eq!(loaded.assets.len(), names.len());
+
+    // Every asset is on disk already, so nothing is downloaded
+    let offline = Client::with_transport(MemoryTransport::new(), Endpoints::default());
+    assert!(Project::migrate(&offline, path.clone()).unwrap());
+    let json = read_json(&path.join("data").join("project.json"));
+    assert_eq!(json["objName"], "Stage");
+    for name in names.iter() {
+        let saved = std::fs::read(path.join("data").join("assets").join(name)).unwrap();
+        assert_eq!(saved, name.as_bytes());
+    }
+    assert!(path.join("metadata").join("project.json").is_file());
+
+    let migrated = Project::load(path).unwrap();
+    assert_eq!(migrated.manifest.id.as_deref(), Some("42"));
+    assert_eq!(migrated.manifest.format, Some(Format::Scratch2));
+}
@@ -0,0 +1,19 @@
+use scratch::ProjectData;
+
+const PROJECT_2: &str = include_str!("fixtures/project2.json");
+const PROJECT_3: &str = include_str!("fixtures/project3.json");
+
+#[test]
+pub fn detect_format() {
+    match ProjectData::from_slice(PROJECT_2.as_bytes()).unwrap() {
+        ProjectData::Scratch2(data) => assert_eq!(data.children[0].name, "Sprite1"),
+        ProjectData::Scratch3(_) => panic!("Expected a Scratch 2 project"),
+    }
+
+    match ProjectData::from_slice(PROJECT_3.as_bytes()).unwrap() {
+        ProjectData::Scratch3(data) => assert_eq!(data.targets[1].name, "Sprite1"),
+        ProjectData::Scratch2(_) => panic!("Expected a Scratch 3 project"),
+    }
+
+    assert!(ProjectData::from_slice(b"{\"title\": \"Not a project\"}").is_err());
+}
@@ -0,0 +1,277 @@
+mod util;
+
+use scratch::{
+    client::{
+        Client,
+        Endpoints,
+        MemoryTransport,
+    },
+    download::DownloadOptions,
+    scratch3::ProjectJson,
+    Project,
+    SaveOptions,
+    ScratchError,
+};
+use std::{
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+use util::TempDir;
+
+fn project() -> Project {
+    let data: ProjectJson = serde_json::from_str(include_str!("fixtures/project3.json")).unwrap();
+    let mut project: Project = data.into();
+    project.name = Some("game".into());
+    let names = project
+        .data
+        .asset_names()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    for name in names {
+        project.assets.insert(name.clone(), name.into_bytes());
+    }
+    project
+}
+
+/// Saves a project, then adds what a build and a user would leave in it
+fn saved_project(dir: &Path) -> PathBuf {
+    let mut project = project();
+    project
+        .manifest
+        .target_settings_mut("python-pygame")
+        .insert("fps".into(), toml::Value::Integer(60));
+    project.save(dir.to_path_buf(), SaveOptions::new()).unwrap();
+
+    let path = dir.join("game");
+    std::fs::create_dir_all(path.join("target").join("python-pygame")).unwrap();
+    std::fs::write(
+        path.join("target").join("python-pygame").join("index.py"),
+        "",
+    )
+    .unwrap();
+    std::fs::create_dir_all(path.join("data").join("assets")).unwrap();
+    std::fs::write(path.join("data").join("assets").join("a.svg"), "<svg/>").unwrap();
+    std::fs::write(path.join("data").join("notes.txt"), "notes").unwrap();
+    path
+}
+
+fn entries(dir: &Path) -> Vec<String> {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect()
+}
+
+/// The id of a process that has already exited, for a save that died part way
+fn dead_pid() -> u32 {
+    let mut child = std::process::Command::new(env!("CARGO"))
+        .arg("--version")
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    child.wait().unwrap();
+    child.id()
+}
+
+#[test]
+pub fn existing_project_is_kept_by_default() {
+    let dir = TempDir::new("save-default");
+    let path = saved_project(&dir);
+
+    let result = project().save(dir.to_path_buf(), SaveOptions::new());
+    assert!(matches!(result, Err(ScratchError::InvalidSavePath)));
+    assert!(path.join("data").join("notes.txt").exists());
+}
+
+#[test]
+pub fn overwrite_keeps_builds_and_assets() {
+    let dir = TempDir::new("save-overwrite");
+    let path = saved_project(&dir);
+
+    let mut project = project();
+    project
+        .save(dir.to_path_buf(), SaveOptions::new().overwrite(true))
+        .unwrap();
+
+    assert!(path
+        .join("target")
+        .join("python-pygame")
+        .join("index.py")
+        .exists());
+    assert!(path.join("data").join("assets").join("a.svg").exists());
+    assert!(!path.join("data").join("notes.txt").exists());
+    let loaded = Project::load(path).unwrap();
+    assert!(loaded.manifest.targets.is_empty());
+    assert_eq!(entries(&dir), vec!["game"]);
+}
+
+#[test]
+pub fn update_keeps_old_files_and_settings() {
+    let dir = TempDir::new("save-update");
+    let path = saved_project(&dir);
+
+    let mut project = project();
+    project
+        .manifest
+        .target_settings_mut("js")
+        .insert("minify".into(), toml::Value::Boolean(true));
+    project
+        .save(dir.to_path_buf(), SaveOptions::new().update(true))
+        .unwrap();
+
+    assert!(path
+        .join("target")
+        .join("python-pygame")
+        .join("index.py")
+        .exists());
+    assert!(path.join("data").join("assets").join("a.svg").exists());
+    assert_eq!(
+        std::fs::read_to_string(path.join("data").join("notes.txt")).unwrap(),
+        "notes"
+    );
+    let loaded = Project::load(path).unwrap();
+    let targets = &loaded.manifest.targets;
+    assert_eq!(targets["python-pygame"]["fps"].as_integer(), Some(60));
+    assert_eq!(targets["js"]["minify"].as_bool(), Some(true));
+    assert_eq!(entries(&dir), vec!["game"]);
+}
+
+#[test]
+pub fn invalid_paths() {
+    let dir = TempDir::new("save-invalid");
+    let mut unnamed = project();
+    unnamed.name = None;
+    assert!(matches!(
+        unnamed.save(dir.to_path_buf(), SaveOptions::new()),
+        Err(ScratchError::InvalidSavePath)
+    ));
+    assert!(matches!(
+        project().save(dir.join("missing"), SaveOptions::new()),
+        Err(ScratchError::InvalidSavePath)
+    ));
+    assert!(entries(&dir).is_empty());
+}
+
+#[test]
+pub fn assets_are_saved_and_reused() {
+    let dir = TempDir::new("save-assets");
+    let path = saved_project(&dir);
+    let names = project().assets.keys().cloned().collect::<Vec<_>>();
+    for name in names.iter() {
+        let saved = std::fs::read(path.join("data").join("assets").join(name)).unwrap();
+        assert_eq!(saved, name.as_bytes());
+    }
+
+    // Nothing is bundled this time, so every asset has to come from the old copy
+    let transport = Arc::new(MemoryTransport::new());
+    let client = Client::with_transport(transport.clone(), Endpoints::default());
+    let mut project = project();
+    project.assets.clear();
+    project
+        .save_with_client(&client, dir.to_path_buf(), SaveOptions::new().update(true))
+        .unwrap();
+    assert!(transport.requests().is_empty());
+
+    let loaded = Project::load(path).unwrap();
+    let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+    let downloads = loaded.load_assets(&client, &names, &DownloadOptions::new(), |_| {});
+    assert_eq!(downloads.into_result().unwrap().len(), names.len());
+    assert!(transport.requests().is_empty());
+}
+
+#[test]
+pub fn missing_assets_fail_the_save() {
+    let dir = TempDir::new("save-missing");
+    let client = Client::with_transport(MemoryTransport::new(), Endpoints::default());
+    let mut project = project();
+    project.assets.clear();
+
+    let options = SaveOptions::new();
+    let result = project.save_with_client(&client, dir.to_path_buf(), options);
+    assert!(matches!(result, Err(ScratchError::Custom(_))));
+    assert!(entries(&dir).is_empty());
+}
+
+#[test]
+pub fn interrupted_swap_is_undone_on_load() {
+    let dir = TempDir::new("save-interrupted");
+    let path = saved_project(&dir);
+
+    // As if a save died after setting the old copy aside and moving the build out of it
+    let pid = dead_pid();
+    let backup = dir.join(format!("game.old-{}", pid));
+    let staging = dir.join(format!(".game.new-{}", pid));
+    std::fs::rename(&path, &backup).unwrap();
+    std::fs::create_dir_all(staging.join("data")).unwrap();
+    std::fs::rename(backup.join("target"), staging.join("target")).unwrap();
+
+    let loaded = Project::load(path.clone()).unwrap();
+    assert_eq!(loaded.get_name(), "game");
+    assert!(path
+        .join("target")
+        .join("python-pygame")
+        .join("index.py")
+        .exists());
+    assert!(path.join("data").join("notes.txt").exists());
+    assert_eq!(entries(&dir), vec!["game"]);
+}
+
+#[test]
+pub fn running_swap_is_left_alone_on_load() {
+    let dir = TempDir::new("save-running");
+    let path = saved_project(&dir);
+
+    // This process is still running, so its swap may be part way through
+    let backup = dir.join(format!("game.old-{}", std::process::id()));
+    std::fs::rename(&path, &backup).unwrap();
+
+    assert!(Project::load(path.clone()).is_err());
+    assert!(backup.join("data").join("notes.txt").exists());
+    assert!(!path.exists());
+}
+
+#[test]
+pub fn finished_swap_is_cleaned_up_on_save() {
+    let dir = TempDir::new("save-finished");
+    saved_project(&dir);
+    std::fs::create_dir(dir.join(format!("game.old-{}", dead_pid()))).unwrap();
+
+    project()
+        .save(dir.to_path_buf(), SaveOptions::new().overwrite(true))
+        .unwrap();
+    assert_eq!(entries(&dir), vec!["game"]);
+}
+
+#[test]
+pub fn save_reports_only_missing_downloads() {
+    let dir = TempDir::new("save-progress");
+    let mut project = project();
+    let name = project.assets.keys().next().unwrap().clone();
+    project.assets.remove(&name);
+
+    let transport = Arc::new(
+        MemoryTransport::new().with(&format!("/internalapi/asset/{}/get", name), name.as_str()),
+    );
+    let client = Client::with_transport(transport.clone(), Endpoints::default());
+    let reports = Mutex::new(Vec::new());
+    project
+        .save_with_progress(&client, dir.to_path_buf(), SaveOptions::new(), |p| {
+            reports
+                .lock()
+                .unwrap()
+                .push((p.name.to_string(), p.done, p.total));
+        })
+        .unwrap();
+
+    assert_eq!(reports.into_inner().unwrap(), vec![(name.clone(), 1, 1)]);
+    assert_eq!(transport.requests().len(), 1);
+    let saved = std::fs::read(dir.join("game").join("data").join("assets").join(&name)).unwrap();
+    assert_eq!(saved, name.as_bytes());
+}
@@ -0,0 +1,108 @@
+use scratch::{
+    scratch3::{
+        convert::convert,
+        script::{
+            build_scripts,
+            Expr,
+            ScriptIssue,
+        },
+        Primitive,
+        TargetBlock,
+    },
+    types::ProjectJson as ProjectJson2,
+};
+use serde_json::json;
+use std::collections::HashMap;
+
+const PROJECT_2: &str = include_str!("fixtures/project2.json");
+
+fn blocks(value: serde_json::Value) -> HashMap<String, TargetBlock> {
+    serde_json::from_value(value).unwrap()
+}
+
+fn block(opcode: &str, parent: Option<&str>, next: Option<&str>) -> serde_json::Value {
+    json!({
+        "opcode": opcode,
+        "parent": parent,
+        "next": next,
+        "inputs": {},
+        "fields": {},
+        "shadow": false,
+        "topLevel": parent.is_none(),
+    })
+}
+
+#[test]
+pub fn nested_scripts() {
+    let project: ProjectJson2 = serde_json::from_str(PROJECT_2).unwrap();
+    let project = convert(&project).project;
+
+    let built = build_scripts(&project.targets[1].blocks);
+    assert!(built.issues.is_empty(), "{:?}", built.issues);
+    assert_eq!(built.scripts.len(), 1);
+
+    let script = &built.scripts[0];
+    assert_eq!(script.hat().unwrap().opcode, "event_whenflagclicked");
+    assert_eq!(script.blocks.len(), 3);
+
+    let repeat = &script.blocks[1];
+    assert_eq!(repeat.opcode, "control_repeat");
+    assert_eq!(repeat.input("TIMES").literal(), Some("10"));
+
+    let body = repeat.substack("SUBSTACK");
+    assert_eq!(body.len(), 1);
+    assert_eq!(body[0].opcode, "music_playNoteForBeats");
+    assert_eq!(body[0].input("NOTE").literal(), Some("60"));
+    assert_eq!(script.blocks[2].opcode, "sound_playuntildone");
+}
+
+#[test]
+pub fn inline_reporters() {
+    let mut hat = block("event_whenflagclicked", None, Some("turn"));
+    hat["x"] = json!(10);
+    let mut turn = block("motion_turnright", Some("hat"), None);
+    turn["inputs"]["DEGREES"] = json!([3, "add", [4, "15"]]);
+    let mut add = block("operator_add", Some("turn"), None);
+    add["inputs"]["NUM1"] = json!([1, [4, "1"]]);
+    add["inputs"]["NUM2"] = json!([3, [12, "score", "v1"], [4, ""]]);
+
+    let built = build_scripts(&blocks(json!({ "hat": hat, "turn": turn, "add": add })));
+    assert!(built.issues.is_empty(), "{:?}", built.issues);
+    assert_eq!(built.scripts[0].x, 10.0);
+
+    let turn = &built.scripts[0].blocks[1];
+    let add = match turn.input("DEGREES") {
+        Expr::Block(add) => add,
+        other => panic!("Expected a reporter, got {:?}", other),
+    };
+    assert_eq!(add.opcode, "operator_add");
+    match add.input("NUM2") {
+        Expr::Primitive(Primitive::Variable { name, .. }) => assert_eq!(name, "score"),
+        other => panic!("Expected a variable, got {:?}", other),
+    }
+}
+
+#[test]
+pub fn broken_links() {
+    let built = build_scripts(&blocks(json!({
+        "hat": block("event_whenflagclicked", None, Some("a")),
+        "a": block("motion_turnright", Some("hat"), Some("b")),
+        "b": block("motion_turnleft", Some("a"), Some("a")),
+        "lost": block("motion_ifonedgebounce", Some("gone"), None),
+        "hat2": block("event_whenflagclicked", None, Some("missing")),
+    })));
+
+    assert_eq!(built.scripts.len(), 2);
+    assert_eq!(built.scripts[0].blocks.len(), 3);
+    assert!(built.issues.contains(&ScriptIssue::Cycle {
+        id: "a".into(),
+        from: "b".into()
+    }));
+    assert!(built.issues.contains(&ScriptIssue::Missing {
+        id: "missing".into(),
+        from: "hat2".into()
+    }));
+    assert!(built
+        .issues
+        .contains(&ScriptIssue::Orphan { id: "lost".into() }));
+}
@@ -0,0 +1,37 @@
+use std::{
+    ops::Deref,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// An empty directory under the system temp dir for one test, removed again when it is dropped
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Clears out anything an earlier run that didn't clean up left under the same name
+    pub fn new(name: &str) -> Self {
+        let mut path = std::env::temp_dir();
+        path.push(format!("scratch-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,180 @@
+mod util;
+
+use scratch::{
+    scratch3::ProjectJson as ProjectJson3,
+    types::ProjectJson as ProjectJson2,
+    validate::{
+        validate,
+        Code,
+        Severity,
+    },
+    Project,
+    ProjectData,
+    SaveOptions,
+};
+use serde_json::json;
+use std::collections::HashMap;
+use util::TempDir;
+
+const PROJECT_2: &str = include_str!("fixtures/project2.json");
+const PROJECT_3: &str = include_str!("fixtures/project3.json");
+
+fn block(opcode: &str, parent: Option<&str>, next: Option<&str>) -> serde_json::Value {
+    json!({
+        "opcode": opcode,
+        "parent": parent,
+        "next": next,
+        "inputs": {},
+        "fields": {},
+        "shadow": false,
+        "topLevel": parent.is_none(),
+    })
+}
+
+fn target(name: &str, is_stage: bool, blocks: serde_json::Value) -> serde_json::Value {
+    json!({
+        "isStage": is_stage,
+        "name": name,
+        "variables": { "var": ["score", 0] },
+        "lists": {},
+        "broadcasts": { "go": "go" },
+        "blocks": blocks,
+        "comments": {},
+        "currentCostume": 0,
+        "costumes": [{
+            "assetId": "a",
+            "name": "costume1",
+            "md5ext": "a.svg",
+            "dataFormat": "svg",
+            "rotationCenterX": 0,
+            "rotationCenterY": 0
+        }],
+        "sounds": [],
+        "volume": 100,
+        "layerOrder": 0,
+    })
+}
+
+#[test]
+pub fn fixtures_are_valid() {
+    let project: ProjectJson2 = serde_json::from_str(PROJECT_2).unwrap();
+    assert_eq!(validate(&ProjectData::Scratch2(project), None), Vec::new());
+
+    let project: ProjectJson3 = serde_json::from_str(PROJECT_3).unwrap();
+    assert_eq!(validate(&ProjectData::Scratch3(project), None), Vec::new());
+}
+
+#[test]
+pub fn broken_project() {
+    let mut set = block("data_setvariableto", Some("flag"), Some("gone"));
+    set["fields"] = json!({ "VARIABLE": ["lives", "missing"] });
+    let mut broadcast = block("event_broadcast", None, None);
+    broadcast["inputs"] = json!({ "BROADCAST_INPUT": [1, [11, "stop", "stop"]] });
+    let mut switch = block("looks_switchcostumeto", None, None);
+    switch["inputs"] = json!({ "COSTUME": [1, "menu"] });
+    let mut menu = block("looks_costume", Some("switch"), None);
+    menu["shadow"] = json!(true);
+    menu["fields"] = json!({ "COSTUME": ["costume2", null] });
+
+    let project: ProjectJson3 = serde_json::from_value(json!({
+        "targets": [
+            target("Stage", true, json!({})),
+            target("Sprite1", false, json!({
+                "flag": block("event_whenflagclicked", None, Some("set")),
+                "set": set,
+                "hat": block("event_whenkeypressed", Some("flag"), None),
+                "broadcast": broadcast,
+                "switch": switch,
+                "menu": menu,
+            })),
+        ],
+        "monitors": [],
+        "extensions": [],
+        "meta": { "semver": "3.0.0" }
+    }))
+    .unwrap();
+
+    let mut assets = HashMap::new();
+    assets.insert("a.svg".to_string(), Vec::new());
+    let data = ProjectData::Scratch3(project);
+    let diagnostics = validate(&data, Some(&assets));
+
+    let found = diagnostics
+        .iter()
+        .map(|d| (d.code, d.target.as_str(), d.block.as_deref()))
+        .collect::<Vec<_>>();
+    assert!(found.contains(&(Code::DanglingNext, "Sprite1", Some("set"))));
+    assert!(found.contains(&(Code::MissingVariable, "Sprite1", Some("set"))));
+    assert!(found.contains(&(Code::HatNotTopLevel, "Sprite1", Some("hat"))));
+    assert!(found.contains(&(Code::MissingBroadcast, "Sprite1", Some("broadcast"))));
+    assert!(found.contains(&(Code::MissingCostume, "Sprite1", Some("menu"))));
+    assert!(!found.iter().any(|d| d.0 == Code::MissingAsset));
+
+    let dangling = diagnostics
+        .iter()
+        .find(|d| d.code == Code::DanglingNext)
+        .unwrap();
+    assert_eq!(dangling.severity, Severity::Error);
+    assert_eq!(
+        dangling.to_string(),
+        "error[dangling-next] Sprite1 (block set): `next` points to missing block gone"
+    );
+
+    let diagnostics = validate(&data, Some(&HashMap::new()));
+    let missing = diagnostics
+        .iter()
+        .filter(|d| d.code == Code::MissingAsset)
+        .count();
+    assert_eq!(missing, 2);
+}
+
+#[test]
+pub fn scratch2_points_at_scripts() {
+    let mut project: serde_json::Value = serde_json::from_str(PROJECT_2).unwrap();
+    project["children"][0]["scripts"] = json!([
+        [0, 0, [["whenGreenFlag"], ["show"]]],
+        [0, 100, [["whenGreenFlag"], ["show"], ["lookLike:", "nope"]]]
+    ]);
+    let project: ProjectJson2 = serde_json::from_value(project).unwrap();
+    let diagnostics = validate(&ProjectData::Scratch2(project), None);
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, Code::MissingCostume);
+    assert_eq!(diagnostics[0].block, None);
+    assert_eq!(diagnostics[0].script, Some(1));
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "warning[missing-costume] Sprite1 (script 2): Switches to missing costume \"nope\""
+    );
+}
+
+#[test]
+pub fn saved_assets_are_checked() {
+    let data: ProjectJson3 = serde_json::from_str(PROJECT_3).unwrap();
+    let mut project: Project = data.into();
+    project.name = Some("saved".into());
+    let names = project
+        .data
+        .asset_names()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    for name in names.iter() {
+        project
+            .assets
+            .insert(name.clone(), name.clone().into_bytes());
+    }
+    let dir = TempDir::new("validate-saved");
+    project.save(dir.to_path_buf(), SaveOptions::new()).unwrap();
+
+    let path = dir.join("saved");
+    assert_eq!(Project::load(path.clone()).unwrap().validate(), Vec::new());
+
+    std::fs::remove_file(path.join("data").join("assets").join(&names[0])).unwrap();
+    let diagnostics = Project::load(path).unwrap().validate();
+    assert!(!diagnostics.is_empty());
+    for diagnostic in diagnostics.iter() {
+        assert_eq!(diagnostic.code, Code::MissingAsset);
+        assert!(diagnostic.message.contains(&names[0]));
+    }
+}
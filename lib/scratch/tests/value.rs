@@ -0,0 +1,243 @@
+use scratch::value::{
+    self,
+    Value,
+};
+
+fn number(n: f64) -> Value {
+    Value::Number(n)
+}
+
+fn text(s: &str) -> Value {
+    Value::from(s)
+}
+
+/// Checks a binary operator against results from scratch-vm, compared as text so NaN and infinities match
+fn check(op: fn(&Value, &Value) -> Value, table: &[(Value, Value, &str)]) {
+    for (lhs, rhs, expected) in table.iter() {
+        let result = op(lhs, rhs).to_string();
+        assert_eq!(result, *expected, "{:?}, {:?}", lhs, rhs);
+    }
+}
+
+#[test]
+pub fn casts() {
+    let numbers = [
+        (text(""), 0.0),
+        (text("  "), 0.0),
+        (text("abc"), 0.0),
+        (text("10"), 10.0),
+        (text(" 10 "), 10.0),
+        (text("1.5"), 1.5),
+        (text(".5"), 0.5),
+        (text("1e3"), 1000.0),
+        (text("0x10"), 16.0),
+        (text("Infinity"), f64::INFINITY),
+        (text("-Infinity"), f64::NEG_INFINITY),
+        (text("inf"), 0.0),
+        (text("nan"), 0.0),
+        (number(f64::NAN), 0.0),
+        (Value::Bool(true), 1.0),
+        (Value::Bool(false), 0.0),
+    ];
+    for (value, expected) in numbers.iter() {
+        assert_eq!(value.to_number(), *expected, "{:?}", value);
+    }
+
+    let strings = [
+        (number(123.0), "123"),
+        (number(1.5), "1.5"),
+        (number(-0.0), "0"),
+        (number(0.1 + 0.2), "0.30000000000000004"),
+        (number(0.000001), "0.000001"),
+        (number(1e-7), "1e-7"),
+        (number(1.5e-10), "1.5e-10"),
+        (number(1e20), "100000000000000000000"),
+        (number(1e21), "1e+21"),
+        (number(-1.25e25), "-1.25e+25"),
+        (number(f64::INFINITY), "Infinity"),
+        (number(f64::NEG_INFINITY), "-Infinity"),
+        (number(f64::NAN), "NaN"),
+        (Value::Bool(true), "true"),
+    ];
+    for (value, expected) in strings.iter() {
+        assert_eq!(value.to_string(), *expected);
+    }
+
+    let bools = [
+        (text(""), false),
+        (text("0"), false),
+        (text("false"), false),
+        (text("FALSE"), false),
+        (text("0.0"), true),
+        (text(" "), true),
+        (text("a"), true),
+        (number(0.0), false),
+        (number(f64::NAN), false),
+        (number(-1.0), true),
+    ];
+    for (value, expected) in bools.iter() {
+        assert_eq!(value.to_bool(), *expected, "{:?}", value);
+    }
+}
+
+#[test]
+pub fn comparisons() {
+    check(
+        value::gt,
+        &[
+            (text("10"), text("9"), "true"),
+            (text("10a"), text("9"), "false"),
+            (text("b"), text("A"), "true"),
+            (number(f64::INFINITY), number(1e308), "true"),
+        ],
+    );
+    check(
+        value::lt,
+        &[
+            (text("a"), text("b"), "true"),
+            (text(""), number(0.0), "true"),
+            (number(-1.0), text(""), "false"),
+        ],
+    );
+    check(
+        value::equals,
+        &[
+            (text("abc"), text("ABC"), "true"),
+            (text("1"), text("1.0"), "true"),
+            (text(""), number(0.0), "false"),
+            (text(" "), text(""), "false"),
+            (text("Infinity"), number(f64::INFINITY), "true"),
+            (Value::Bool(true), text("true"), "true"),
+            (Value::Bool(true), number(1.0), "true"),
+            (number(f64::NAN), text("nan"), "true"),
+        ],
+    );
+}
+
+#[test]
+pub fn arithmetic() {
+    check(
+        value::add,
+        &[
+            (text("1"), text("2"), "3"),
+            (text("a"), number(1.0), "1"),
+            (number(0.1), number(0.2), "0.30000000000000004"),
+        ],
+    );
+    check(
+        value::divide,
+        &[
+            (number(1.0), number(0.0), "Infinity"),
+            (number(-1.0), number(0.0), "-Infinity"),
+            (number(0.0), number(0.0), "NaN"),
+        ],
+    );
+    check(
+        value::modulo,
+        &[
+            (number(-1.0), number(3.0), "2"),
+            (number(1.0), number(-3.0), "-2"),
+            (number(5.5), number(2.0), "1.5"),
+            (number(5.0), number(0.0), "NaN"),
+        ],
+    );
+
+    let rounds = [
+        (number(2.5), "3"),
+        (number(-2.5), "-2"),
+        (text("1.4"), "1"),
+        (number(0.49999999999999994), "0"),
+        (number(-0.5), "0"),
+        (number(f64::INFINITY), "Infinity"),
+    ];
+    for (num, expected) in rounds.iter() {
+        assert_eq!(value::round(num).to_string(), *expected);
+    }
+
+    let math = [
+        ("abs", -5.0, "5"),
+        ("floor", -1.5, "-2"),
+        ("ceiling", 1.2, "2"),
+        ("sqrt", -1.0, "NaN"),
+        ("sin", 30.0, "0.5"),
+        ("sin", 180.0, "0"),
+        ("cos", 90.0, "0"),
+        ("tan", 90.0, "Infinity"),
+        ("tan", -90.0, "-Infinity"),
+        ("tan", 45.0, "1"),
+        ("log", 100.0, "2"),
+        ("log", 1000.0, "2.9999999999999996"),
+        ("10 ^", 2.0, "100"),
+        ("ln", 0.0, "-Infinity"),
+        ("unknown", 5.0, "0"),
+    ];
+    for (op, n, expected) in math.iter() {
+        assert_eq!(
+            value::math_op(op, &number(*n)).to_string(),
+            *expected,
+            "{} {}",
+            op,
+            n
+        );
+    }
+
+    assert_eq!(value::random(&number(1.0), &number(10.0), 0.0), number(1.0));
+    assert_eq!(
+        value::random(&number(1.0), &number(10.0), 0.999),
+        number(10.0)
+    );
+    assert_eq!(value::random(&number(10.0), &number(1.0), 0.5), number(6.0));
+    assert_eq!(value::random(&number(1.0), &text("10.0"), 0.5), number(5.5));
+    assert_eq!(value::random(&text("5"), &number(5.0), 0.5), number(5.0));
+    assert_eq!(
+        value::random(&number(0.0), &number(3e9), 1e-10),
+        number(0.0)
+    );
+
+    let ints = [
+        (number(1e20), true),
+        (number(3e9), true),
+        (number(-3e9), true),
+        (number(1e21), false),
+        (number(1.5), false),
+        (number(f64::INFINITY), false),
+        (number(f64::NAN), true),
+    ];
+    for (value, expected) in ints.iter() {
+        assert_eq!(value.is_int(), *expected, "{:?}", value);
+    }
+}
+
+#[test]
+pub fn text_operators() {
+    check(
+        value::join,
+        &[
+            (text("a"), number(1.0), "a1"),
+            (number(1.0), number(2.0), "12"),
+            (Value::Bool(true), text(""), "true"),
+            (number(1e21), text(""), "1e+21"),
+        ],
+    );
+    check(
+        value::letter_of,
+        &[
+            (number(1.0), text("hello"), "h"),
+            (number(1.9), text("hello"), "h"),
+            (number(0.0), text("hello"), ""),
+            (number(6.0), text("hello"), ""),
+            (number(2.0), number(123.0), "2"),
+        ],
+    );
+    check(
+        value::contains,
+        &[
+            (text("Hello"), text("ELL"), "true"),
+            (text("Hello"), text("world"), "false"),
+            (number(123.0), number(2.0), "true"),
+        ],
+    );
+    assert_eq!(value::join(&number(1.0), &number(2.0)), text("12"));
+    assert_eq!(value::length(&text("hello")), number(5.0));
+    assert_eq!(value::length(&number(-1.5)), number(4.0));
+}
@@ -0,0 +1,288 @@
+use scratch::{
+    ir::Program,
+    types::ProjectJson as ProjectJson2,
+    vm::{
+        Value,
+        Vm,
+    },
+    Project,
+    ProjectData,
+};
+use serde_json::json;
+
+const PROJECT_2: &str = include_str!("fixtures/project2.json");
+
+/// A headless VM for the Scratch 2 fixture with these scripts on its sprite
+fn headless(scripts: serde_json::Value) -> Vm {
+    vm(scripts, json!([])).virtual_clock().seed(1)
+}
+
+/// A VM for the Scratch 2 fixture with these scripts and variables on its sprite
+fn vm(scripts: serde_json::Value, variables: serde_json::Value) -> Vm {
+    let mut project: serde_json::Value = serde_json::from_str(PROJECT_2).unwrap();
+    project["children"][0]["scripts"] = scripts;
+    project["children"][0]["variables"] = variables;
+    let project: ProjectJson2 = serde_json::from_value(project).unwrap();
+    Vm::from_program(Program::from(&ProjectData::Scratch2(project)))
+}
+
+#[test]
+pub fn loops_yield_every_pass() {
+    let mut vm = vm(
+        json!([[
+            0,
+            0,
+            [["whenGreenFlag"], ["doRepeat", 3, [["changeXposBy:", 10]]]]
+        ]]),
+        json!([]),
+    );
+    vm.green_flag();
+
+    for x in [10.0, 20.0, 30.0].iter() {
+        vm.frame();
+        assert_eq!(vm.sprite("Sprite1").unwrap().x, *x);
+        assert!(vm.is_running());
+    }
+    vm.frame();
+    assert!(!vm.is_running());
+}
+
+#[test]
+pub fn waits_take_a_frame() {
+    let mut vm = vm(
+        json!([[
+            0,
+            0,
+            [
+                ["whenGreenFlag"],
+                ["changeXposBy:", 1],
+                ["wait:elapsed:from:", 0],
+                ["changeXposBy:", 1]
+            ]
+        ]]),
+        json!([]),
+    );
+    vm.green_flag();
+
+    vm.frame();
+    assert_eq!(vm.sprite("Sprite1").unwrap().x, 1.0);
+    vm.frame();
+    assert_eq!(vm.sprite("Sprite1").unwrap().x, 2.0);
+    assert!(!vm.is_running());
+}
+
+#[test]
+pub fn waiting_on_nothing_goes_on() {
+    let mut vm = vm(
+        json!([[
+            0,
+            0,
+            [
+                ["whenGreenFlag"],
+                ["changeXposBy:", 1],
+                ["doBroadcastAndWait", "nobody"],
+                ["changeXposBy:", 1]
+            ]
+        ]]),
+        json!([]),
+    );
+    vm.green_flag();
+
+    vm.frame();
+    assert_eq!(vm.sprite("Sprite1").unwrap().x, 2.0);
+    assert!(!vm.is_running());
+}
+
+#[test]
+pub fn broadcasts_and_keys_start_hats() {
+    let mut vm = vm(
+        json!([
+            [0, 0, [["whenGreenFlag"], ["broadcast:", "go"]]],
+            [
+                0,
+                100,
+                [["whenIReceive", "go"], ["changeVar:by:", "count", 1]]
+            ],
+            [
+                0,
+                200,
+                [["whenKeyPressed", "space"], ["changeVar:by:", "count", 10]]
+            ]
+        ]),
+        json!([{ "name": "count", "value": 0, "isPersistent": false }]),
+    );
+    vm.green_flag();
+    vm.frame();
+    assert_eq!(
+        vm.sprite("Sprite1").unwrap().variable("count"),
+        Some(&Value::Number(1.0))
+    );
+
+    vm.key_down("space");
+    vm.key_down("a");
+    vm.frame();
+    assert_eq!(
+        vm.sprite("Sprite1").unwrap().variable("count"),
+        Some(&Value::Number(11.0))
+    );
+}
+
+#[test]
+pub fn clones_run_their_own_scripts() {
+    let mut vm = vm(
+        json!([
+            [0, 0, [["whenGreenFlag"], ["createCloneOf", "_myself_"]]],
+            [0, 100, [["whenCloned"], ["changeXposBy:", 50]]]
+        ]),
+        json!([]),
+    );
+    vm.green_flag();
+    vm.frame();
+
+    let sprites = vm.sprites();
+    assert_eq!(sprites.len(), 2);
+    assert!(!sprites[0].is_clone);
+    assert_eq!(sprites[0].x, 0.0);
+    assert!(sprites[1].is_clone);
+    assert_eq!(sprites[1].x, 50.0);
+
+    vm.stop_all();
+    assert_eq!(vm.sprites().len(), 1);
+}
+
+#[test]
+pub fn headless_fixture() {
+    let data = ProjectData::from_slice(include_bytes!("fixtures/project3.json")).unwrap();
+    let project: Project = data.into();
+    let mut vm = Vm::headless(&project);
+    vm.green_flag();
+    vm.step(1);
+
+    let sprite = vm.sprite("Sprite1").unwrap();
+    assert_eq!(sprite.direction, 105.0);
+    assert_eq!((sprite.x, sprite.y), (0.0, 0.0));
+    assert_eq!(sprite.costume, 0);
+    assert_eq!(sprite.size, 100.0);
+    assert!(sprite.visible);
+    assert_eq!(
+        vm.stage().variable("my variable"),
+        Some(&Value::Number(0.0))
+    );
+    assert!(!vm.is_running());
+}
+
+#[test]
+pub fn headless_waits_count_frames() {
+    let mut vm = headless(json!([[
+        0,
+        0,
+        [
+            ["whenGreenFlag"],
+            ["wait:elapsed:from:", 1],
+            ["gotoX:y:", ["timer"], 0]
+        ]
+    ]]));
+    vm.green_flag();
+
+    vm.step(30);
+    assert!(vm.is_running());
+    assert_eq!(vm.sprite("Sprite1").unwrap().x, 0.0);
+    vm.step(1);
+    assert!(!vm.is_running());
+    assert_eq!(vm.sprite("Sprite1").unwrap().x, 1.0);
+    assert_eq!(vm.frame_count(), 31);
+    assert_eq!(vm.time(), 31.0 / 30.0);
+}
+
+#[test]
+pub fn headless_waits_started_mid_run() {
+    // Starts the wait on frame 14, where 14 / 30 + 1 is more than 44 / 30 in floating point
+    let mut vm = headless(json!([[
+        0,
+        0,
+        [
+            ["whenGreenFlag"],
+            ["doRepeat", 14, [["changeXposBy:", 1]]],
+            ["wait:elapsed:from:", 1],
+            ["changeXposBy:", 1]
+        ]
+    ]]));
+    vm.green_flag();
+
+    vm.step(44);
+    assert!(vm.is_running());
+    assert_eq!(vm.sprite("Sprite1").unwrap().x, 14.0);
+    vm.step(1);
+    assert!(!vm.is_running());
+    assert_eq!(vm.sprite("Sprite1").unwrap().x, 15.0);
+    assert_eq!(vm.frame_count(), 45);
+}
+
+#[test]
+pub fn headless_runs_repeat() {
+    let scripts = json!([[
+        0,
+        0,
+        [
+            ["whenGreenFlag"],
+            [
+                "doForever",
+                [
+                    [
+                        "gotoX:y:",
+                        ["randomFrom:to:", -240, 240],
+                        ["randomFrom:to:", -180, 180]
+                    ],
+                    ["turnRight:", ["randomFrom:to:", 0, 1]]
+                ]
+            ]
+        ]
+    ]]);
+    let mut first = headless(scripts.clone());
+    let mut second = headless(scripts);
+    first.green_flag();
+    second.green_flag();
+
+    for _ in 0..10 {
+        first.step(1);
+        second.step(1);
+        assert_eq!(first.sprites(), second.sprites());
+    }
+}
+
+#[test]
+pub fn lists_save_their_items() {
+    let mut project: serde_json::Value = serde_json::from_str(PROJECT_2).unwrap();
+    project["children"][0]["scripts"] = json!([[
+        0,
+        0,
+        [
+            ["whenGreenFlag"],
+            ["append:toList:", "c", "letters"],
+            ["insert:at:ofList:", "z", "last", "letters"],
+            ["deleteLine:ofList:", 1, "letters"],
+            [
+                "setVar:to:",
+                "found",
+                ["getLine:ofList:", "last", "letters"]
+            ]
+        ]
+    ]]);
+    project["children"][0]["variables"] = json!([{ "name": "found", "value": "" }]);
+    project["children"][0]["lists"] = json!([{ "listName": "letters", "contents": ["a", "b"] }]);
+    let project: ProjectJson2 = serde_json::from_value(project).unwrap();
+    let mut vm = Vm::from_program(Program::from(&ProjectData::Scratch2(project)));
+    vm.green_flag();
+    vm.frame();
+
+    let sprite = vm.sprite("Sprite1").unwrap();
+    assert_eq!(sprite.variable("found"), Some(&Value::from("z")));
+    assert_eq!(
+        sprite.list("letters").unwrap().contents(),
+        Value::from("bcz")
+    );
+
+    let lists = sprite.lists_json();
+    let saved = serde_json::to_value(lists.values().next().unwrap()).unwrap();
+    assert_eq!(saved, json!(["letters", ["b", "c", "z"]]));
+}
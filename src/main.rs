@@ -11,10 +11,19 @@ extern crate fs_extra;
 mod scratch;
 
 use crate::{
-    scratch::target::PyGameTarget,
+    scratch::{
+        target::PyGameTarget,
+        utils::with_progress,
+    },
     scratch_crate::{
+        cache::AssetCache,
+        coverage::Coverage,
+        target::Target,
+        validate::Severity,
+        vm::Vm,
         Project,
         SaveOptions,
+        ScratchResult,
     },
 };
 use clap::{
@@ -35,6 +44,11 @@ fn main() {
                         .short("c")
                         .long("code")
                         .required(true),
+                )
+                .arg(
+                    Arg::with_name("overwrite")
+                        .long("overwrite")
+                        .help("Replaces an existing copy instead of updating it"),
                 ),
         ) //temp
         .subcommand(
@@ -48,6 +62,67 @@ fn main() {
                 ),
         )
         .subcommand(SubCommand::with_name("build").arg(Arg::with_name("path").required(true))) //Remove for current dir?
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Runs a project directory or .sb2/.sb3 file")
+                .arg(Arg::with_name("path").required(true))
+                .arg(Arg::with_name("vm").long("vm").help(
+                    "Runs the project in the built-in VM instead of building it with Python",
+                )),
+        )
+        .subcommand(
+            SubCommand::with_name("convert")
+                .arg(Arg::with_name("input").required(true))
+                .arg(Arg::with_name("output").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .arg(Arg::with_name("path").required(true))
+                .arg(Arg::with_name("output").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Checks a project directory or .sb2/.sb3 file for problems")
+                .arg(Arg::with_name("path").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("coverage")
+                .about("Shows which of a project's blocks each target can build")
+                .arg(Arg::with_name("path").required(true))
+                .arg(
+                    Arg::with_name("format")
+                        .takes_value(true)
+                        .short("f")
+                        .long("format")
+                        .possible_values(&["table", "json"])
+                        .default_value("table"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Rewrites a project saved by an older version in the current layout")
+                .arg(Arg::with_name("path").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("cache")
+                .about("Manages the asset cache shared by every project")
+                .arg(
+                    Arg::with_name("dir")
+                        .takes_value(true)
+                        .long("dir")
+                        .help("Uses this cache directory instead of the default one"),
+                )
+                .subcommand(SubCommand::with_name("list").about("Lists the cached assets"))
+                .subcommand(
+                    SubCommand::with_name("verify")
+                        .about("Checks every cached asset against its md5"),
+                )
+                .subcommand(
+                    SubCommand::with_name("prune")
+                        .about("Removes damaged assets, and assets none of the given projects use")
+                        .arg(Arg::with_name("projects").multiple(true)),
+                ),
+        )
         .subcommand(SubCommand::with_name("old_main"))
         .get_matches();
 
@@ -61,10 +136,19 @@ fn main() {
 
             println!("Downloading Project... ");
             let net_project = scratch_crate::NetworkProject::new(code.to_string());
-            let data = client.get_data(&net_project).unwrap();
-            let mut project: Project = data.into();
+            let mut project = client.get_full_project(&net_project).unwrap();
             project.name = Some(format!("scratch_{}", code));
-            project.save(path.clone(), SaveOptions::new()).unwrap();
+
+            let options = if matches.is_present("overwrite") {
+                SaveOptions::new().overwrite(true)
+            } else {
+                SaveOptions::new().update(true)
+            };
+            // Only assets an older copy of the project lacks are downloaded
+            with_progress(|progress| {
+                project.save_with_progress(&client, path.clone(), options, progress)
+            })
+            .unwrap();
         }
         ("info", Some(matches)) => {
             let code = matches.value_of("code").expect("Code not specified");
@@ -105,6 +189,148 @@ fn main() {
             project.build(&mut target).unwrap();
             project.run(&mut target).unwrap();
         }
+        ("run", Some(matches)) => {
+            let path = PathBuf::from(matches.value_of("path").expect("No path specified"));
+            if matches.is_present("vm") {
+                let project = load_project(path).expect("Error Loading Project");
+                let mut vm = Vm::new(&project).on_say(|sprite, message| {
+                    println!("{}: {}", sprite, message);
+                });
+                vm.green_flag();
+                vm.run();
+            } else {
+                let mut project: Project = Project::load(path).unwrap();
+                let mut target = PyGameTarget::new();
+                project.build(&mut target).unwrap();
+                project.run(&mut target).unwrap();
+            }
+        }
+        ("convert", Some(matches)) => {
+            let input = PathBuf::from(matches.value_of("input").expect("No input specified"));
+            let output = PathBuf::from(matches.value_of("output").expect("No output specified"));
+            let project = scratch_crate::archive::load_sb2(input).unwrap();
+            let conversion = scratch_crate::scratch3::convert::convert(
+                project.data.as_ref2().expect("Scratch 2 Project"),
+            );
+            for block in conversion.unsupported.iter() {
+                println!("Unsupported block in {}: {}", block.target, block.selector);
+            }
+
+            let mut converted: Project = conversion.project.into();
+            converted.assets = project.assets;
+            scratch_crate::archive::save_sb3(&converted, &mut client, output).unwrap();
+        }
+        ("export", Some(matches)) => {
+            let path = PathBuf::from(matches.value_of("path").expect("No path specified"));
+            let output = PathBuf::from(matches.value_of("output").expect("No output specified"));
+            let project: Project = Project::load(path).unwrap();
+            scratch_crate::archive::save_sb3(&project, &mut client, output).unwrap();
+        }
+        ("check", Some(matches)) => {
+            let path = PathBuf::from(matches.value_of("path").expect("No path specified"));
+            let project = load_project(path).expect("Error Loading Project");
+
+            let diagnostics = project.validate();
+            for diagnostic in diagnostics.iter() {
+                println!("{}", diagnostic);
+            }
+            let errors = diagnostics
+                .iter()
+                .filter(|d| d.severity == Severity::Error)
+                .count();
+            println!(
+                "{} error(s), {} warning(s)",
+                errors,
+                diagnostics.len() - errors
+            );
+            if errors > 0 {
+                std::process::exit(1);
+            }
+        }
+        ("coverage", Some(matches)) => {
+            let path = PathBuf::from(matches.value_of("path").expect("No path specified"));
+            let project = load_project(path).expect("Error Loading Project");
+            let coverage = Coverage::new(&project.data)
+                .target_with("pygame", scratch_crate::target::PyGameTarget::supports)
+                .target_with("pygame-sb2", PyGameTarget::supports)
+                .target_with("js", scratch::api::targets::JsTarget::supports)
+                .target_with("vm", scratch_crate::vm::supports);
+
+            match matches.value_of("format") {
+                Some("json") => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&coverage).expect("Error Writing JSON")
+                ),
+                _ => print_coverage(&coverage),
+            }
+        }
+        ("migrate", Some(matches)) => {
+            let path = PathBuf::from(matches.value_of("path").expect("No path specified"));
+            if Project::migrate(&client, path).expect("Error Migrating Project") {
+                println!("Migrated");
+            } else {
+                println!("Already up to date");
+            }
+        }
+        ("cache", Some(matches)) => {
+            let cache = match matches.value_of("dir") {
+                Some(dir) => AssetCache::new(PathBuf::from(dir)),
+                None => AssetCache::open_default().expect("No Cache Directory"),
+            };
+
+            match matches.subcommand() {
+                ("list", _) => {
+                    let assets = cache.list().expect("Error Reading Cache");
+                    for asset in assets.iter() {
+                        println!("{:>10}  {}", asset.size, asset.md5ext);
+                    }
+                    let size: u64 = assets.iter().map(|a| a.size).sum();
+                    println!(
+                        "{} asset(s), {} bytes in {}",
+                        assets.len(),
+                        size,
+                        cache.path().display()
+                    );
+                }
+                ("verify", _) => {
+                    let damaged = cache.verify().expect("Error Reading Cache");
+                    for md5ext in damaged.iter() {
+                        println!("damaged: {}", md5ext);
+                    }
+                    println!("{} damaged asset(s)", damaged.len());
+                    if !damaged.is_empty() {
+                        std::process::exit(1);
+                    }
+                }
+                ("prune", Some(matches)) => {
+                    let projects = matches
+                        .values_of("projects")
+                        .map(|paths| {
+                            paths
+                                .map(|path| {
+                                    load_project(PathBuf::from(path))
+                                        .expect("Error Loading Project")
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                        .unwrap_or_default();
+
+                    let removed = cache
+                        .prune(|md5ext| {
+                            projects.is_empty()
+                                || projects
+                                    .iter()
+                                    .any(|p| p.data.asset_names().contains(&md5ext))
+                        })
+                        .expect("Error Pruning Cache");
+                    for md5ext in removed.iter() {
+                        println!("removed: {}", md5ext);
+                    }
+                    println!("{} asset(s) removed", removed.len());
+                }
+                _ => println!("Use one of list, verify or prune"),
+            }
+        }
         ("old_main", Some(matches)) => {
             old_main();
         }
@@ -113,6 +339,61 @@ fn main() {
     }
 }
 
+/// Loads a saved project directory, or an .sb2/.sb3 file along with its assets
+fn load_project(path: PathBuf) -> ScratchResult<Project> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("sb3") => scratch_crate::archive::load_sb3(&path),
+        Some("sb2") => scratch_crate::archive::load_sb2(&path),
+        _ => Project::load(path),
+    }
+}
+
+fn print_coverage(coverage: &Coverage) {
+    let width = coverage
+        .sprites
+        .iter()
+        .flat_map(|s| s.opcodes.iter())
+        .map(|o| o.opcode.len())
+        .max()
+        .unwrap_or(0)
+        .max("opcode".len());
+
+    for sprite in coverage.sprites.iter() {
+        println!("{} ({} blocks)", sprite.name, sprite.blocks());
+        if sprite.opcodes.is_empty() {
+            println!();
+            continue;
+        }
+
+        let mut header = format!("  {:width$}  {:>5}", "opcode", "count", width = width);
+        for target in coverage.targets.iter() {
+            header += &format!("  {:>10}", target);
+        }
+        println!("{}", header);
+
+        for opcode in sprite.opcodes.iter() {
+            let mut row = format!(
+                "  {:width$}  {:>5}",
+                opcode.opcode,
+                opcode.count,
+                width = width
+            );
+            for target in coverage.targets.iter() {
+                let supported = opcode.supported.contains(target);
+                row += &format!("  {:>10}", if supported { "yes" } else { "-" });
+            }
+            println!("{}", row);
+        }
+
+        let mut total = format!("  {:width$}  {:>5}", "supported", "", width = width);
+        for target in coverage.targets.iter() {
+            let supported = format!("{}/{}", sprite.supported_blocks(target), sprite.blocks());
+            total += &format!("  {:>10}", supported);
+        }
+        println!("{}\n", total);
+    }
+}
+
 //Hack to keep old code
 fn old_main() {
     std::fs::remove_dir_all("projects/scratch_239742347/target/js");
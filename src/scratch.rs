@@ -4,14 +4,24 @@ pub mod target;
 pub mod utils;
 
 use crate::scratch_crate::{
-    target::Target,
-    types::{
-        Block,
-        ProjectJson,
-        SoundJson,
-        SpriteJson,
+    ir::{
+        self,
+        BinaryOp,
+        Expr,
+        Hat,
+        Lowered,
+        Sound,
+        Sprite,
+        Statement,
     },
+    target::{
+        python_number,
+        Target,
+        MATH_ROUND_PY,
+    },
+    types::ProjectJson,
     util::FileCreater,
+    value::Value,
     ScratchError,
     ScratchResult,
 };
@@ -51,29 +61,90 @@ impl PyGameCodeGen {
         self.data.push('\n');
     }
 
-    pub fn write_block(&mut self, block: &Block) {
-        match block {
-            Block::PlaySoundAndWait(sound) => {
-                self.writeln(&format!("sound_list['{}'].play()", sound));
+    /// Whether there's real code for a Scratch 3 opcode lowered on its own, counting the hat `PyGameIndexFile`
+    /// starts scripts from
+    pub fn supports(opcode: &str) -> bool {
+        match ir::lower_opcode(opcode) {
+            Some(Lowered::Hat(hat)) => hat == Hat::GreenFlag,
+            Some(Lowered::Statement(statement)) => {
+                PyGameCodeGen::new(0).write_supported(&statement)
+            }
+            Some(Lowered::Reporter(expr)) => reporter(&expr).is_some(),
+            _ => false,
+        }
+    }
+
+    pub fn write_statement(&mut self, statement: &Statement) {
+        if !self.write_supported(statement) {
+            self.writeln(&format!("#{}", statement.opcode()));
+        }
+    }
+
+    /// Writes a statement there's real code for, giving `false` without writing anything for the rest
+    fn write_supported(&mut self, statement: &Statement) -> bool {
+        match statement {
+            Statement::PlaySound { sound, wait: true } => {
+                self.writeln(&format!(
+                    "sound_list['{}'].play()",
+                    sound.as_literal().unwrap_or("")
+                ));
             }
-            Block::PlayNote(note, beat) => {
+            Statement::PlayNote { note, beats } => {
+                let note = number(note);
                 self.writeln(&format!("midi_player.note_on({}, 127)", note));
                 self.writeln("t0 = time.time()");
-                self.writeln(&format!("while (time.time() - t0) < {}:", beat));
+                self.writeln(&format!("while (time.time() - t0) < {}:", number(beats)));
                 self.writeln("\tyield");
                 self.writeln(&format!("midi_player.note_off({}, 0)", note));
             }
-            Block::DoRepeat(n, blocks) => {
+            Statement::Repeat { times, body } => {
+                let mut codegen = PyGameCodeGen::new(self.tab);
+                codegen.writeln(&format!("for _i in range(math_round({})):", number(times)));
+                codegen.tab += 1;
+                codegen.writeln("pass");
+                for statement in body {
+                    codegen.write_statement(statement);
+                }
+                self.write_str(&codegen.data);
+            }
+            Statement::Forever(body) => {
                 let mut codegen = PyGameCodeGen::new(self.tab);
-                codegen.writeln(&format!("for _i in range({}):", n));
+                codegen.writeln("while True:");
                 codegen.tab += 1;
-                for block in blocks {
-                    codegen.write_block(block);
+                for statement in body {
+                    codegen.write_statement(statement);
                 }
+                codegen.writeln("yield");
                 self.write_str(&codegen.data);
             }
-            _ => self.writeln(&format!("#{:?}", block)),
+            Statement::Wait(secs) => {
+                self.writeln("t0 = time.time()");
+                self.writeln(&format!("while (time.time() - t0) < {}:", number(secs)));
+                self.writeln("\tyield");
+            }
+            _ => return false,
         }
+        true
+    }
+}
+
+/// A Python expression for a numeric argument, or `0` for anything that isn't supported yet
+fn number(expr: &Expr) -> String {
+    reporter(expr).unwrap_or_else(|| "0".into())
+}
+
+/// A Python expression for a numeric argument, if it's supported
+fn reporter(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Literal(literal) => Some(python_number(Value::from(literal.as_str()).to_number())),
+        Expr::Binary { op, lhs, rhs } => match op {
+            BinaryOp::Add => Some(format!("({} + {})", number(lhs), number(rhs))),
+            BinaryOp::Subtract => Some(format!("({} - {})", number(lhs), number(rhs))),
+            BinaryOp::Multiply => Some(format!("({} * {})", number(lhs), number(rhs))),
+            BinaryOp::Divide => Some(format!("({} / {})", number(lhs), number(rhs))),
+            _ => None,
+        },
+        _ => None,
     }
 }
 
@@ -90,7 +161,7 @@ impl PyGameIndexFile {
         }
     }
 
-    pub fn add_sprite(&mut self, s: &SpriteJson) {
+    pub fn add_sprite(&mut self, s: &Sprite) {
         let mut costume_data = String::new();
         if s.costumes.len() > 0 {
             costume_data = s
@@ -99,7 +170,11 @@ impl PyGameIndexFile {
                 .map(|c| {
                     format!(
                         "{}.costumes.append(Costume({}, {}, {}, 'assets/{}'))\n",
-                        s.name, c.center_x, c.center_y, c.resolution, c.src
+                        s.name,
+                        c.rotation_center_x,
+                        c.rotation_center_y,
+                        c.bitmap_resolution,
+                        c.md5ext
                     )
                 })
                 .collect();
@@ -107,31 +182,24 @@ impl PyGameIndexFile {
 
         let mut script_data = String::new();
 
-        if let Some(scripts) = &s.scripts {
-            for (i, script) in scripts.iter().enumerate() {
-                match script.blocks[0] {
-                    Block::WhenStart => {
-                        let script_name = format!("script_{}_{}", self.sprites.len(), i);
-                        let mut codegen = PyGameCodeGen::new(0);
-
-                        codegen.writeln(&format!("def {}(e):", script_name));
-                        codegen.tab += 1;
-                        for block in script.blocks[1..].iter() {
-                            codegen.write_block(&block);
-                        }
-                        codegen.writeln("return");
-                        script_data += &codegen.data;
-                        script_data += &format!("event_system.on('start', {})", script_name);
-                    }
-                    _ => {}
+        for (i, script) in s.scripts.iter().enumerate() {
+            if script.hat == Hat::GreenFlag {
+                let script_name = format!("script_{}_{}", self.sprites.len(), i);
+                let mut codegen = PyGameCodeGen::new(0);
+
+                codegen.writeln(&format!("def {}(e):", script_name));
+                codegen.tab += 1;
+                for statement in script.body.iter() {
+                    codegen.write_statement(statement);
                 }
+                codegen.writeln("return");
+                script_data += &codegen.data;
+                script_data += &format!("event_system.on('start', {})", script_name);
             }
         }
 
-        if let Some(sounds) = &s.sounds {
-            for s in sounds {
-                self.add_sound(&s);
-            }
+        for sound in s.sounds.iter() {
+            self.add_sound(sound);
         }
 
         let data = format!(
@@ -140,7 +208,7 @@ impl PyGameIndexFile {
 {script}
 sprite_list.append({name})
 "#,
-            s.current_costume_index,
+            s.current_costume,
             costume = costume_data,
             script = script_data,
             name = s.name,
@@ -151,19 +219,21 @@ sprite_list.append({name})
         self.sprites.push(data);
     }
 
-    pub fn add_sound(&mut self, s: &SoundJson) {
+    pub fn add_sound(&mut self, s: &Sound) {
         self.sounds.push(format!(
             "sound_list['{}'] = pygame.mixer.Sound('assets/{}')\n",
-            s.name, s.src
+            s.name, s.md5ext
         ));
     }
 
     pub fn build(&self) -> String {
         let mut ret = String::new();
+        ret += "import math\n";
         ret += "import pygame\n";
         ret += "import pygame.midi\n";
         ret += "import time\n";
         ret += "from svg import Parser, Rasterizer\n";
+        ret += MATH_ROUND_PY;
 
         ret += include_str!("./scratch/event.py");
         ret += include_str!("./scratch/event_dispatcher.py");
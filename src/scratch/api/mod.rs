@@ -2,6 +2,10 @@ pub mod targets;
 pub mod types;
 
 use self::types::*;
+use crate::scratch_crate::cache::{
+    self,
+    AssetCache,
+};
 use curl::easy::{
     Easy2,
     Handler,
@@ -10,12 +14,15 @@ use curl::easy::{
 
 pub struct Api {
     handle: Easy2<BufferBody>,
+    /// The shared asset cache, if there is a place for it
+    cache: Option<AssetCache>,
 }
 
 impl Api {
     pub fn new() -> Self {
         return Self {
             handle: Easy2::new(BufferBody::new()),
+            cache: AssetCache::open_default(),
         };
     }
 
@@ -57,12 +64,21 @@ impl Api {
         return Ok(project);
     }
 
+    /// Gets an asset through the shared cache. A cache that can't be read or written only costs a download.
     pub fn get_asset(&mut self, name: &str) -> ApiResult<Vec<u8>> {
+        if let Some(data) = self.cache.as_ref().and_then(|c| c.get(name).ok().flatten()) {
+            return Ok(data);
+        }
+
         let url = format!(
             "https://cdn.assets.scratch.mit.edu/internalapi/asset/{}/get",
             name
         );
-        return self.get_url(&url);
+        let data = self.get_url(&url)?;
+        if let Some(cache) = self.cache.as_ref().filter(|_| cache::is_valid(name, &data)) {
+            let _ = cache.insert(name, &data);
+        }
+        return Ok(data);
     }
 }
 
@@ -12,14 +12,21 @@ use std::process::{
 
 use self::super::{
     super::utils::DirCreater,
-    types::{
-        json::{
-            ProjectJson,
-            ScriptJson,
-        },
-        Block,
-        Project,
+    types::Project,
+};
+use crate::scratch_crate::{
+    ir::{
+        self,
+        Costume,
+        Expr,
+        Hat,
+        Lowered,
+        Program,
+        Script,
+        Sprite,
+        Statement,
     },
+    ProjectData,
 };
 
 pub trait Target {
@@ -32,6 +39,8 @@ pub trait Target {
 #[derive(Debug)]
 pub enum TargetError {
     FileAlreadyExists,
+    /// The project's `metadata/project.json` is missing or can't be read
+    InvalidProject,
 }
 
 pub type TargetResult<T> = Result<T, TargetError>;
@@ -48,16 +57,24 @@ impl JsTarget {
         std::fs::write(&path, data)
     }
 
-    pub fn get_sprite_js(sprite: &super::types::json::SpriteJson) -> String {
+    /// Lowers the project data saved in the project's `metadata` dir
+    pub fn load_program(project: &Project) -> TargetResult<Program> {
+        let mut path = project.path.clone().ok_or(TargetError::InvalidProject)?;
+        path.push("metadata");
+        path.push("project.json");
+        let data = std::fs::read(&path).map_err(|_| TargetError::InvalidProject)?;
+        let data = ProjectData::from_slice(&data).map_err(|_| TargetError::InvalidProject)?;
+        Ok(Program::from(&data))
+    }
+
+    pub fn get_sprite_js(sprite: &Sprite) -> String {
         let costumes = sprite.costumes.iter().fold(String::new(), |code, costume| {
             code + &Self::get_costume_js(sprite, costume)
         });
 
         let mut scripts_str = String::new();
-        if let Some(ref scripts) = sprite.scripts {
-            for script in scripts.iter() {
-                scripts_str += &Self::get_scratch_script_js(&script);
-            }
+        for script in sprite.scripts.iter() {
+            scripts_str += &Self::get_scratch_script_js(script);
         }
 
         return format!(
@@ -79,10 +96,7 @@ let {name} = new Scratch.Sprite();
         );
     }
 
-    pub fn get_costume_js(
-        sprite: &super::types::json::SpriteJson,
-        costume: &super::types::json::CostumeJson,
-    ) -> String {
+    pub fn get_costume_js(sprite: &Sprite, costume: &Costume) -> String {
         return format!(
             r#"//Start {costume_name}
 let {name}_{costume_name} = new Scratch.Costume();
@@ -98,40 +112,33 @@ let {name}_{costume_name} = new Scratch.Costume();
 "#,
             name = sprite.name,
             costume_name = costume.name,
-            src = costume.src,
-            x = costume.center_x,
-            y = costume.center_y,
-            resolution = costume.resolution
+            src = costume.md5ext,
+            x = costume.rotation_center_x,
+            y = costume.rotation_center_y,
+            resolution = costume.bitmap_resolution
         );
     }
 
-    pub fn get_main_js(project: &ProjectJson) -> String {
+    pub fn get_main_js(program: &Program) -> String {
         let mut sound_list: Vec<&_> = Vec::new();
-        sound_list.extend(
-            project.sounds.iter().chain(
-                project
-                    .children
-                    .iter()
-                    .flat_map(|child| child.sounds.iter()),
-            ),
-        );
+        sound_list.extend(program.targets().flat_map(|target| target.sounds.iter()));
 
         let mut audio_data = String::new();
         for audio in sound_list.iter() {
             audio_data += &format!(
                 "game.audioAssets.set('{name}', new Audio('assets/audio/{md5}'));\n",
                 name = audio.name,
-                md5 = audio.src
+                md5 = audio.md5ext
             );
         }
 
-        let mut body = project
-            .children
+        let mut body = program
+            .sprites
             .iter()
             .map(|sprite| Self::get_sprite_js(sprite))
             .chain(
-                project
-                    .children
+                program
+                    .sprites
                     .iter()
                     .map(|sprite| format!("game.add({name});\n", name = sprite.name)),
             )
@@ -150,42 +157,62 @@ game.start();
         return main_js;
     }
 
-    pub fn get_scratch_script_js(script: &ScriptJson) -> String {
+    pub fn get_scratch_script_js(script: &Script) -> String {
         let mut total = String::new();
-        let mut block_list = script.blocks.iter();
-        if let Some(first) = block_list.next() {
-            if let Block::WhenStart = first {
-                for block in block_list {
-                    total += &Self::get_scratch_block_js(&block);
-                }
+        if Self::runs(&script.hat) {
+            for statement in script.body.iter() {
+                total += &Self::get_scratch_block_js(statement);
             }
         }
         return total;
     }
 
-    pub fn get_scratch_block_js(block: &Block) -> String {
-        let mut total = String::new();
-        match block {
-            Block::PlaySoundAndWait(ref data) => {
-                total += &format!(
-                    r#"if(game.data.get("GAME_FIRST_CYCLE")){{
-		let audio = game.audioAssets.get('{name}');
-		audio.play();
-	}}"#,
-                    name = data.song
-                );
+    /// Whether `get_scratch_script_js` writes out the scripts under a hat
+    fn runs(hat: &Hat) -> bool {
+        matches!(hat, Hat::GreenFlag)
+    }
+
+    /// Whether `get_scratch_block_js` handles a Scratch 3 opcode lowered on its own, counting the hat
+    /// `get_scratch_script_js` looks for
+    pub fn supports(opcode: &str) -> bool {
+        match ir::lower_opcode(opcode) {
+            Some(Lowered::Hat(hat)) => Self::runs(&hat),
+            Some(Lowered::Statement(statement)) => {
+                Self::get_supported_block_js(&statement).is_some()
             }
-            _ => {
-                let ident = block.get_name();
-                total += &format!(
+            _ => false,
+        }
+    }
+
+    pub fn get_scratch_block_js(statement: &Statement) -> String {
+        match Self::get_supported_block_js(statement) {
+            Some(js) => js,
+            None => {
+                let ident = statement.opcode();
+                format!(
                     r#"//{ident}
 "#,
                     ident = ident
-                );
+                )
             }
         }
+    }
 
-        return total;
+    /// The code for a statement, if there is any
+    fn get_supported_block_js(statement: &Statement) -> Option<String> {
+        match statement {
+            Statement::PlaySound {
+                sound: Expr::Literal(name),
+                wait: true,
+            } => Some(format!(
+                r#"if(game.data.get("GAME_FIRST_CYCLE")){{
+		let audio = game.audioAssets.get('{name}');
+		audio.play();
+	}}"#,
+                name = name
+            )),
+            _ => None,
+        }
     }
 }
 
@@ -198,7 +225,8 @@ impl Target for JsTarget {
         let name = project.get_name().unwrap().clone();
         println!("Setting up js target in {}", path.display());
 
-        let main_js = Self::get_main_js(&project.project_json);
+        let program = Self::load_program(project)?;
+        let main_js = Self::get_main_js(&program);
 
         let package_json = format!(
             r#"{{
@@ -14,9 +14,16 @@ pub use self::{
 };
 
 use self::super::{
-    super::utils::DirCreater,
+    super::utils::{
+        download_with_progress,
+        DirCreater,
+    },
     targets::Target,
 };
+use crate::scratch_crate::{
+    client::Client,
+    download::DownloadOptions,
+};
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Project {
@@ -74,7 +81,7 @@ impl Project {
         return path;
     }
 
-    pub fn init(&mut self, api: &mut super::Api, mut path: PathBuf) -> ApiResult<()> {
+    pub fn init(&mut self, client: &Client, mut path: PathBuf) -> ApiResult<()> {
         //Test to see if loc is valid?
         let mut builder = DirCreater::new(path.clone());
         builder
@@ -113,23 +120,29 @@ impl Project {
 
         builder.down("assets").down("audio");
 
-        let mut asset_buf;
+        let sounds = self.sound_list.iter().map(|data| data.src.as_str());
+        let images = self.img_list.iter().map(|img| img.src.as_str());
+        let names = sounds.chain(images).collect::<Vec<_>>();
+        let downloads = download_with_progress(names.len(), |progress| {
+            client.get_assets(&names, &DownloadOptions::new(), progress)
+        });
+
         for data in &self.sound_list {
-            println!("Downloading: {}", &data.src);
-            asset_buf = api.get_asset(&data.src).unwrap();
-            builder
-                .write_file(&data.src, &asset_buf)
-                .expect("Error Saving file");
+            if let Some(asset) = downloads.assets.get(&data.src) {
+                builder
+                    .write_file(&data.src, asset)
+                    .expect("Error Saving file");
+            }
         }
 
         builder.up().down("images");
 
         for img in &self.img_list {
-            println!("Downloading: {}", &img.src);
-            asset_buf = api.get_asset(&img.src).unwrap();
-            builder
-                .write_file(&img.src, &asset_buf)
-                .expect("Error Saving file");
+            if let Some(asset) = downloads.assets.get(&img.src) {
+                builder
+                    .write_file(&img.src, asset)
+                    .expect("Error Saving file");
+            }
         }
 
         builder.up().up();
@@ -1,10 +1,14 @@
 use crate::scratch::{
+    utils::download_with_progress,
+    PyGameCodeGen,
     PyGameIndexFile,
     ScratchError,
     ScratchResult,
 };
 pub use crate::scratch_crate::{
     client::Client,
+    download::DownloadOptions,
+    ir::Program,
     util::{
         make_command,
         FileCreater,
@@ -54,6 +58,10 @@ impl Target for PyGameTarget {
         "python-pygame"
     }
 
+    fn supports(opcode: &str) -> bool {
+        PyGameCodeGen::supports(opcode)
+    }
+
     fn init(&mut self) -> ScratchResult<()> {
         self.has_pygame = test_import("pygame")?;
         let has_cython = test_import("cython")?;
@@ -72,23 +80,10 @@ impl Target for PyGameTarget {
         let path = project.get_build_path(self);
         let mut file_creater = FileCreater::new(path);
 
+        let program = Program::from(&project.data);
         let mut index_file = PyGameIndexFile::new();
-        index_file.add_sprite(
-            &project
-                .data
-                .as_ref2()
-                .expect("Scratch 2 Project")
-                .clone()
-                .to_sprite_json(),
-        );
-        for c in project
-            .data
-            .as_ref2()
-            .expect("Scratch 2 Project")
-            .children
-            .iter()
-        {
-            index_file.add_sprite(c);
+        for sprite in program.targets() {
+            index_file.add_sprite(sprite);
         }
 
         let _ = file_creater
@@ -97,51 +92,39 @@ impl Target for PyGameTarget {
             .mkdir("assets")
             .is_ok();
 
-        let mut client = Client::new();
+        let client = Client::new();
 
-        for c in project
-            .data
-            .as_ref2()
-            .expect("Scratch 2 Project")
-            .children
-            .iter()
+        let costumes = program
+            .targets()
             .flat_map(|s| s.costumes.iter())
-            .chain(
-                project
-                    .data
-                    .as_ref2()
-                    .expect("Scratch 2 Project")
-                    .costumes
-                    .iter(),
-            )
-        {
-            if !file_creater.exists(&c.src) {
-                println!("Downloading {}...", c.src);
-                file_creater
-                    .write_bytes(&c.src, &client.get_asset(&c.src)?)
-                    .unwrap();
-                if c.src.ends_with(".svg") {
-                    dbg!("SVG");
-                }
+            .map(|c| c.md5ext.as_str());
+        let sounds = program
+            .targets()
+            .flat_map(|s| s.sounds.iter())
+            .map(|s| s.md5ext.as_str());
+        let mut names = Vec::new();
+        for md5ext in costumes.chain(sounds) {
+            let dest = file_creater.path.join(md5ext);
+            if !dest.exists() && !project.copy_asset(md5ext, &dest)? {
+                names.push(md5ext);
             }
         }
+        names.sort_unstable();
+        names.dedup();
 
-        for c in project
-            .data
-            .as_ref2()
-            .expect("Scratch 2 Project")
-            .children
+        let missing = names
             .iter()
-            .flat_map(|s| s.sounds.iter())
-            .flatten()
-        {
-            if !file_creater.exists(&c.src) {
-                println!("Downloading {}...", c.src);
-                file_creater
-                    .write_bytes(&c.src, &client.get_asset(&c.src)?)
-                    .unwrap();
-            }
+            .filter(|name| !project.assets.contains_key(**name))
+            .count();
+        let downloads = download_with_progress(missing, |progress| {
+            project.load_assets(&client, &names, &DownloadOptions::new(), progress)
+        });
+        for (md5ext, data) in downloads.assets.iter() {
+            file_creater
+                .write_bytes(md5ext, data)
+                .map_err(ScratchError::Io)?;
         }
+        downloads.into_result()?;
 
         Ok(())
     }
@@ -1,5 +1,58 @@
+use crate::scratch_crate::download::{
+    DownloadProgress,
+    Downloads,
+};
+use indicatif::{
+    ProgressBar,
+    ProgressStyle,
+};
 use std::path::PathBuf;
 
+fn progress_bar(len: usize) -> ProgressBar {
+    let bar = ProgressBar::new(len as u64);
+    bar.set_style(
+        ProgressStyle::default_bar().template("Downloading [{bar:40}] {pos}/{len} {msg}"),
+    );
+    bar
+}
+
+/// Runs a batch of downloads behind a progress bar, then lists every asset that failed
+pub fn download_with_progress<F>(len: usize, download: F) -> Downloads
+where
+    F: FnOnce(&(dyn Fn(&DownloadProgress) + Sync)) -> Downloads,
+{
+    let bar = progress_bar(len);
+    let downloads = download(&|progress: &DownloadProgress| {
+        bar.set_message(progress.name);
+        bar.inc(1);
+    });
+    bar.finish_and_clear();
+
+    for (name, error) in downloads.failed.iter() {
+        println!("Failed to download {}: {:?}", name, error);
+    }
+    downloads
+}
+
+/// Runs something that downloads an unknown number of assets, like a save, behind a progress bar sized from its
+/// progress reports. Assets that fail are listed as they do.
+pub fn with_progress<F, T>(download: F) -> T
+where
+    F: FnOnce(&(dyn Fn(&DownloadProgress) + Sync)) -> T,
+{
+    let bar = progress_bar(0);
+    let result = download(&|progress: &DownloadProgress| {
+        bar.set_length(progress.total as u64);
+        bar.set_message(progress.name);
+        if let Some(error) = progress.error {
+            bar.println(format!("Failed to download {}: {:?}", progress.name, error));
+        }
+        bar.inc(1);
+    });
+    bar.finish_and_clear();
+    result
+}
+
 pub struct DirCreater {
     path: PathBuf,
     logger: Box<DirCreaterLogger>,